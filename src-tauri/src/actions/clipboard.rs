@@ -0,0 +1,24 @@
+use std::process::{Command, Stdio};
+
+/// Tools tried in turn to read the clipboard: Wayland, X11, macOS.
+const READERS: &[(&str, &[&str])] = &[
+    ("wl-paste", &["--no-newline"]),
+    ("xclip", &["-selection", "clipboard", "-o"]),
+    ("xsel", &["--clipboard", "--output"]),
+    ("pbpaste", &[]),
+];
+
+/// Text on the system clipboard, if a reader is installed and it holds
+/// any. Blocking.
+pub fn read() -> Option<String> {
+    READERS.iter().find_map(|(cmd, args)| {
+        let output = Command::new(cmd)
+            .args(*args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        let text = String::from_utf8_lossy(&output.stdout).into_owned();
+        (output.status.success() && !text.trim().is_empty()).then_some(text)
+    })
+}
@@ -0,0 +1,250 @@
+use super::SemanticAction;
+use crate::config::{ActionParam, ButtonMapping, CustomAction};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// Built-in templates. They're written to the actions directory when
+/// missing, so editing the file there overrides one and deleting it
+/// restores the default.
+const DEFAULTS: &[(&str, &str)] = &[
+    ("context", include_str!("defaults/context.md")),
+    ("explain", include_str!("defaults/explain.md")),
+    ("fix", include_str!("defaults/fix.md")),
+    ("continue", include_str!("defaults/continue.md")),
+    ("plan", include_str!("defaults/plan.md")),
+    ("summarize", include_str!("defaults/summarize.md")),
+    ("voice", include_str!("defaults/voice.md")),
+];
+
+/// Placeholders and the context source their value comes from.
+const PLACEHOLDER_SOURCES: &[(&str, &str)] = &[
+    ("branch", "git"),
+    ("diff", "git"),
+    ("files", "git"),
+    ("history", "shell_history"),
+    ("output_tail", "terminal"),
+];
+
+#[derive(Debug, Default, Deserialize)]
+struct Frontmatter {
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    icon: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    gamepad: Option<String>,
+    #[serde(default)]
+    sources: Option<Vec<String>>,
+    #[serde(default)]
+    params: Vec<ActionParam>,
+}
+
+/// An action prompt loaded from `~/.deckmind/actions/<id>.md`:
+///
+/// ```markdown
+/// ---
+/// label: Review
+/// icon: R
+/// description: Review uncommitted changes
+/// gamepad: L2
+/// sources: [git]
+/// params:
+///   - name: focus
+///     type: choice
+///     options: [correctness, style]
+/// ---
+/// Review these changes on {branch} for {focus}:
+/// {diff}
+/// {#if output_tail}The last command printed:
+/// {output_tail}{/if}
+/// ```
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionTemplate {
+    pub id: String,
+    pub label: String,
+    pub icon: Option<String>,
+    pub description: Option<String>,
+    /// Button the action is bound to unless `button_mappings` already
+    /// binds the action or the button.
+    pub gamepad: Option<String>,
+    /// Context sources to collect; all enabled ones if unset.
+    pub sources: Option<Vec<String>>,
+    pub prompt: String,
+    /// Values to ask for before building the prompt.
+    pub params: Vec<ActionParam>,
+    /// Replaces one of the built-in actions.
+    pub builtin: bool,
+}
+
+impl ActionTemplate {
+    fn parse(id: &str, content: &str) -> Result<Self, String> {
+        let (front, body) = match split_frontmatter(content) {
+            Some((yaml, body)) => {
+                let front: Frontmatter = serde_yaml::from_str(yaml).map_err(|e| format!("Invalid frontmatter: {}", e))?;
+                (front, body)
+            }
+            None => (Frontmatter::default(), content),
+        };
+        Ok(ActionTemplate {
+            id: id.to_string(),
+            label: front.label.unwrap_or_else(|| id.to_string()),
+            icon: front.icon,
+            description: front.description,
+            gamepad: front.gamepad,
+            sources: front.sources,
+            prompt: body.trim().to_string(),
+            params: front.params,
+            builtin: DEFAULTS.iter().any(|(builtin, _)| *builtin == id),
+        })
+    }
+
+    /// A `custom_actions` entry from config.yaml.
+    pub fn from_custom(action: &CustomAction) -> Self {
+        ActionTemplate {
+            id: action.id.clone(),
+            label: action.label.clone(),
+            icon: action.icon.clone(),
+            description: action.description.clone(),
+            gamepad: None,
+            sources: action.sources.clone(),
+            prompt: action.prompt.clone(),
+            params: action.params.clone(),
+            builtin: false,
+        }
+    }
+
+    /// Sources to collect: the declared ones plus those the prompt's
+    /// placeholders read from. `None` means every enabled source.
+    pub fn sources(&self) -> Option<Vec<String>> {
+        required_sources(&self.prompt, self.sources.as_deref())
+    }
+}
+
+/// `declared` sources plus the ones `prompt`'s placeholders need, or
+/// `None` (every enabled source) when nothing is declared.
+pub fn required_sources(prompt: &str, declared: Option<&[String]>) -> Option<Vec<String>> {
+    let mut sources = declared?.to_vec();
+    for (name, source) in PLACEHOLDER_SOURCES {
+        let used = prompt.contains(&format!("{{{}}}", name)) || prompt.contains(&format!("{{#if {}}}", name));
+        if used && !sources.iter().any(|s| s == source) {
+            sources.push(source.to_string());
+        }
+    }
+    let uses_project = prompt.contains("{project.") || prompt.contains("{#if project.");
+    if uses_project && !sources.iter().any(|s| s == "project") {
+        sources.push("project".to_string());
+    }
+    Some(sources)
+}
+
+/// `(yaml, body)` when `content` opens with a `---` fenced block.
+fn split_frontmatter(content: &str) -> Option<(&str, &str)> {
+    let rest = content.strip_prefix("---\n").or_else(|| content.strip_prefix("---\r\n"))?;
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return Some((&rest[..offset], &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// Action templates: the built-ins, overridden or extended by files in
+/// the actions directory.
+pub struct ActionLibrary {
+    templates: Vec<ActionTemplate>,
+}
+
+impl ActionLibrary {
+    fn dir() -> PathBuf {
+        dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".deckmind")
+            .join("actions")
+    }
+
+    /// Only the built-in templates.
+    pub fn builtin() -> Self {
+        let templates = DEFAULTS
+            .iter()
+            .map(|(id, content)| ActionTemplate::parse(id, content).expect("built-in action template"))
+            .collect();
+        ActionLibrary { templates }
+    }
+
+    /// Read `~/.deckmind/actions/*.md`, first writing out any built-in
+    /// template that has no file. Files that fail to parse are skipped
+    /// (built-ins fall back to their default).
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        let dir = Self::dir();
+        fs::create_dir_all(&dir)?;
+        for (id, content) in DEFAULTS {
+            let path = dir.join(format!("{}.md", id));
+            if !path.exists() {
+                fs::write(&path, content)?;
+            }
+        }
+
+        let mut library = Self::builtin();
+        let mut custom = Vec::new();
+        for entry in fs::read_dir(&dir)?.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("md") {
+                continue;
+            }
+            let Some(id) = path.file_stem().and_then(|s| s.to_str()) else { continue };
+            let parsed = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| ActionTemplate::parse(id, &content));
+            match parsed {
+                Ok(template) => match library.templates.iter_mut().find(|t| t.id == template.id) {
+                    Some(builtin) => *builtin = template,
+                    None => custom.push(template),
+                },
+                Err(e) => log::warn!("Skipping action template {}: {}", path.display(), e),
+            }
+        }
+        custom.sort_by(|a, b| a.id.cmp(&b.id));
+        library.templates.extend(custom);
+        Ok(library)
+    }
+
+    /// Built-ins first, then the user's own actions by id.
+    pub fn list(&self) -> &[ActionTemplate] {
+        &self.templates
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ActionTemplate> {
+        self.templates.iter().find(|t| t.id == id)
+    }
+
+    /// The template `action` is built from; Interrupt and macros have none.
+    pub fn template_for(&self, action: &SemanticAction) -> Option<&ActionTemplate> {
+        match action {
+            SemanticAction::Interrupt | SemanticAction::Macro { .. } => None,
+            _ => self.get(action.id()),
+        }
+    }
+
+    /// Add the `gamepad` bindings templates declare, unless the action
+    /// or the button is already mapped.
+    pub fn bind_buttons(&self, mappings: &mut Vec<ButtonMapping>) {
+        for template in &self.templates {
+            let Some(button) = &template.gamepad else { continue };
+            let taken = mappings
+                .iter()
+                .any(|m| m.action == template.id || m.gamepad.as_deref() == Some(button.as_str()));
+            if !taken {
+                mappings.push(ButtonMapping {
+                    action: template.id.clone(),
+                    keyboard: None,
+                    gamepad: Some(button.clone()),
+                });
+            }
+        }
+    }
+}
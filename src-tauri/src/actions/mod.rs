@@ -1,5 +1,10 @@
+pub mod clipboard;
+mod library;
+pub mod params;
 mod router;
 mod templates;
 
+pub use library::{required_sources, ActionLibrary, ActionTemplate};
+pub use params::ActionForm;
 pub use router::ActionRouter;
 pub use templates::SemanticAction;
@@ -0,0 +1,160 @@
+use super::ActionTemplate;
+use crate::config::{ActionParam, ParamKind};
+use crate::git::run_git;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Files offered by a file parameter.
+const MAX_FILES: usize = 2000;
+
+/// Directories offered by a recent-dir parameter.
+const MAX_DIRS: usize = 20;
+
+/// A parameter as the controller UI asks for it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ParamField {
+    pub name: String,
+    pub label: String,
+    /// `text`, `choice`, `file`, `recent_dir` or `voice`.
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub default: Option<String>,
+    pub required: bool,
+    /// What can be picked: the choices, files relative to the session's
+    /// directory, or directories. Empty for text and voice.
+    pub options: Vec<String>,
+}
+
+/// What an action needs before its prompt can be built.
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionForm {
+    pub action_id: String,
+    pub label: String,
+    pub params: Vec<ParamField>,
+}
+
+/// The fields to fill in for `template`, with files listed from `dir`
+/// and `recent_dirs` (latest first) offered for directories. Blocking.
+pub fn form(template: &ActionTemplate, dir: &Path, recent_dirs: &[String]) -> Result<ActionForm, String> {
+    check_names(&template.params)?;
+    let params = template
+        .params
+        .iter()
+        .map(|param| {
+            let (kind, options) = match &param.kind {
+                ParamKind::Text => ("text", Vec::new()),
+                ParamKind::Choice { options } => ("choice", options.clone()),
+                ParamKind::File { extensions } => ("file", list_files(dir, extensions)),
+                ParamKind::RecentDir => ("recent_dir", recent_dirs.iter().take(MAX_DIRS).cloned().collect()),
+                ParamKind::Voice => ("voice", Vec::new()),
+            };
+            ParamField {
+                name: param.name.clone(),
+                label: label(param).to_string(),
+                kind,
+                default: param.default.clone(),
+                required: param.required,
+                options,
+            }
+        })
+        .collect();
+    Ok(ActionForm {
+        action_id: template.id.clone(),
+        label: template.label.clone(),
+        params,
+    })
+}
+
+/// Placeholder values for `params` from what the user `supplied`, falling
+/// back to defaults. Optional parameters left empty render as nothing.
+pub fn values(params: &[ActionParam], supplied: &HashMap<String, String>) -> Result<HashMap<String, String>, String> {
+    check_names(params)?;
+    let mut values = HashMap::new();
+    for param in params {
+        let value = supplied
+            .get(&param.name)
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .or(param.default.as_deref());
+        let Some(value) = value else {
+            if param.required {
+                return Err(format!("{} is required", label(param)));
+            }
+            values.insert(param.name.clone(), String::new());
+            continue;
+        };
+        if let ParamKind::Choice { options } = &param.kind {
+            if !options.iter().any(|o| o == value) {
+                return Err(format!("{} must be one of: {}", label(param), options.join(", ")));
+            }
+        }
+        values.insert(param.name.clone(), value.to_string());
+    }
+    Ok(values)
+}
+
+fn label(param: &ActionParam) -> &str {
+    param.label.as_deref().unwrap_or(&param.name)
+}
+
+/// Parameter names must be usable as `{name}` placeholders.
+fn check_names(params: &[ActionParam]) -> Result<(), String> {
+    static NAME: OnceLock<Regex> = OnceLock::new();
+    let name = NAME.get_or_init(|| Regex::new(r"^[a-z_][a-z0-9_]*$").expect("parameter name pattern"));
+    match params.iter().find(|p| !name.is_match(&p.name)) {
+        Some(param) => Err(format!("Invalid parameter name '{}': use lowercase letters, digits and _", param.name)),
+        None => Ok(()),
+    }
+}
+
+/// Files under `dir`, relative to it: what git tracks or would track,
+/// or a walk that skips hidden entries outside a repository.
+fn list_files(dir: &Path, extensions: &[String]) -> Vec<String> {
+    let wanted = |path: &str| {
+        extensions.is_empty()
+            || Path::new(path)
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|ext| extensions.iter().any(|x| x.trim_start_matches('.') == ext))
+    };
+    let mut files: Vec<String> = match run_git(dir, &["ls-files", "--cached", "--others", "--exclude-standard"]) {
+        Ok(listing) => listing.lines().filter(|f| wanted(f)).take(MAX_FILES).map(str::to_string).collect(),
+        Err(_) => walk(dir, &wanted),
+    };
+    files.sort();
+    files.dedup();
+    files
+}
+
+fn walk(root: &Path, wanted: &dyn Fn(&str) -> bool) -> Vec<String> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else { continue };
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(t) if t.is_dir() => pending.push(path),
+                Ok(t) if t.is_file() => {
+                    let Ok(relative) = path.strip_prefix(root) else { continue };
+                    let relative = relative.to_string_lossy();
+                    if wanted(&relative) {
+                        files.push(relative.into_owned());
+                        if files.len() >= MAX_FILES {
+                            return files;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    files
+}
@@ -1,12 +1,13 @@
-use super::templates::SemanticAction;
+use super::library::ActionTemplate;
 use crate::context::EnvironmentContext;
+use std::collections::HashMap;
 
 pub struct ActionRouter;
 
 impl ActionRouter {
-    pub fn build_prompt(action: &SemanticAction, context: &EnvironmentContext) -> String {
-        let template = action.template();
-        let context_str = context.to_prompt_string();
-        template.replace("{context}", &context_str)
+    /// Fill `template` from `context`; `vars` supplies `{input}`,
+    /// `{clipboard}` and the like.
+    pub fn build_prompt(template: &ActionTemplate, context: &EnvironmentContext, vars: &HashMap<String, String>) -> String {
+        context.fill_template_with(&template.prompt, vars)
     }
 }
@@ -1,3 +1,3 @@
 mod schema;
 
-pub use schema::{AppConfig, SafetyMode, ButtonMapping};
+pub use schema::{ActionParam, AppConfig, SafetyMode, ButtonMapping, ContextSourceConfig, CustomAction, InterruptConfig, MacroDef, MacroStepKind, ParamKind, RedactionConfig, WaitFor, WorktreePoolConfig};
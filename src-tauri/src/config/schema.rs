@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
 
@@ -39,6 +40,222 @@ pub struct CustomAction {
     #[serde(default)]
     pub icon: Option<String>,
     pub prompt: String,
+    /// Context sources to collect for `{context}`; all enabled ones if unset.
+    #[serde(default)]
+    pub sources: Option<Vec<String>>,
+    /// Values asked for before the prompt is built.
+    #[serde(default)]
+    pub params: Vec<ActionParam>,
+}
+
+/// A value an action asks for; it fills `{name}` in the prompt.
+///
+/// ```yaml
+/// params:
+///   - name: symbol
+///     type: text
+///   - name: scope
+///     type: choice
+///     options: [unit, integration]
+///     default: unit
+///   - name: file
+///     type: file
+///     extensions: [rs]
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionParam {
+    pub name: String,
+    /// Shown when asking; `name` if unset.
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(flatten)]
+    pub kind: ParamKind,
+    #[serde(default)]
+    pub default: Option<String>,
+    #[serde(default = "default_true")]
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ParamKind {
+    Text,
+    /// One of a fixed list.
+    Choice { options: Vec<String> },
+    /// A file in the session's directory, relative to it.
+    File {
+        /// Only offer files with these extensions.
+        #[serde(default)]
+        extensions: Vec<String>,
+    },
+    /// A directory sessions were recently opened in.
+    RecentDir,
+    /// Free text, dictated.
+    Voice,
+}
+
+/// Steps run one after another against a session, e.g. Fix, wait for
+/// the agent to finish, run the tests, and Fix again while they fail.
+///
+/// ```yaml
+/// macros:
+///   - id: fix-until-green
+///     label: Fix until green
+///     steps:
+///       - label: fix
+///         action: fix
+///       - wait: idle
+///       - shell: cargo test
+///         timeout_secs: 900
+///       - branch: { exit_code: 0, then: done, else: fix }
+///       - label: done
+///         notify: Tests pass
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MacroDef {
+    pub id: String,
+    pub label: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    pub steps: Vec<MacroStep>,
+    /// Most steps run in one go, so a loop that never settles stops.
+    #[serde(default = "default_macro_max_steps")]
+    pub max_steps: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MacroStep {
+    /// Name `branch` steps jump to.
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Limit for `wait` and `shell` steps; the macro fails when it's hit.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    #[serde(flatten)]
+    pub kind: MacroStepKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MacroStepKind {
+    /// Send an action's prompt: a built-in (`fix`) or a template id.
+    Action(String),
+    /// Send a prompt template. `{exit_code}` and `{output}` hold the last
+    /// `shell` or `wait` step's result.
+    Prompt(String),
+    Wait(WaitFor),
+    /// Run a command in the session's directory, outside its terminal.
+    Shell(String),
+    Branch(MacroBranch),
+    /// Show a message (a `macro-notify` event).
+    Notify(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WaitFor {
+    /// The agent is done: the session is quiet with no queued prompts.
+    Idle,
+    /// A new line of terminal output matches this regex.
+    Pattern(String),
+}
+
+/// Jump based on the last `shell` or `wait` step. Both conditions must
+/// hold when both are given.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MacroBranch {
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    /// Regex searched for in the step's output.
+    #[serde(default)]
+    pub matches: Option<String>,
+    /// Label to go to when the condition holds (`end` stops the macro);
+    /// the next step when unset.
+    #[serde(default)]
+    pub then: Option<String>,
+    /// Label to go to otherwise.
+    #[serde(default, rename = "else")]
+    pub otherwise: Option<String>,
+}
+
+/// Per-source overrides for context collection, keyed by source id
+/// (`git`, `shell_history`, `processes`, `ports`, ...).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextSourceConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Give up on the source after this long.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Position in the prompt; lower comes first.
+    #[serde(default)]
+    pub order: Option<i32>,
+}
+
+/// Scrubbing of secrets (tokens, passwords, keys) from context before
+/// it reaches the agent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedactionConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Extra regexes to redact on top of the built-in detectors. A named
+    /// group `keep` (or `rest`) is left in place, e.g. `(?P<keep>ticket=)\S+`.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        RedactionConfig {
+            enabled: true,
+            patterns: Vec::new(),
+        }
+    }
+}
+
+/// How long `interrupt_session` gives each step to stop the agent before
+/// escalating. Idle is only noticed after a second of quiet, so shorter
+/// waits only succeed when the agent exits.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InterruptConfig {
+    #[serde(default = "default_escape_wait")]
+    pub escape_wait_ms: u64,
+    #[serde(default = "default_ctrl_c_wait")]
+    pub ctrl_c_wait_ms: u64,
+    #[serde(default = "default_sigint_wait")]
+    pub sigint_wait_ms: u64,
+    #[serde(default = "default_sigterm_wait")]
+    pub sigterm_wait_ms: u64,
+    #[serde(default = "default_sigkill_wait")]
+    pub sigkill_wait_ms: u64,
+}
+
+impl Default for InterruptConfig {
+    fn default() -> Self {
+        InterruptConfig {
+            escape_wait_ms: default_escape_wait(),
+            ctrl_c_wait_ms: default_ctrl_c_wait(),
+            sigint_wait_ms: default_sigint_wait(),
+            sigterm_wait_ms: default_sigterm_wait(),
+            sigkill_wait_ms: default_sigkill_wait(),
+        }
+    }
+}
+
+/// Keep pre-created worktrees ready for a repository so new sessions
+/// can start instantly.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorktreePoolConfig {
+    pub repo: String,
+    /// Number of spare worktrees to keep.
+    #[serde(default = "default_pool_size")]
+    pub size: usize,
+    /// Shell command run in each new spare before it is offered
+    /// (e.g. `npm ci`).
+    #[serde(default)]
+    pub setup_command: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,6 +289,50 @@ pub struct AppConfig {
 
     #[serde(default)]
     pub custom_actions: Vec<CustomAction>,
+
+    #[serde(default)]
+    pub macros: Vec<MacroDef>,
+
+    /// Command run in each best-of-N worktree to compare candidates
+    /// (e.g. `cargo test`). No tests are run when unset.
+    #[serde(default)]
+    pub best_of_n_test_command: Option<String>,
+
+    /// Directory DeckMind creates worktrees under.
+    /// Defaults to `~/.deckmind/worktrees`.
+    #[serde(default)]
+    pub worktree_root: Option<String>,
+
+    /// Remove a session's managed worktree on close when it is clean.
+    #[serde(default)]
+    pub delete_worktrees_on_close: bool,
+
+    #[serde(default)]
+    pub worktree_pools: Vec<WorktreePoolConfig>,
+
+    /// Snapshot a session's working tree before each action or message
+    /// so it can be rolled back. Callers can override per request.
+    #[serde(default)]
+    pub auto_checkpoint: bool,
+
+    #[serde(default)]
+    pub context_sources: BTreeMap<String, ContextSourceConfig>,
+
+    /// Most characters of context (roughly four per token) pasted into
+    /// an action prompt.
+    #[serde(default = "default_context_budget")]
+    pub context_budget: usize,
+
+    /// Per-action overrides of `context_budget`, keyed by action id
+    /// (`fix`, `summarize`, or a custom action's id).
+    #[serde(default)]
+    pub action_context_budgets: BTreeMap<String, usize>,
+
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+
+    #[serde(default)]
+    pub interrupt: InterruptConfig,
 }
 
 fn default_claude_path() -> String {
@@ -86,6 +347,38 @@ fn default_true() -> bool {
     true
 }
 
+fn default_pool_size() -> usize {
+    2
+}
+
+fn default_macro_max_steps() -> usize {
+    100
+}
+
+fn default_escape_wait() -> u64 {
+    2000
+}
+
+fn default_ctrl_c_wait() -> u64 {
+    2000
+}
+
+fn default_sigint_wait() -> u64 {
+    3000
+}
+
+fn default_sigterm_wait() -> u64 {
+    5000
+}
+
+fn default_sigkill_wait() -> u64 {
+    2000
+}
+
+fn default_context_budget() -> usize {
+    6000
+}
+
 fn default_theme() -> String {
     "cyber".to_string()
 }
@@ -172,6 +465,17 @@ impl Default for AppConfig {
             voice_enabled: true,
             theme: default_theme(),
             custom_actions: Vec::new(),
+            macros: Vec::new(),
+            best_of_n_test_command: None,
+            worktree_root: None,
+            delete_worktrees_on_close: false,
+            worktree_pools: Vec::new(),
+            auto_checkpoint: false,
+            context_sources: BTreeMap::new(),
+            context_budget: default_context_budget(),
+            action_context_budgets: BTreeMap::new(),
+            redaction: RedactionConfig::default(),
+            interrupt: InterruptConfig::default(),
         }
     }
 }
@@ -182,6 +486,26 @@ impl AppConfig {
         base.join(".deckmind").join("config.yaml")
     }
 
+    /// Resolved root directory for managed worktrees.
+    pub fn worktree_root(&self) -> PathBuf {
+        match self.worktree_root {
+            Some(ref root) if !root.is_empty() => PathBuf::from(root),
+            _ => dirs::home_dir()
+                .unwrap_or_else(|| PathBuf::from("."))
+                .join(".deckmind")
+                .join("worktrees"),
+        }
+    }
+
+    /// Context budget for an action, or the default one for free-form
+    /// prompts.
+    pub fn context_budget_for(&self, action_id: Option<&str>) -> usize {
+        action_id
+            .and_then(|id| self.action_context_budgets.get(id))
+            .copied()
+            .unwrap_or(self.context_budget)
+    }
+
     pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
         let path = Self::config_path();
         if !path.exists() {
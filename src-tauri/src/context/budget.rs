@@ -0,0 +1,217 @@
+use super::collector::{overflow_line, ContextSection, SectionBody};
+
+/// Sections that would get less room than this are dropped instead.
+const MIN_SECTION_CHARS: usize = 80;
+
+/// Longest list item or text line kept whole; longer ones are cut in the
+/// middle.
+const MAX_LINE_CHARS: usize = 160;
+
+/// Shrink `sections` (most important first) so that, rendered and joined
+/// by blank lines, they take at most `budget` characters.
+pub(super) fn fit(sections: Vec<ContextSection>, budget: usize) -> Vec<ContextSection> {
+    let mut sections: Vec<ContextSection> = sections.into_iter().map(clamp_lines).collect();
+
+    loop {
+        // Each section is preceded by a blank-line separator
+        let available = budget.saturating_sub(sections.len() * 2);
+        let needs: Vec<usize> = sections.iter().map(|s| char_len(&s.render())).collect();
+        if needs.iter().sum::<usize>() <= available {
+            return sections;
+        }
+
+        let allowances = allocate(&needs, available);
+        let starved = needs
+            .iter()
+            .zip(&allowances)
+            .any(|(need, allowance)| allowance < need && *allowance < MIN_SECTION_CHARS);
+        if starved {
+            sections.pop();
+            continue;
+        }
+
+        return sections
+            .into_iter()
+            .zip(allowances)
+            .map(|(section, allowance)| shrink(section, allowance))
+            .collect();
+    }
+}
+
+/// Split `available` between sections: any that need less than an equal
+/// share get all they need, the rest split what's left evenly (the first
+/// of them getting the rounding remainder).
+fn allocate(needs: &[usize], available: usize) -> Vec<usize> {
+    let mut allowances = vec![0; needs.len()];
+    let mut open: Vec<usize> = (0..needs.len()).collect();
+    let mut remaining = available;
+
+    while !open.is_empty() {
+        let share = remaining / open.len();
+        let (fits, rest): (Vec<usize>, Vec<usize>) = open.iter().partition(|&&i| needs[i] <= share);
+        if fits.is_empty() {
+            for &i in &rest {
+                allowances[i] = share;
+            }
+            allowances[rest[0]] += remaining - share * rest.len();
+            break;
+        }
+        for i in fits {
+            allowances[i] = needs[i];
+            remaining -= needs[i];
+        }
+        open = rest;
+    }
+
+    allowances
+}
+
+/// Cut over-long list items and text lines regardless of budget.
+fn clamp_lines(mut section: ContextSection) -> ContextSection {
+    match &mut section.body {
+        SectionBody::Line { .. } => {}
+        SectionBody::Text { text, .. } => {
+            *text = text
+                .lines()
+                .map(|line| truncate_middle(line, MAX_LINE_CHARS))
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+        SectionBody::List { items, .. } => {
+            for item in items.iter_mut() {
+                *item = truncate_middle(item, MAX_LINE_CHARS);
+            }
+        }
+    }
+    section
+}
+
+/// Make `section` render within `limit` characters.
+fn shrink(mut section: ContextSection, limit: usize) -> ContextSection {
+    if char_len(&section.render()) <= limit {
+        return section;
+    }
+    // "Title: " or "Title:\n"
+    let room = limit.saturating_sub(char_len(&section.title) + 2);
+
+    let body = match section.body {
+        SectionBody::Line { text } => SectionBody::Line {
+            text: truncate_middle(&text, room),
+        },
+        SectionBody::Text { text, keep_last } => {
+            let lines: Vec<&str> = text.lines().collect();
+            let text = if keep_last {
+                keep_tail(&lines, room)
+            } else {
+                keep_head_and_tail(&lines, room)
+            };
+            SectionBody::Text { text, keep_last }
+        }
+        SectionBody::List { marker, mut items, omitted, keep_last } => {
+            let total = items.len() + omitted;
+            let reserve = char_len(&overflow_line(&section.title, total)) + 1;
+            if keep_last {
+                items.reverse();
+            }
+            let mut used = 0;
+            let mut kept = Vec::new();
+            for item in items {
+                let len = char_len(&item) + char_len(&marker) + 4;
+                if used + len + reserve > room {
+                    break;
+                }
+                used += len;
+                kept.push(item);
+            }
+            if keep_last {
+                kept.reverse();
+            }
+            SectionBody::List {
+                omitted: total - kept.len(),
+                marker,
+                items: kept,
+                keep_last,
+            }
+        }
+    };
+
+    section.body = body;
+    section
+}
+
+/// Keep as many leading lines as fit, plus the last line (which for
+/// output like `git diff --stat` is the summary), with a marker for the
+/// lines in between.
+fn keep_head_and_tail(lines: &[&str], room: usize) -> String {
+    let total: usize = lines.iter().map(|l| char_len(l) + 1).sum();
+    if total <= room + 1 || lines.len() < 2 {
+        return truncate_middle(&lines.join("\n"), room);
+    }
+
+    let last = lines[lines.len() - 1];
+    let marker_len = char_len(&format!("… {} more lines", lines.len())) + 1;
+    let mut budget = room.saturating_sub(char_len(last) + marker_len);
+    let mut head = Vec::new();
+    for line in &lines[..lines.len() - 1] {
+        let len = char_len(line) + 1;
+        if len > budget {
+            break;
+        }
+        budget -= len;
+        head.push(*line);
+    }
+
+    let skipped = lines.len() - 1 - head.len();
+    let mut out = head;
+    let marker = format!("… {} more lines", skipped);
+    out.push(&marker);
+    out.push(last);
+    out.join("\n")
+}
+
+/// Keep as many trailing lines as fit, after a marker for the earlier
+/// ones.
+fn keep_tail(lines: &[&str], room: usize) -> String {
+    let marker_len = char_len(&format!("… {} earlier lines", lines.len())) + 1;
+    let mut budget = room.saturating_sub(marker_len);
+    let mut tail = Vec::new();
+    for line in lines.iter().rev() {
+        let len = char_len(line) + 1;
+        if len > budget {
+            break;
+        }
+        budget -= len;
+        tail.push(*line);
+    }
+    if tail.is_empty() {
+        return truncate_middle(lines.last().copied().unwrap_or_default(), room);
+    }
+    tail.reverse();
+
+    let marker = format!("… {} earlier lines", lines.len() - tail.len());
+    let mut out = vec![marker.as_str()];
+    out.extend(tail);
+    out.join("\n")
+}
+
+/// `src/some/very/long/path/file.rs` → `src/so…/path/file.rs`, keeping
+/// more of the end, which tends to be the informative part.
+fn truncate_middle(s: &str, max: usize) -> String {
+    let len = char_len(s);
+    if len <= max {
+        return s.to_string();
+    }
+    if max == 0 {
+        return String::new();
+    }
+    let keep = max - 1;
+    let head = keep / 3;
+    let tail = keep - head;
+    let start: String = s.chars().take(head).collect();
+    let end: String = s.chars().skip(len - tail).collect();
+    format!("{}…{}", start, end)
+}
+
+fn char_len(s: &str) -> usize {
+    s.chars().count()
+}
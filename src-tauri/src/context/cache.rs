@@ -0,0 +1,170 @@
+use super::ContextSection;
+use crate::git::run_git;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A tree with more directories than this isn't cached; watching it would
+/// use up the user's inotify watches.
+const MAX_WATCHED_DIRS: usize = 4000;
+
+/// How long cached sections are reused when nothing changes.
+const TTL: Duration = Duration::from_secs(10);
+
+/// Sections from cacheable sources, per directory, dropped when files
+/// under the directory (outside what git ignores) change.
+pub(super) struct DirCache {
+    dirs: Mutex<HashMap<String, CachedDir>>,
+}
+
+struct CachedDir {
+    created: Instant,
+    /// Set by the watcher when something under the directory changes.
+    changed: Arc<AtomicBool>,
+    /// `None` if the directory couldn't be watched; nothing is cached then.
+    watcher: Option<RecommendedWatcher>,
+    sections: HashMap<String, (Instant, Vec<ContextSection>)>,
+}
+
+impl CachedDir {
+    /// Watching a large tree takes a while; call off the async runtime.
+    fn watch(dir: &str) -> Self {
+        let changed = Arc::new(AtomicBool::new(false));
+        let flag = changed.clone();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else { return };
+            let modifies = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_));
+            if modifies && event.paths.iter().any(|p| !is_git_bookkeeping(p)) {
+                flag.store(true, Ordering::Relaxed);
+            }
+        })
+        .map_err(|e| e.to_string())
+        .and_then(|mut watcher| {
+            for (path, mode) in watch_list(Path::new(dir))? {
+                // Directories can disappear between listing and watching
+                if let Err(e) = watcher.watch(&path, mode) {
+                    if path.exists() {
+                        return Err(e.to_string());
+                    }
+                }
+            }
+            Ok(watcher)
+        });
+        if let Err(e) = &watcher {
+            log::warn!("Not caching context for {}: {}", dir, e);
+        }
+        CachedDir {
+            created: Instant::now(),
+            changed,
+            watcher: watcher.ok(),
+            sections: HashMap::new(),
+        }
+    }
+
+    fn is_live(&self) -> bool {
+        self.created.elapsed() < TTL || self.sections.values().any(|(at, _)| at.elapsed() < TTL)
+    }
+}
+
+impl DirCache {
+    pub fn new() -> Self {
+        DirCache { dirs: Mutex::new(HashMap::new()) }
+    }
+
+    /// Sections `source` produced for `dir` if they're recent and nothing
+    /// under `dir` has changed since.
+    pub fn get(&self, dir: &str, source: &str) -> Option<Vec<ContextSection>> {
+        let mut dirs = self.dirs.lock().ok()?;
+        let cached = dirs.get_mut(dir)?;
+        if cached.changed.swap(false, Ordering::Relaxed) {
+            cached.sections.clear();
+        }
+        match cached.sections.get(source) {
+            Some((at, sections)) if at.elapsed() < TTL => Some(sections.clone()),
+            _ => None,
+        }
+    }
+
+    /// Remember `source`'s sections for `dir`, starting a watcher for the
+    /// directory if there isn't one. Blocks while the watcher starts.
+    pub fn put(&self, dir: &str, source: &str, sections: Vec<ContextSection>) {
+        let watched = match self.dirs.lock() {
+            Ok(mut dirs) => {
+                // Stop watching directories nothing was cached for lately
+                dirs.retain(|_, cached| cached.is_live());
+                dirs.contains_key(dir)
+            }
+            Err(_) => return,
+        };
+        let fresh = (!watched).then(|| CachedDir::watch(dir));
+
+        let Ok(mut dirs) = self.dirs.lock() else { return };
+        let cached = match fresh {
+            Some(fresh) => dirs.entry(dir.to_string()).or_insert(fresh),
+            None => match dirs.get_mut(dir) {
+                Some(cached) => cached,
+                None => return,
+            },
+        };
+        if cached.watcher.is_some() {
+            cached.sections.insert(source.to_string(), (Instant::now(), sections));
+        }
+    }
+}
+
+/// What to watch for changes that affect `dir`'s sources: each directory
+/// git would list files in, so ignored trees such as `target/` or
+/// `node_modules/` add no watches, plus the repository's HEAD and refs.
+/// Outside a repository, `dir` itself.
+fn watch_list(dir: &Path) -> Result<Vec<(PathBuf, RecursiveMode)>, String> {
+    let Ok(listing) = run_git(dir, &["ls-files", "-z", "--cached", "--others", "--exclude-standard"]) else {
+        return Ok(vec![(dir.to_path_buf(), RecursiveMode::NonRecursive)]);
+    };
+    let mut dirs: BTreeSet<PathBuf> = BTreeSet::from([dir.to_path_buf()]);
+    for file in listing.split('\0').filter(|f| !f.is_empty()) {
+        let mut parent = Path::new(file).parent();
+        while let Some(p) = parent.filter(|p| !p.as_os_str().is_empty()) {
+            if !dirs.insert(dir.join(p)) {
+                break;
+            }
+            parent = p.parent();
+        }
+    }
+    if dirs.len() > MAX_WATCHED_DIRS {
+        return Err(format!("more than {} directories", MAX_WATCHED_DIRS));
+    }
+
+    let mut list: Vec<_> = dirs.into_iter().map(|d| (d, RecursiveMode::NonRecursive)).collect();
+    let git_dir = run_git(dir, &["rev-parse", "--absolute-git-dir"])?;
+    let common_dir = run_git(dir, &["rev-parse", "--path-format=absolute", "--git-common-dir"])?;
+    list.push((PathBuf::from(git_dir), RecursiveMode::NonRecursive));
+    list.push((Path::new(&common_dir).join("refs"), RecursiveMode::Recursive));
+    Ok(list)
+}
+
+/// Files git rewrites while only reading the repository (`git status`
+/// refreshes the index), which would otherwise invalidate the cache on
+/// every collection.
+fn is_git_bookkeeping(path: &Path) -> bool {
+    let mut components = path.components().skip_while(|c| *c != Component::Normal(".git".as_ref()));
+    if components.next().is_none() {
+        return false;
+    }
+    // Linked worktrees keep their index under .git/worktrees/<name>/
+    let inner: Vec<_> = components.collect();
+    if let Some(Component::Normal(first)) = inner.first() {
+        if *first == "objects" || *first == "logs" {
+            return true;
+        }
+    }
+    match inner.last() {
+        Some(Component::Normal(name)) => {
+            let name = name.to_string_lossy();
+            name.starts_with("index") || name.ends_with(".lock")
+        }
+        _ => false,
+    }
+}
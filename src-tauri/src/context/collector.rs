@@ -1,136 +1,392 @@
+use super::budget;
+use super::cache::DirCache;
+use super::redact::Redactor;
+use super::source::{ContextSource, ContextTarget};
+use super::sources;
+use super::template::{self, Value};
+use crate::config::ContextSourceConfig;
+use crate::git::status::RepoState;
+use crate::procfs::{ListeningPort, ProcessInfo};
+use crate::project::ProjectInfo;
 use serde::{Deserialize, Serialize};
-use std::process::Command;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
 
+/// Template placeholder for `EnvironmentContext::changes_since_last_visit`.
+pub const VISIT_PLACEHOLDER: &str = "{changes_since_last_visit}";
+
+/// Text of a section whose content took too long to collect.
+const TIMED_OUT: &str = "(timed out)";
+
+/// How a section's content is laid out in the prompt.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct EnvironmentContext {
-    pub cwd: String,
-    pub git_branch: Option<String>,
-    pub git_diff_summary: Option<String>,
-    pub modified_files: Vec<String>,
-    pub recent_commands: Vec<String>,
-    pub last_exit_code: Option<i32>,
-    pub running_processes: Vec<String>,
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SectionBody {
+    /// `Title: text`
+    Line { text: String },
+    /// `Title:` followed by the text on its own lines.
+    Text {
+        text: String,
+        /// Trim from the front instead of the middle (command output,
+        /// where the end matters most).
+        #[serde(default)]
+        keep_last: bool,
+    },
+    /// `Title:` followed by one `  <marker> item` line per item, plus
+    /// `  …and N more <noun>` when items were left out to fit the budget.
+    List {
+        marker: String,
+        items: Vec<String>,
+        #[serde(default)]
+        omitted: usize,
+        /// Trim from the front instead of the back (newest-last lists).
+        #[serde(default)]
+        keep_last: bool,
+    },
 }
 
-impl EnvironmentContext {
-    pub fn to_prompt_string(&self) -> String {
-        let mut parts = vec![format!("Directory: {}", self.cwd)];
+/// A titled block of context produced by a source.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextSection {
+    /// Id of the source that produced it.
+    pub source: String,
+    pub title: String,
+    pub body: SectionBody,
+    /// What the section was rendered from, moved into the matching
+    /// `EnvironmentContext` field when the context is collected.
+    #[serde(skip)]
+    pub data: Option<SectionData>,
+}
 
-        if let Some(ref branch) = self.git_branch {
-            parts.push(format!("Git branch: {}", branch));
-        }
+/// Typed data a source attaches to one of its sections.
+#[derive(Debug, Clone)]
+pub enum SectionData {
+    Project(ProjectInfo),
+    Processes(Vec<ProcessInfo>),
+    Ports(Vec<ListeningPort>),
+    Git(RepoState),
+}
 
-        if !self.modified_files.is_empty() {
-            parts.push(format!(
-                "Modified files:\n{}",
-                self.modified_files
-                    .iter()
-                    .map(|f| format!("  - {}", f))
-                    .collect::<Vec<_>>()
-                    .join("\n")
-            ));
+impl ContextSection {
+    pub fn line(source: &str, title: &str, text: impl Into<String>) -> Self {
+        Self::new(source, title, SectionBody::Line { text: text.into() })
+    }
+
+    pub fn text(source: &str, title: &str, text: impl Into<String>) -> Self {
+        Self::new(source, title, SectionBody::Text { text: text.into(), keep_last: false })
+    }
+
+    /// Text whose end is kept when it's trimmed, such as command output.
+    pub fn output(source: &str, title: &str, text: impl Into<String>) -> Self {
+        Self::new(source, title, SectionBody::Text { text: text.into(), keep_last: true })
+    }
+
+    pub fn list(source: &str, title: &str, marker: &str, items: Vec<String>) -> Self {
+        Self::new(source, title, SectionBody::List {
+            marker: marker.to_string(),
+            items,
+            omitted: 0,
+            keep_last: false,
+        })
+    }
+
+    /// A list ordered oldest first, so the end is kept when it's trimmed.
+    pub fn recent_list(source: &str, title: &str, marker: &str, items: Vec<String>) -> Self {
+        Self::new(source, title, SectionBody::List {
+            marker: marker.to_string(),
+            items,
+            omitted: 0,
+            keep_last: true,
+        })
+    }
+
+    /// Stands in for content that took too long to collect.
+    pub fn timed_out(source: &str, title: &str) -> Self {
+        Self::line(source, title, TIMED_OUT)
+    }
+
+    pub fn is_timed_out(&self) -> bool {
+        matches!(&self.body, SectionBody::Line { text } if text == TIMED_OUT)
+    }
+
+    fn new(source: &str, title: &str, body: SectionBody) -> Self {
+        ContextSection {
+            source: source.to_string(),
+            title: title.to_string(),
+            body,
+            data: None,
         }
+    }
+
+    pub fn with_data(mut self, data: SectionData) -> Self {
+        self.data = Some(data);
+        self
+    }
 
-        if let Some(ref diff) = self.git_diff_summary {
-            parts.push(format!("Git diff summary:\n{}", diff));
+    pub(super) fn render(&self) -> String {
+        match &self.body {
+            SectionBody::Line { text } => format!("{}: {}", self.title, text),
+            _ => format!("{}:\n{}", self.title, self.content()),
         }
+    }
 
-        if !self.recent_commands.is_empty() {
-            parts.push(format!(
-                "Recent commands:\n{}",
-                self.recent_commands
+    /// The section without its title.
+    fn content(&self) -> String {
+        match &self.body {
+            SectionBody::Line { text } | SectionBody::Text { text, .. } => text.clone(),
+            SectionBody::List { marker, items, omitted, keep_last } => {
+                let mut lines: Vec<String> = items
                     .iter()
-                    .map(|c| format!("  $ {}", c))
-                    .collect::<Vec<_>>()
-                    .join("\n")
-            ));
+                    .map(|item| format!("  {} {}", marker, item))
+                    .collect();
+                if *omitted > 0 {
+                    let overflow = overflow_line(&self.title, *omitted);
+                    if *keep_last {
+                        lines.insert(0, overflow);
+                    } else {
+                        lines.push(overflow);
+                    }
+                }
+                lines.join("\n")
+            }
         }
+    }
+}
 
-        if let Some(code) = self.last_exit_code {
-            parts.push(format!("Last exit code: {}", code));
-        }
+/// `  …and 240 more files`, named after the last word of the title.
+pub(super) fn overflow_line(title: &str, omitted: usize) -> String {
+    let noun = title.rsplit(' ').next().unwrap_or("items").to_lowercase();
+    format!("  …and {} more {}", omitted, noun)
+}
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvironmentContext {
+    pub cwd: String,
+    pub sections: Vec<ContextSection>,
+    /// Detected project type, for `{project.*}` placeholders.
+    #[serde(default)]
+    pub project: Option<ProjectInfo>,
+    /// Processes running in the session, from the `processes` source.
+    #[serde(default)]
+    pub running_processes: Vec<ProcessInfo>,
+    /// Ports the session listens on, from the `ports` source.
+    #[serde(default)]
+    pub listening_ports: Vec<ListeningPort>,
+    /// Branch, upstream, pending operation and the like, from the `git`
+    /// source. `None` outside a repository.
+    #[serde(default)]
+    pub git: Option<RepoState>,
+    /// What changed since the user last viewed the project, for
+    /// `{changes_since_last_visit}`. Only filled in when a template asks.
+    #[serde(default)]
+    pub changes_since_last_visit: Option<String>,
+}
+
+impl EnvironmentContext {
+    pub fn to_prompt_string(&self) -> String {
+        let mut parts = vec![self.header()];
+        parts.extend(self.sections.iter().map(ContextSection::render));
         parts.join("\n\n")
     }
+
+    /// Shrink the sections so `to_prompt_string` is at most `budget`
+    /// characters (roughly four per token). Earlier sections have priority:
+    /// later ones are dropped first when there isn't room for everything.
+    pub fn fit(mut self, budget: usize) -> Self {
+        let available = budget.saturating_sub(self.header().chars().count());
+        self.sections = budget::fit(self.sections, available);
+        self
+    }
+
+    fn header(&self) -> String {
+        format!("Directory: {}", self.cwd)
+    }
+
+    /// Fill a prompt template; see `fill_template_with`.
+    pub fn fill_template(&self, template: &str) -> String {
+        self.fill_template_with(template, &HashMap::new())
+    }
+
+    /// Fill a prompt template from this context and `vars` (such as
+    /// `input` and `clipboard`), which take precedence. Besides `vars`,
+    /// templates can use `{context}`, `{changes_since_last_visit}`,
+    /// `{cwd}`, `{branch}`, `{diff}`, `{files}`, `{history}`,
+    /// `{output_tail}` and `{project.<field>}` (e.g. `{project.test_cmd}`),
+    /// and `{#if name}…{else}…{/if}` blocks that check whether a value is
+    /// non-empty. Project fields that aren't known become `unknown`.
+    pub fn fill_template_with(&self, template: &str, vars: &HashMap<String, String>) -> String {
+        template::render(template, |name| match vars.get(name) {
+            Some(value) => Some(Value::Text(value.clone())),
+            None => self.placeholder(name),
+        })
+    }
+
+    fn placeholder(&self, name: &str) -> Option<Value> {
+        let text = |text: Option<String>| Some(text.map_or(Value::Unset(""), Value::Text));
+        match name {
+            "context" => text(Some(self.to_prompt_string())),
+            "changes_since_last_visit" => Some(match &self.changes_since_last_visit {
+                Some(changes) => Value::Text(changes.clone()),
+                None => Value::Unset("No earlier visit recorded."),
+            }),
+            "cwd" => text(Some(self.cwd.clone())),
+            "branch" => text(self.git.as_ref().map(RepoState::describe_head)),
+            "diff" => text(self.section_content(&["Git diff summary"])),
+            "files" => text(self.section_content(&["Modified files"])),
+            "history" => text(self.section_content(&["Recent commands"])),
+            "output_tail" => text(self.section_content(&["Command output", "Terminal output"])),
+            "input" | "clipboard" => text(None),
+            _ => {
+                let field = name.strip_prefix("project.")?;
+                Some(match self.project.as_ref().and_then(|project| project.field(field)) {
+                    Some(value) => Value::Text(value),
+                    None => Value::Unset("unknown"),
+                })
+            }
+        }
+    }
+
+    /// Content of the first section with one of `titles`, unless it
+    /// timed out.
+    fn section_content(&self, titles: &[&str]) -> Option<String> {
+        titles.iter().find_map(|title| {
+            self.sections
+                .iter()
+                .find(|s| s.title == *title && !s.is_timed_out())
+                .map(ContextSection::content)
+        })
+    }
 }
 
-pub struct ContextCollector;
+/// Settings for one source, resolved against its defaults.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceStatus {
+    pub id: String,
+    pub enabled: bool,
+    pub order: i32,
+    pub timeout_ms: u64,
+}
+
+/// Registry of context sources.
+pub struct ContextCollector {
+    sources: Vec<Arc<dyn ContextSource>>,
+    cache: Arc<DirCache>,
+}
 
 impl ContextCollector {
-    pub async fn collect() -> EnvironmentContext {
-        let cwd = std::env::current_dir()
-            .map(|p| p.display().to_string())
-            .unwrap_or_else(|_| "unknown".to_string());
-
-        let git_branch = Self::run_command("git", &["rev-parse", "--abbrev-ref", "HEAD"]);
-        let git_diff_summary = Self::run_command("git", &["diff", "--stat"]);
-
-        let modified_files = Self::run_command("git", &["status", "--porcelain"])
-            .map(|output| {
-                output
-                    .lines()
-                    .map(|l| l.to_string())
-                    .collect::<Vec<_>>()
-            })
-            .unwrap_or_default();
+    /// A collector with the built-in sources registered.
+    pub fn new() -> Self {
+        let mut collector = ContextCollector {
+            sources: Vec::new(),
+            cache: Arc::new(DirCache::new()),
+        };
+        sources::register_builtin(&mut collector);
+        collector
+    }
 
-        let recent_commands = Self::get_recent_shell_history(20);
+    /// Add a source, replacing any registered under the same id.
+    pub fn register(&mut self, source: Arc<dyn ContextSource>) {
+        self.sources.retain(|s| s.id() != source.id());
+        self.sources.push(source);
+    }
 
-        EnvironmentContext {
-            cwd,
-            git_branch,
-            git_diff_summary,
-            modified_files,
-            recent_commands,
-            last_exit_code: None,
-            running_processes: Vec::new(),
-        }
+    /// Every registered source with its effective settings, in order.
+    pub fn sources(&self, settings: &BTreeMap<String, ContextSourceConfig>) -> Vec<SourceStatus> {
+        let mut statuses: Vec<SourceStatus> = self
+            .sources
+            .iter()
+            .map(|source| {
+                let config = settings.get(source.id());
+                SourceStatus {
+                    id: source.id().to_string(),
+                    enabled: !matches!(config, Some(c) if !c.enabled),
+                    order: config.and_then(|c| c.order).unwrap_or_else(|| source.default_order()),
+                    timeout_ms: config
+                        .and_then(|c| c.timeout_ms)
+                        .unwrap_or_else(|| source.default_timeout().as_millis() as u64),
+                }
+            })
+            .collect();
+        statuses.sort_by(|a, b| (a.order, &a.id).cmp(&(b.order, &b.id)));
+        statuses
     }
 
-    fn run_command(cmd: &str, args: &[&str]) -> Option<String> {
-        Command::new(cmd)
-            .args(args)
-            .output()
-            .ok()
-            .filter(|o| o.status.success())
-            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
-    }
-
-    fn get_recent_shell_history(count: usize) -> Vec<String> {
-        // Try zsh history first, then bash
-        let history_file = dirs::home_dir()
-            .map(|h| h.join(".zsh_history"))
-            .filter(|p| p.exists())
-            .or_else(|| {
-                dirs::home_dir()
-                    .map(|h| h.join(".bash_history"))
-                    .filter(|p| p.exists())
+    /// Run every enabled source (restricted to `only`, if given)
+    /// concurrently and redact secrets from the result. A source that
+    /// outlives its timeout is replaced by a "timed out" line. Cacheable
+    /// sources reuse what they produced for the directory until something
+    /// in it changes.
+    pub async fn collect(
+        &self,
+        target: ContextTarget,
+        settings: &BTreeMap<String, ContextSourceConfig>,
+        only: Option<&[&str]>,
+        redactor: &Redactor,
+    ) -> EnvironmentContext {
+        let target = Arc::new(target);
+        let runs = self
+            .sources(settings)
+            .into_iter()
+            .filter(|status| match only {
+                Some(ids) => status.enabled && ids.contains(&status.id.as_str()),
+                None => status.enabled,
+            })
+            .filter_map(|status| {
+                let source = self.sources.iter().find(|s| s.id() == status.id)?.clone();
+                let target = target.clone();
+                let cache = source.cacheable().then(|| self.cache.clone());
+                Some(async move {
+                    if let Some(sections) = cache.as_ref().and_then(|c| c.get(&target.cwd, &status.id)) {
+                        return sections;
+                    }
+                    let timeout = Duration::from_millis(status.timeout_ms);
+                    let sections = match tokio::time::timeout(timeout, source.collect(&target)).await {
+                        Ok(sections) => sections,
+                        Err(_) => {
+                            log::warn!("Context source {} timed out after {:?}", status.id, timeout);
+                            return vec![ContextSection::timed_out(&status.id, &source_title(&status.id))];
+                        }
+                    };
+                    if let Some(cache) = cache.filter(|_| !sections.iter().any(ContextSection::is_timed_out)) {
+                        let (dir, id, cached) = (target.cwd.clone(), status.id.clone(), sections.clone());
+                        tokio::task::spawn_blocking(move || cache.put(&dir, &id, cached));
+                    }
+                    sections
+                })
             });
 
-        if let Some(path) = history_file {
-            if let Ok(content) = std::fs::read_to_string(&path) {
-                return content
-                    .lines()
-                    .rev()
-                    .take(count)
-                    .map(|line| {
-                        // Strip zsh history metadata (: timestamp:0;command)
-                        if line.starts_with(": ") {
-                            line.splitn(3, ';')
-                                .nth(1)
-                                .unwrap_or(line)
-                                .to_string()
-                        } else {
-                            line.to_string()
-                        }
-                    })
-                    .collect::<Vec<_>>()
-                    .into_iter()
-                    .rev()
-                    .collect();
+        let mut sections: Vec<ContextSection> = futures_util::future::join_all(runs).await.into_iter().flatten().collect();
+
+        let mut context = EnvironmentContext {
+            cwd: target.cwd.clone(),
+            sections: Vec::new(),
+            project: None,
+            running_processes: Vec::new(),
+            listening_ports: Vec::new(),
+            git: None,
+            changes_since_last_visit: None,
+        };
+        for data in sections.iter_mut().filter_map(|s| s.data.take()) {
+            match data {
+                SectionData::Project(project) => context.project = Some(project),
+                SectionData::Processes(processes) => context.running_processes = processes,
+                SectionData::Ports(ports) => context.listening_ports = ports,
+                SectionData::Git(state) => context.git = Some(state),
             }
         }
+        context.sections = sections;
+        redactor.redact_context(&mut context);
+        context
+    }
+}
 
-        Vec::new()
+/// `shell_history` → `Shell history`, for a source with no sections.
+fn source_title(id: &str) -> String {
+    let text = id.replace('_', " ");
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => text,
     }
 }
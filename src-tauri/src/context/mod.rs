@@ -1,3 +1,13 @@
+mod budget;
+mod cache;
 mod collector;
+mod redact;
+mod source;
+mod sources;
+mod template;
+pub mod visits;
 
-pub use collector::{ContextCollector, EnvironmentContext};
+pub use collector::{ContextCollector, ContextSection, EnvironmentContext, SectionData, SourceStatus, VISIT_PLACEHOLDER};
+pub use redact::Redactor;
+pub use source::{ContextSource, ContextTarget};
+pub use sources::TestResultsSource;
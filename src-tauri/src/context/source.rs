@@ -0,0 +1,67 @@
+use super::ContextSection;
+use crate::procfs::ProcessScope;
+use crate::session::output::TerminalOutput;
+use futures_util::future::BoxFuture;
+use std::time::Duration;
+
+/// Where context is being collected for.
+#[derive(Debug, Clone)]
+pub struct ContextTarget {
+    pub cwd: String,
+    /// Session the prompt is for, if any.
+    pub session_id: Option<String>,
+    /// The session's processes (see `SessionManager::process_scope`).
+    pub scope: ProcessScope,
+    /// Shell the session runs in; `$SHELL` when unknown.
+    pub shell: Option<String>,
+    /// The shell's process, to read the environment it set up.
+    pub shell_pid: Option<u32>,
+    /// The session's recent terminal output.
+    pub terminal: Option<TerminalOutput>,
+}
+
+impl ContextTarget {
+    /// Falls back to the process cwd when no directory is given.
+    pub fn new(working_dir: Option<&str>, session_id: Option<&str>, scope: ProcessScope) -> Self {
+        let cwd = match working_dir {
+            Some(dir) => dir.to_string(),
+            None => std::env::current_dir()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|_| "unknown".to_string()),
+        };
+        ContextTarget {
+            cwd,
+            session_id: session_id.map(str::to_string),
+            scope,
+            shell: std::env::var("SHELL").ok(),
+            shell_pid: None,
+            terminal: None,
+        }
+    }
+}
+
+/// One kind of environment information that can be added to prompts.
+///
+/// `collect` runs concurrently with the other sources and is dropped once
+/// the source's timeout passes, so it must not block: run commands with
+/// `tokio::process` and move file or `/proc` reads to the blocking pool.
+pub trait ContextSource: Send + Sync {
+    /// Stable name used in `context_sources` config and by actions that
+    /// ask for specific sources.
+    fn id(&self) -> &'static str;
+
+    /// Position among sources when the config doesn't set one. Lower first.
+    fn default_order(&self) -> i32;
+
+    fn default_timeout(&self) -> Duration {
+        Duration::from_secs(3)
+    }
+
+    /// Whether the sections depend only on the files under the target
+    /// directory, so they can be reused until something there changes.
+    fn cacheable(&self) -> bool {
+        false
+    }
+
+    fn collect<'a>(&'a self, target: &'a ContextTarget) -> BoxFuture<'a, Vec<ContextSection>>;
+}
@@ -0,0 +1,89 @@
+use super::{run_command, CommandError};
+use crate::context::{ContextSection, ContextSource, ContextTarget, SectionData};
+use crate::git::status::RepoState;
+use futures_util::future::BoxFuture;
+use std::path::Path;
+
+/// Branch and upstream, in-progress operations, recent commits, modified
+/// files and diff summary.
+pub struct GitSource;
+
+impl ContextSource for GitSource {
+    fn id(&self) -> &'static str {
+        "git"
+    }
+
+    fn default_order(&self) -> i32 {
+        10
+    }
+
+    fn cacheable(&self) -> bool {
+        true
+    }
+
+    fn collect<'a>(&'a self, target: &'a ContextTarget) -> BoxFuture<'a, Vec<ContextSection>> {
+        Box::pin(async move {
+            let cwd = target.cwd.as_str();
+            // Without optional locks, a `git status` killed on timeout
+            // can't leave a stale index.lock behind
+            let git = |args: &'static [&'static str]| run_command(cwd, "git", args);
+            let (status, git_dir, stashes, log, diff) = tokio::join!(
+                git(&["--no-optional-locks", "status", "--porcelain=v2", "--branch"]),
+                git(&["--no-optional-locks", "rev-parse", "--absolute-git-dir"]),
+                git(&["--no-optional-locks", "stash", "list"]),
+                git(&["--no-optional-locks", "log", "-5", "--no-color", "--format=%h %s"]),
+                git(&["--no-optional-locks", "diff", "--stat"]),
+            );
+
+            let mut sections = Vec::new();
+            let state = match status {
+                // Not a repository
+                Err(CommandError::Failed) => return sections,
+                Err(CommandError::TimedOut) => {
+                    sections.push(ContextSection::timed_out(self.id(), "Git status"));
+                    None
+                }
+                Ok(status) => Some(RepoState::parse(
+                    &status,
+                    git_dir.as_deref().ok().map(Path::new),
+                    stashes.as_deref().ok(),
+                    log.as_deref().ok(),
+                )),
+            };
+
+            if let Some(state) = &state {
+                let branch = ContextSection::line(self.id(), "Git branch", state.describe_head());
+                sections.push(branch.with_data(SectionData::Git(state.clone())));
+                if let Some(operation) = &state.operation {
+                    sections.push(ContextSection::line(self.id(), "Git operation", operation.describe()));
+                }
+                if !state.conflicts.is_empty() {
+                    sections.push(ContextSection::list(self.id(), "Conflicted files", "!", state.conflicts.clone()));
+                }
+                if log == Err(CommandError::TimedOut) {
+                    sections.push(ContextSection::timed_out(self.id(), "Recent commits"));
+                } else if !state.recent_commits.is_empty() {
+                    sections.push(ContextSection::list(self.id(), "Recent commits", "-", state.recent_commits.clone()));
+                }
+                if !state.changes.is_empty() {
+                    sections.push(ContextSection::list(self.id(), "Modified files", "-", state.changes.clone()));
+                }
+            }
+
+            match diff {
+                Ok(diff) if !diff.is_empty() => {
+                    sections.push(ContextSection::text(self.id(), "Git diff summary", diff));
+                }
+                Err(CommandError::TimedOut) => {
+                    sections.push(ContextSection::timed_out(self.id(), "Git diff summary"));
+                }
+                _ => {}
+            }
+
+            if let Some(state) = state.filter(|s| s.stash_count > 0) {
+                sections.push(ContextSection::line(self.id(), "Stashes", format!("{} stashed change set(s)", state.stash_count)));
+            }
+            sections
+        })
+    }
+}
@@ -0,0 +1,375 @@
+use super::blocking;
+use crate::context::{ContextSection, ContextSource, ContextTarget};
+use crate::procfs;
+use futures_util::future::BoxFuture;
+use rusqlite::{Connection, OpenFlags};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// How many history lines to include.
+const HISTORY_LINES: usize = 20;
+
+/// Only the end of a history file is read; older commands are never shown.
+const TAIL_BYTES: u64 = 512 * 1024;
+
+/// zsh escapes bytes that clash with its tokens as this byte followed by
+/// the original XOR 0x20.
+const ZSH_META: u8 = 0x83;
+
+/// Recent commands from the user's shell history.
+pub struct ShellHistorySource;
+
+impl ContextSource for ShellHistorySource {
+    fn id(&self) -> &'static str {
+        "shell_history"
+    }
+
+    fn default_order(&self) -> i32 {
+        20
+    }
+
+    fn collect<'a>(&'a self, target: &'a ContextTarget) -> BoxFuture<'a, Vec<ContextSection>> {
+        let id = self.id();
+        Box::pin(blocking(target, move |target| {
+            let commands = get_recent_shell_history(target, HISTORY_LINES);
+            if commands.is_empty() {
+                return Vec::new();
+            }
+            vec![ContextSection::recent_list(id, "Recent commands", "$", commands)]
+        }))
+    }
+}
+
+/// A command and, when the history format records it, where it ran.
+struct Entry {
+    command: String,
+    /// Directory the command ran in (atuin).
+    cwd: Option<String>,
+    /// Paths the command referred to, relative to where it ran (fish).
+    paths: Vec<String>,
+}
+
+impl Entry {
+    fn new(command: String) -> Self {
+        Entry { command, cwd: None, paths: Vec::new() }
+    }
+
+    /// Whether the command was run in or is about `dir`. Entries that
+    /// don't say where they ran are kept.
+    fn relevant_to(&self, dir: &Path) -> bool {
+        if let Some(cwd) = &self.cwd {
+            return Path::new(cwd).starts_with(dir);
+        }
+        if self.paths.is_empty() {
+            return true;
+        }
+        self.paths.iter().any(|p| {
+            let path = Path::new(p);
+            if path.is_absolute() {
+                path.starts_with(dir)
+            } else {
+                dir.join(path).exists()
+            }
+        })
+    }
+}
+
+/// The environment of the session's shell as its rc files left it, or
+/// DeckMind's own without a session.
+struct ShellEnv(Option<u32>);
+
+impl ShellEnv {
+    /// Commands the shell started see what its rc files exported; the
+    /// shell's own `/proc` environment predates them, so it comes last.
+    fn var(&self, name: &str) -> Option<String> {
+        let value = match self.0 {
+            Some(shell) => {
+                let mut pids = procfs::process_tree(&[shell]);
+                pids.retain(|&pid| pid != shell);
+                pids.push(shell);
+                pids.into_iter().find_map(|pid| procfs::env_var(pid, name))
+            }
+            None => std::env::var(name).ok(),
+        };
+        value.filter(|v| !v.is_empty())
+    }
+}
+
+/// Atuin's database when the shell records into it (it keeps every
+/// command with its directory), otherwise the history of the session's
+/// shell.
+pub(crate) fn get_recent_shell_history(target: &ContextTarget, count: usize) -> Vec<String> {
+    let dir = Path::new(&target.cwd);
+    let env = ShellEnv(target.shell_pid);
+    let entries = match atuin_db(&env).and_then(|db| atuin(&db, dir, count)) {
+        Some(entries) => entries,
+        None => {
+            let shell = target
+                .shell
+                .as_deref()
+                .and_then(|s| Path::new(s).file_name())
+                .and_then(|s| s.to_str())
+                .unwrap_or("bash");
+            match shell {
+                "fish" => read_tail(&fish_history_file(&env)).map(|h| fish(&h)),
+                "zsh" => read_tail(&histfile(&env, ".zsh_history")).map(|h| zsh(&unmetafy(&h))),
+                _ => read_tail(&histfile(&env, ".bash_history")).map(|h| bash(&String::from_utf8_lossy(&h))),
+            }
+            .unwrap_or_default()
+        }
+    };
+
+    let mut commands: Vec<String> = Vec::new();
+    for entry in entries.into_iter().rev().filter(|e| e.relevant_to(dir)) {
+        let command = entry.command.trim();
+        // Repeats say nothing new
+        if command.is_empty() || commands.last().is_some_and(|last| last == command) {
+            continue;
+        }
+        commands.push(command.to_string());
+        if commands.len() == count {
+            break;
+        }
+    }
+    commands.reverse();
+    commands
+}
+
+/// The shell's `$HISTFILE` if set, else its default file in the home
+/// directory.
+fn histfile(env: &ShellEnv, default: &str) -> PathBuf {
+    match env.var("HISTFILE") {
+        Some(path) => PathBuf::from(path),
+        None => dirs::home_dir().unwrap_or_default().join(default),
+    }
+}
+
+fn data_dir(env: &ShellEnv) -> PathBuf {
+    match env.var("XDG_DATA_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => dirs::home_dir().unwrap_or_default().join(".local/share"),
+    }
+}
+
+fn fish_history_file(env: &ShellEnv) -> PathBuf {
+    data_dir(env).join("fish").join("fish_history")
+}
+
+/// Atuin's shell hook exports `ATUIN_SESSION`; without it the database
+/// may exist but not hold this shell's commands.
+fn atuin_db(env: &ShellEnv) -> Option<PathBuf> {
+    env.var("ATUIN_SESSION")?;
+    let path = match env.var("ATUIN_DB_PATH") {
+        Some(path) => PathBuf::from(path),
+        None => data_dir(env).join("atuin").join("history.db"),
+    };
+    path.is_file().then_some(path)
+}
+
+/// The end of a history file, starting at a line boundary.
+fn read_tail(path: &Path) -> Option<Vec<u8>> {
+    let mut file = File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    let start = len.saturating_sub(TAIL_BYTES);
+    file.seek(SeekFrom::Start(start)).ok()?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).ok()?;
+    if start > 0 {
+        // Drop the partial first line
+        let first = bytes.iter().position(|&b| b == b'\n').map_or(bytes.len(), |i| i + 1);
+        bytes.drain(..first);
+    }
+    Some(bytes)
+}
+
+/// Oldest first, within `dir` (or below it).
+fn atuin(db: &Path, dir: &Path, count: usize) -> Option<Vec<Entry>> {
+    let conn = Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX).ok()?;
+    let dir = dir.display().to_string();
+    let prefix = format!("{}/", dir.trim_end_matches('/'));
+    // Fetch extra rows so consecutive repeats can be dropped
+    let limit = (count * 4) as i64;
+    let mut stmt = conn
+        .prepare(
+            "SELECT command, cwd FROM history
+             WHERE deleted_at IS NULL AND (cwd = ?1 OR substr(cwd, 1, length(?2)) = ?2)
+             ORDER BY timestamp DESC LIMIT ?3",
+        )
+        .ok()?;
+    let rows = stmt
+        .query_map(rusqlite::params![dir, prefix, limit], |row| {
+            Ok(Entry {
+                command: row.get(0)?,
+                cwd: Some(row.get(1)?),
+                paths: Vec::new(),
+            })
+        })
+        .ok()?;
+    let mut entries: Vec<Entry> = rows.filter_map(Result::ok).collect();
+    entries.reverse();
+    Some(entries)
+}
+
+/// fish's YAML-like history:
+///
+/// ```text
+/// - cmd: cargo test -p core
+///   when: 1700000000
+///   paths:
+///     - core/src/lib.rs
+/// ```
+///
+/// Newlines and backslashes in commands are escaped as `\n` and `\\`.
+fn fish(history: &[u8]) -> Vec<Entry> {
+    let text = String::from_utf8_lossy(history);
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut in_paths = false;
+    for line in text.lines() {
+        if let Some(cmd) = line.strip_prefix("- cmd: ") {
+            entries.push(Entry::new(unescape_fish(cmd)));
+            in_paths = false;
+        } else if line.trim_start() == "paths:" {
+            in_paths = true;
+        } else if let Some(path) = line.trim_start().strip_prefix("- ").filter(|_| in_paths) {
+            if let Some(entry) = entries.last_mut() {
+                entry.paths.push(unescape_fish(path));
+            }
+        } else {
+            in_paths = false;
+        }
+    }
+    entries
+}
+
+fn unescape_fish(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Undo zsh's metafication so multi-byte characters decode.
+fn unmetafy(bytes: &[u8]) -> String {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter();
+    while let Some(&b) = iter.next() {
+        if b == ZSH_META {
+            if let Some(&next) = iter.next() {
+                out.push(next ^ 0x20);
+            }
+        } else {
+            out.push(b);
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Plain lines or extended history (`: <start>:<duration>;<command>`).
+/// A line ending in a backslash continues on the next one.
+fn zsh(history: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut current: Option<String> = None;
+    for line in history.lines() {
+        let text = match current.take() {
+            Some(mut command) => {
+                command.push('\n');
+                command.push_str(line);
+                command
+            }
+            None => match line.strip_prefix(": ").and_then(|rest| rest.split_once(';')) {
+                Some((_, command)) => command.to_string(),
+                None => line.to_string(),
+            },
+        };
+        match text.strip_suffix('\\') {
+            Some(continued) => current = Some(continued.to_string()),
+            None => entries.push(Entry::new(text)),
+        }
+    }
+    entries.extend(current.map(Entry::new));
+    entries
+}
+
+/// One command per line; `#<timestamp>` lines appear with `HISTTIMEFORMAT`.
+fn bash(history: &str) -> Vec<Entry> {
+    history
+        .lines()
+        .filter(|line| {
+            let stamp = line.strip_prefix('#');
+            !matches!(stamp, Some(s) if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .map(|line| Entry::new(line.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(entries: &[Entry]) -> Vec<&str> {
+        entries.iter().map(|e| e.command.as_str()).collect()
+    }
+
+    #[test]
+    fn fish_history() {
+        let history = b"- cmd: cargo test -p core\n  when: 1700000000\n  paths:\n    - core/src/lib.rs\n- cmd: echo a\\\\nb\\nls\n  when: 1700000001\n";
+        let entries = fish(history);
+        assert_eq!(commands(&entries), ["cargo test -p core", "echo a\\nb\nls"]);
+        assert_eq!(entries[0].paths, ["core/src/lib.rs"]);
+        assert!(entries[1].paths.is_empty());
+    }
+
+    #[test]
+    fn zsh_extended_history_with_multiline_entries() {
+        let history = ": 1700000000:0;git status\n: 1700000005:3;for f in *.rs; do\\\n  wc -l $f\\\ndone\nplain command\n";
+        assert_eq!(
+            commands(&zsh(history)),
+            ["git status", "for f in *.rs; do\n  wc -l $f\ndone", "plain command"],
+        );
+    }
+
+    #[test]
+    fn zsh_metafied_bytes() {
+        // "é" is 0xC3 0xA9; zsh stores 0xA9 as 0x83 0x89
+        assert_eq!(unmetafy(&[b'c', b'a', b'f', 0xC3, ZSH_META, 0x89]), "café");
+    }
+
+    #[test]
+    fn bash_history_skips_timestamps() {
+        let history = "#1700000000\nmake\n#not a stamp\nls -la\n";
+        assert_eq!(commands(&bash(history)), ["make", "#not a stamp", "ls -la"]);
+    }
+
+    #[test]
+    fn atuin_history_in_directory() {
+        let db = std::env::temp_dir().join(format!("deckmind-atuin-{}.db", uuid::Uuid::new_v4()));
+        let conn = Connection::open(&db).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE history (command TEXT, cwd TEXT, timestamp INTEGER, deleted_at INTEGER);
+             INSERT INTO history VALUES ('cargo build', '/work/app', 1, NULL);
+             INSERT INTO history VALUES ('cd src', '/work/app/src', 2, NULL);
+             INSERT INTO history VALUES ('rm -rf /', '/work/app', 3, 4);
+             INSERT INTO history VALUES ('ls', '/work/application', 5, NULL);
+             INSERT INTO history VALUES ('cargo test', '/work/app', 6, NULL);",
+        )
+        .unwrap();
+        let entries = atuin(&db, Path::new("/work/app"), 10).unwrap();
+        let _ = std::fs::remove_file(&db);
+
+        assert_eq!(commands(&entries), ["cargo build", "cd src", "cargo test"]);
+        assert_eq!(entries[1].cwd.as_deref(), Some("/work/app/src"));
+        assert!(entries[0].relevant_to(Path::new("/work/app")));
+        assert!(!entries[0].relevant_to(Path::new("/work/other")));
+    }
+}
@@ -0,0 +1,64 @@
+mod git;
+mod history;
+mod process;
+mod project;
+mod terminal;
+mod test_results;
+
+use super::{ContextCollector, ContextSection, ContextTarget};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+
+pub(crate) use history::get_recent_shell_history;
+pub use test_results::TestResultsSource;
+
+pub fn register_builtin(collector: &mut ContextCollector) {
+    collector.register(Arc::new(project::ProjectSource));
+    collector.register(Arc::new(terminal::TerminalSource));
+    collector.register(Arc::new(git::GitSource));
+    collector.register(Arc::new(history::ShellHistorySource));
+    collector.register(Arc::new(process::ProcessSource));
+    collector.register(Arc::new(process::PortSource));
+}
+
+/// How long a single command may run before it's killed.
+const COMMAND_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Why a command gave no output.
+#[derive(Debug, PartialEq)]
+enum CommandError {
+    /// Missing, or exited unsuccessfully.
+    Failed,
+    TimedOut,
+}
+
+/// Run a command in `dir` and return its trimmed stdout if it succeeded.
+/// The command is killed after `COMMAND_TIMEOUT`.
+async fn run_command(dir: &str, cmd: &str, args: &[&str]) -> Result<String, CommandError> {
+    let output = Command::new(cmd)
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    match tokio::time::timeout(COMMAND_TIMEOUT, output).await {
+        Ok(Ok(output)) if output.status.success() => {
+            Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        }
+        Ok(_) => Err(CommandError::Failed),
+        Err(_) => Err(CommandError::TimedOut),
+    }
+}
+
+/// Run a source's file or `/proc` reads on the blocking pool.
+async fn blocking<F>(target: &ContextTarget, collect: F) -> Vec<ContextSection>
+where
+    F: FnOnce(&ContextTarget) -> Vec<ContextSection> + Send + 'static,
+{
+    let target = target.clone();
+    tokio::task::spawn_blocking(move || collect(&target))
+        .await
+        .unwrap_or_default()
+}
@@ -0,0 +1,78 @@
+use super::blocking;
+use crate::context::{ContextSection, ContextSource, ContextTarget, SectionData};
+use crate::procfs;
+use futures_util::future::BoxFuture;
+
+/// Processes running in the session, excluding the shell and the agent.
+pub struct ProcessSource;
+
+impl ContextSource for ProcessSource {
+    fn id(&self) -> &'static str {
+        "processes"
+    }
+
+    fn default_order(&self) -> i32 {
+        30
+    }
+
+    fn collect<'a>(&'a self, target: &'a ContextTarget) -> BoxFuture<'a, Vec<ContextSection>> {
+        let id = self.id();
+        Box::pin(blocking(target, move |target| {
+            let processes = procfs::processes(&target.scope);
+            if processes.is_empty() {
+                return Vec::new();
+            }
+            let items = processes
+                .iter()
+                .map(|p| format!(
+                    "[{}] {} ({}, up {}, {:.0}% CPU)",
+                    p.pid,
+                    p.command,
+                    p.state,
+                    format_age(p.age_secs),
+                    p.cpu_percent
+                ))
+                .collect();
+            let section = ContextSection::list(id, "Running processes", "-", items);
+            vec![section.with_data(SectionData::Processes(processes))]
+        }))
+    }
+}
+
+/// TCP ports the session's processes listen on (e.g. dev servers).
+pub struct PortSource;
+
+impl ContextSource for PortSource {
+    fn id(&self) -> &'static str {
+        "ports"
+    }
+
+    fn default_order(&self) -> i32 {
+        40
+    }
+
+    fn collect<'a>(&'a self, target: &'a ContextTarget) -> BoxFuture<'a, Vec<ContextSection>> {
+        let id = self.id();
+        Box::pin(blocking(target, move |target| {
+            let ports = procfs::listening_ports(&target.scope.roots);
+            if ports.is_empty() {
+                return Vec::new();
+            }
+            let items = ports
+                .iter()
+                .map(|p| format!(":{} {} (pid {}, {})", p.port, p.command, p.pid, p.address))
+                .collect();
+            let section = ContextSection::list(id, "Listening ports", "-", items);
+            vec![section.with_data(SectionData::Ports(ports))]
+        }))
+    }
+}
+
+/// `75` → `1m15s`, `7300` → `2h1m`.
+fn format_age(secs: u64) -> String {
+    match secs {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m{}s", s / 60, s % 60),
+        s => format!("{}h{}m", s / 3600, s % 3600 / 60),
+    }
+}
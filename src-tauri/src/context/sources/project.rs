@@ -0,0 +1,48 @@
+use super::blocking;
+use crate::context::{ContextSection, ContextSource, ContextTarget, SectionData};
+use crate::project::ProjectInfo;
+use futures_util::future::BoxFuture;
+use std::path::Path;
+
+/// Project type and the commands to test, build and lint it. Also what
+/// `{project.*}` placeholders read, so they are unknown while it's off.
+pub struct ProjectSource;
+
+impl ContextSource for ProjectSource {
+    fn id(&self) -> &'static str {
+        "project"
+    }
+
+    fn default_order(&self) -> i32 {
+        5
+    }
+
+    fn cacheable(&self) -> bool {
+        true
+    }
+
+    fn collect<'a>(&'a self, target: &'a ContextTarget) -> BoxFuture<'a, Vec<ContextSection>> {
+        let id = self.id();
+        Box::pin(blocking(target, move |target| {
+            let Some(project) = ProjectInfo::detect(Path::new(&target.cwd)) else {
+                return Vec::new();
+            };
+            let summary = ContextSection::line(id, "Project", project.describe());
+
+            let commands: Vec<String> = [
+                ("test", &project.test_cmd),
+                ("build", &project.build_cmd),
+                ("lint", &project.lint_cmd),
+            ]
+            .into_iter()
+            .filter_map(|(kind, cmd)| cmd.as_ref().map(|cmd| format!("{}: {}", kind, cmd)))
+            .collect();
+            let mut sections = vec![summary.with_data(SectionData::Project(project))];
+            if !commands.is_empty() {
+                sections.push(ContextSection::list(id, "Project commands", "-", commands));
+            }
+
+            sections
+        }))
+    }
+}
@@ -0,0 +1,60 @@
+use crate::context::{ContextSection, ContextSource, ContextTarget};
+use futures_util::future::BoxFuture;
+
+/// Lines of output included; the budget trims from the top.
+const LINES: usize = 50;
+
+/// What the session's terminal showed last: the latest command's output
+/// when the shell marks commands (OSC 133), otherwise the last lines.
+pub struct TerminalSource;
+
+impl ContextSource for TerminalSource {
+    fn id(&self) -> &'static str {
+        "terminal"
+    }
+
+    fn default_order(&self) -> i32 {
+        9
+    }
+
+    fn collect<'a>(&'a self, target: &'a ContextTarget) -> BoxFuture<'a, Vec<ContextSection>> {
+        Box::pin(async move { self.sections(target) })
+    }
+}
+
+impl TerminalSource {
+    fn sections(&self, target: &ContextTarget) -> Vec<ContextSection> {
+        let Some(terminal) = &target.terminal else {
+            return Vec::new();
+        };
+
+        if let Some(command) = terminal.last_command.as_ref().filter(|c| !c.command.is_empty()) {
+            let state = match (command.finished, command.exit_code) {
+                (false, _) => "still running".to_string(),
+                (true, Some(code)) => format!("exit {}", code),
+                (true, None) => "finished".to_string(),
+            };
+            let mut sections = vec![ContextSection::line(
+                self.id(),
+                "Last command",
+                format!("`{}` ({})", command.command, state),
+            )];
+            if let Some(output) = tail(&command.output) {
+                sections.push(ContextSection::output(self.id(), "Command output", output));
+            }
+            return sections;
+        }
+
+        tail(&terminal.lines)
+            .map(|output| vec![ContextSection::output(self.id(), "Terminal output", output)])
+            .unwrap_or_default()
+    }
+}
+
+/// The last `LINES` lines, without leading or trailing blank lines.
+fn tail(lines: &[String]) -> Option<String> {
+    let end = lines.iter().rposition(|l| !l.trim().is_empty())? + 1;
+    let lines = &lines[end.saturating_sub(LINES)..end];
+    let start = lines.iter().position(|l| !l.trim().is_empty()).unwrap_or(0);
+    Some(lines[start..].join("\n"))
+}
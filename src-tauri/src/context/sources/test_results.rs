@@ -0,0 +1,90 @@
+use crate::context::{ContextSection, ContextSource, ContextTarget};
+use crate::testing::{TestReport, TestRunner, TestStatus};
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use std::sync::Arc;
+
+/// Output lines shown when a failed run's results couldn't be parsed.
+const OUTPUT_LINES: usize = 30;
+
+/// The session's latest test run and its failures.
+pub struct TestResultsSource {
+    runner: Arc<TestRunner>,
+}
+
+impl TestResultsSource {
+    pub fn new(runner: Arc<TestRunner>) -> Self {
+        TestResultsSource { runner }
+    }
+}
+
+impl ContextSource for TestResultsSource {
+    fn id(&self) -> &'static str {
+        "test_results"
+    }
+
+    fn default_order(&self) -> i32 {
+        8
+    }
+
+    fn collect<'a>(&'a self, target: &'a ContextTarget) -> BoxFuture<'a, Vec<ContextSection>> {
+        Box::pin(async move { self.sections(target) })
+    }
+}
+
+impl TestResultsSource {
+    fn sections(&self, target: &ContextTarget) -> Vec<ContextSection> {
+        let Some(report) = target.session_id.as_deref().and_then(|id| self.runner.latest(id)) else {
+            return Vec::new();
+        };
+        let mut sections = vec![ContextSection::line(self.id(), "Test results", summary(&report))];
+
+        if !report.failures.is_empty() {
+            let text = report
+                .failures
+                .iter()
+                .map(|f| {
+                    let location = match (&f.file, f.line) {
+                        (Some(file), Some(line)) => format!(" ({}:{})", file, line),
+                        (Some(file), None) => format!(" ({})", file),
+                        _ => String::new(),
+                    };
+                    let message: Vec<String> = f.message.lines().map(|l| format!("  {}", l)).collect();
+                    format!("{}{}\n{}", f.name, location, message.join("\n"))
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            sections.push(ContextSection::text(self.id(), "Test failures", text));
+        } else if report.status == TestStatus::Failed {
+            let lines: Vec<&str> = report.output_tail.lines().collect();
+            let start = lines.len().saturating_sub(OUTPUT_LINES);
+            sections.push(ContextSection::output(self.id(), "Test output", lines[start..].join("\n")));
+        }
+
+        sections
+    }
+}
+
+/// `` `cargo test` failed 3m ago: 2 failed, 40 passed, 1 ignored ``
+fn summary(report: &TestReport) -> String {
+    let counts = format!("{} failed, {} passed, {} ignored", report.failed, report.passed, report.ignored);
+    let ago = report
+        .finished_at
+        .map(|at| {
+            let mins = (Utc::now() - at).num_minutes();
+            if mins < 1 {
+                "just now".to_string()
+            } else {
+                format!("{}m ago", mins)
+            }
+        })
+        .unwrap_or_default();
+    match report.status {
+        TestStatus::Running => format!("`{}` running: {} so far", report.command, counts),
+        TestStatus::Passed => format!("`{}` passed {}: {}", report.command, ago, counts),
+        TestStatus::Failed => format!("`{}` failed {}: {}", report.command, ago, counts),
+        TestStatus::TimedOut => format!("`{}` timed out {}: {}", report.command, ago, counts),
+        TestStatus::Cancelled => format!("`{}` cancelled {}: {}", report.command, ago, counts),
+        TestStatus::Error => format!("`{}` could not start: {}", report.command, report.output_tail),
+    }
+}
@@ -0,0 +1,122 @@
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// What a placeholder stands for.
+pub enum Value {
+    Text(String),
+    /// Nothing to show; the text is printed in its place and `{#if}`
+    /// treats it as false.
+    Unset(&'static str),
+}
+
+impl Value {
+    fn is_set(&self) -> bool {
+        matches!(self, Value::Text(text) if !text.trim().is_empty())
+    }
+}
+
+/// One level of `{#if}` nesting.
+struct Branch {
+    /// The enclosing text is being printed.
+    outer: bool,
+    condition: bool,
+    in_else: bool,
+}
+
+impl Branch {
+    fn printing(&self) -> bool {
+        self.outer && (self.condition != self.in_else)
+    }
+}
+
+/// Fill `{name}` placeholders and evaluate `{#if name}…{else}…{/if}`
+/// blocks in one pass, so substituted text is never expanded again.
+/// `lookup` returns `None` for names it doesn't know; those are left as
+/// they are, and are false in conditions.
+pub fn render(template: &str, lookup: impl Fn(&str) -> Option<Value>) -> String {
+    static TOKEN: OnceLock<Regex> = OnceLock::new();
+    let token = TOKEN.get_or_init(|| {
+        Regex::new(r"\{(?:#if ([a-z_][a-z0-9_.]*)|(else)|(/if)|([a-z_][a-z0-9_.]*))\}").expect("template token pattern")
+    });
+
+    let mut out = String::with_capacity(template.len());
+    let mut branches: Vec<Branch> = Vec::new();
+    let printing = |branches: &[Branch]| branches.last().is_none_or(Branch::printing);
+    let mut last = 0;
+
+    for caps in token.captures_iter(template) {
+        let whole = caps.get(0).expect("whole match");
+        if printing(&branches) {
+            out.push_str(&template[last..whole.start()]);
+        }
+        last = whole.end();
+
+        if let Some(name) = caps.get(1) {
+            let outer = printing(&branches);
+            let condition = outer && lookup(name.as_str()).is_some_and(|v| v.is_set());
+            branches.push(Branch { outer, condition, in_else: false });
+        } else if caps.get(2).is_some() {
+            match branches.last_mut() {
+                Some(branch) => branch.in_else = true,
+                None => out.push_str(whole.as_str()),
+            }
+        } else if caps.get(3).is_some() {
+            if branches.pop().is_none() {
+                out.push_str(whole.as_str());
+            }
+        } else if printing(&branches) {
+            match lookup(&caps[4]) {
+                Some(Value::Text(text)) => out.push_str(&text),
+                Some(Value::Unset(text)) => out.push_str(text),
+                None => out.push_str(whole.as_str()),
+            }
+        }
+    }
+    if printing(&branches) {
+        out.push_str(&template[last..]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(template: &str) -> String {
+        render(template, |name| match name {
+            "branch" => Some(Value::Text("main".to_string())),
+            "diff" => Some(Value::Text("{branch} {#if branch}x{/if}".to_string())),
+            "files" => Some(Value::Text("  ".to_string())),
+            "project.name" => Some(Value::Unset("unknown")),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(fill("On {branch}, {project.name}, {missing}"), "On main, unknown, {missing}");
+        // Substituted text is not expanded again
+        assert_eq!(fill("{diff}"), "{branch} {#if branch}x{/if}");
+    }
+
+    #[test]
+    fn conditions() {
+        assert_eq!(fill("{#if branch}on {branch}{else}detached{/if}."), "on main.");
+        assert_eq!(fill("{#if files}changed{else}clean{/if}"), "clean");
+        assert_eq!(fill("{#if project.name}named{else}{project.name}{/if}"), "unknown");
+        assert_eq!(fill("{#if missing}yes{/if}no"), "no");
+    }
+
+    #[test]
+    fn nested_conditions() {
+        let template = "{#if branch}A{#if files}B{else}C{#if diff}D{/if}{/if}E{else}F{#if branch}G{else}H{/if}{/if}";
+        assert_eq!(fill(template), "ACDE");
+        let template = "{#if files}A{#if branch}B{else}C{/if}{else}D{#if missing}E{else}F{/if}G{/if}";
+        assert_eq!(fill(template), "DFG");
+    }
+
+    #[test]
+    fn stray_tags_are_kept() {
+        assert_eq!(fill("a {else} b {/if} c"), "a {else} b {/if} c");
+    }
+}
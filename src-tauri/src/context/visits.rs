@@ -0,0 +1,136 @@
+use super::sources::get_recent_shell_history;
+use super::ContextTarget;
+use crate::git::run_git;
+use crate::project::ProjectInfo;
+use crate::session::SessionInfo;
+use crate::storage::{SessionMark, VisitSnapshot};
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Shell commands kept per snapshot.
+const COMMANDS: usize = 20;
+
+/// Items listed per kind of change before the rest are counted.
+const MAX_ITEMS: usize = 10;
+
+/// Key visits are stored under: the project's root, or the directory
+/// itself outside a recognised project.
+pub fn project_key(dir: &str) -> String {
+    ProjectInfo::detect(Path::new(dir))
+        .map(|p| p.root)
+        .unwrap_or_else(|| dir.to_string())
+}
+
+/// Record the state of `target`'s directory and which of `sessions` are
+/// open in its `project`. Blocking.
+pub fn capture(target: &ContextTarget, project: &str, sessions: &[SessionInfo]) -> VisitSnapshot {
+    let dir = Path::new(&target.cwd);
+    let status = run_git(dir, &["--no-optional-locks", "status", "--short"]).unwrap_or_default();
+    VisitSnapshot {
+        taken_at: Utc::now(),
+        head: run_git(dir, &["rev-parse", "HEAD"]).ok(),
+        changes: status.lines().map(str::to_string).collect(),
+        commands: get_recent_shell_history(target, COMMANDS),
+        sessions: sessions
+            .iter()
+            .filter(|s| matches!(&s.working_dir, Some(wd) if Path::new(wd).starts_with(project)))
+            .map(|s| SessionMark {
+                id: s.id.clone(),
+                name: s.name.clone(),
+            })
+            .collect(),
+    }
+}
+
+/// What changed between the user's last visit and `now`, as prompt text.
+/// Blocking.
+pub fn describe_changes(dir: &str, last: &VisitSnapshot, now: &VisitSnapshot) -> String {
+    let mut parts = vec![format!("Last visit: {} ago", format_elapsed(last.taken_at, now.taken_at))];
+
+    if last.head != now.head {
+        let commits = match (&last.head, &now.head) {
+            (Some(old), Some(_)) => {
+                let range = format!("{}..HEAD", old);
+                run_git(Path::new(dir), &["log", "--no-color", "--max-count=100", "--format=%h %s", &range]).ok()
+            }
+            _ => None,
+        };
+        match commits {
+            Some(log) if !log.is_empty() => {
+                let commits: Vec<String> = log.lines().map(str::to_string).collect();
+                parts.push(list("New commits", "-", &commits));
+            }
+            // Moved back, or the old HEAD is gone (rebase, reset)
+            _ => parts.push(format!(
+                "HEAD moved from {} to {}",
+                short(last.head.as_deref()),
+                short(now.head.as_deref())
+            )),
+        }
+    }
+
+    let before: HashSet<&String> = last.changes.iter().collect();
+    let modified: Vec<String> = now.changes.iter().filter(|c| !before.contains(c)).cloned().collect();
+    if !modified.is_empty() {
+        parts.push(list("Newly modified files", "-", &modified));
+    }
+
+    let commands = commands_since(&last.commands, &now.commands);
+    if !commands.is_empty() {
+        parts.push(list("Commands run", "$", commands));
+    }
+
+    let open: HashSet<&str> = now.sessions.iter().map(|s| s.id.as_str()).collect();
+    let exited: Vec<String> = last
+        .sessions
+        .iter()
+        .filter(|s| !open.contains(s.id.as_str()))
+        .map(|s| s.name.clone())
+        .collect();
+    if !exited.is_empty() {
+        parts.push(list("Sessions that exited", "-", &exited));
+    }
+
+    if parts.len() == 1 {
+        parts.push("Nothing changed since then.".to_string());
+    }
+    parts.join("\n")
+}
+
+/// Commands in `now` that come after the end of `before`, matching on
+/// the last few so a repeated command isn't mistaken for the old one.
+fn commands_since<'a>(before: &[String], now: &'a [String]) -> &'a [String] {
+    if before.is_empty() {
+        return now;
+    }
+    let anchor = &before[before.len().saturating_sub(3)..];
+    match (anchor.len()..=now.len()).rev().find(|&end| now[..end].ends_with(anchor)) {
+        Some(end) => &now[end..],
+        None => now,
+    }
+}
+
+fn list(title: &str, marker: &str, items: &[String]) -> String {
+    let mut lines = vec![format!("{} ({}):", title, items.len())];
+    lines.extend(items.iter().take(MAX_ITEMS).map(|item| format!("  {} {}", marker, item)));
+    if items.len() > MAX_ITEMS {
+        lines.push(format!("  …and {} more", items.len() - MAX_ITEMS));
+    }
+    lines.join("\n")
+}
+
+fn short(hash: Option<&str>) -> String {
+    hash.map(|h| h.chars().take(7).collect()).unwrap_or_else(|| "nothing".to_string())
+}
+
+/// `45m`, `3h 12m`, `2d 4h`.
+fn format_elapsed(from: DateTime<Utc>, to: DateTime<Utc>) -> String {
+    let mins = (to - from).num_minutes().max(0);
+    match mins {
+        0 => "less than a minute".to_string(),
+        m if m < 60 => format!("{}m", m),
+        m if m < 24 * 60 => format!("{}h {}m", m / 60, m % 60),
+        m => format!("{}d {}h", m / (24 * 60), m % (24 * 60) / 60),
+    }
+}
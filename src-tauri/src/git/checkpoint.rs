@@ -0,0 +1,193 @@
+use super::diff::{self, DiffStatus, FileDiff};
+use super::run_git;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use uuid::Uuid;
+
+/// Hidden ref namespace; not shown by `git branch`, `git stash` or `git log`.
+const REF_PREFIX: &str = "refs/deckmind/checkpoints";
+
+/// Prefix of checkpoint commit messages; the rest is the label.
+const MESSAGE_PREFIX: &str = "DeckMind checkpoint: ";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Checkpoint {
+    pub id: String,
+    pub ref_name: String,
+    pub commit: String,
+    pub created_at: DateTime<Utc>,
+    /// What was about to happen when the snapshot was taken (e.g. "Fix").
+    pub label: String,
+}
+
+/// Run git with a private index file so the user's index is never touched.
+/// Identity is fixed so snapshots work in repos without user.name/email.
+fn git_with_index(dir: &Path, index: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_INDEX_FILE", index)
+        .env("GIT_AUTHOR_NAME", "DeckMind")
+        .env("GIT_AUTHOR_EMAIL", "deckmind@localhost")
+        .env("GIT_COMMITTER_NAME", "DeckMind")
+        .env("GIT_COMMITTER_EMAIL", "deckmind@localhost")
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// A scratch index in the repo's git dir, removed on drop.
+struct TempIndex(PathBuf);
+
+impl TempIndex {
+    fn new(dir: &Path) -> Result<Self, String> {
+        let git_dir = run_git(dir, &["rev-parse", "--absolute-git-dir"])?;
+        let path = Path::new(&git_dir).join(format!("deckmind-index-{}", Uuid::new_v4()));
+        // Seeding from the real index lets `git add` reuse its stat cache
+        let _ = std::fs::copy(Path::new(&git_dir).join("index"), &path);
+        Ok(TempIndex(path))
+    }
+}
+
+impl Drop for TempIndex {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Commit the current working tree (tracked and untracked files, honoring
+/// .gitignore) on top of HEAD without changing HEAD, the index or stash.
+/// The whole repository is captured, even when `dir` is a subdirectory.
+fn snapshot(dir: &Path, label: &str) -> Result<String, String> {
+    let index = TempIndex::new(dir)?;
+    git_with_index(dir, &index.0, &["add", "--all", "--", ":/"])?;
+    let tree = git_with_index(dir, &index.0, &["write-tree"])?;
+
+    let message = format!("{}{}", MESSAGE_PREFIX, label);
+    let mut args = vec!["commit-tree", tree.as_str(), "-m", message.as_str()];
+    // A repo without commits yet gets a parentless snapshot
+    let parent = run_git(dir, &["rev-parse", "--verify", "--quiet", "HEAD"]).ok();
+    if let Some(ref parent) = parent {
+        args.extend(["-p", parent.as_str()]);
+    }
+    git_with_index(dir, &index.0, &args)
+}
+
+/// Snapshot the working tree and keep it under a hidden ref for `session_id`.
+pub fn create(dir: &Path, session_id: &str, label: &str) -> Result<Checkpoint, String> {
+    let commit = snapshot(dir, label)?;
+    let created_at = Utc::now();
+    // Millisecond prefix keeps refs in creation order; the suffix keeps two
+    // checkpoints taken in the same millisecond apart
+    let id = format!("{}-{}", created_at.timestamp_millis(), &Uuid::new_v4().simple().to_string()[..8]);
+    let ref_name = format!("{}/{}/{}", REF_PREFIX, session_id, id);
+    run_git(dir, &["update-ref", &ref_name, &commit])?;
+    Ok(Checkpoint {
+        id,
+        ref_name,
+        commit,
+        created_at,
+        label: label.to_string(),
+    })
+}
+
+/// Checkpoints for a session, newest first.
+pub fn list(dir: &Path, session_id: &str) -> Result<Vec<Checkpoint>, String> {
+    let prefix = format!("{}/{}/", REF_PREFIX, session_id);
+    let out = run_git(dir, &[
+        "for-each-ref",
+        "--sort=-refname",
+        "--format=%(refname)%09%(objectname)%09%(committerdate:iso-strict)%09%(subject)",
+        &prefix,
+    ])?;
+
+    let checkpoints = out
+        .lines()
+        .filter_map(|line| {
+            let mut cols = line.splitn(4, '\t');
+            let ref_name = cols.next()?.to_string();
+            let commit = cols.next()?.to_string();
+            let created_at = DateTime::parse_from_rfc3339(cols.next()?).ok()?.with_timezone(&Utc);
+            let subject = cols.next().unwrap_or("");
+            let id = ref_name.strip_prefix(&prefix)?.to_string();
+            Some(Checkpoint {
+                id,
+                ref_name,
+                commit,
+                created_at,
+                label: subject.trim_start_matches(MESSAGE_PREFIX).to_string(),
+            })
+        })
+        .collect();
+    Ok(checkpoints)
+}
+
+fn find(dir: &Path, session_id: &str, checkpoint_id: &str) -> Result<Checkpoint, String> {
+    list(dir, session_id)?
+        .into_iter()
+        .find(|c| c.id == checkpoint_id)
+        .ok_or_else(|| format!("Checkpoint {} not found", checkpoint_id))
+}
+
+/// What changed in the working tree since a checkpoint.
+pub fn diff(dir: &Path, session_id: &str, checkpoint_id: &str) -> Result<Vec<FileDiff>, String> {
+    let checkpoint = find(dir, session_id, checkpoint_id)?;
+    let now = snapshot(dir, "diff")?;
+    diff::between(dir, &checkpoint.commit, &now, DiffStatus::Snapshot)
+}
+
+/// Undo one hunk (or, without `hunk`, all) of the changes to `path` since
+/// a checkpoint, as listed by `diff`.
+pub fn revert(
+    dir: &Path,
+    session_id: &str,
+    checkpoint_id: &str,
+    path: &str,
+    hunk: Option<(usize, Option<&str>)>,
+) -> Result<(), String> {
+    let checkpoint = find(dir, session_id, checkpoint_id)?;
+    let now = snapshot(dir, "revert")?;
+    // `git apply` skips paths outside the directory it runs in
+    let dir = &super::repo_root(dir)?;
+    diff::revert_between(dir, &checkpoint.commit, &now, path, hunk)
+}
+
+/// Put the working tree back to a checkpoint. HEAD and the index are left
+/// alone, so restored content shows up as ordinary uncommitted changes.
+/// The current state is checkpointed first, so a restore can be undone.
+pub fn restore(dir: &Path, session_id: &str, checkpoint_id: &str) -> Result<Checkpoint, String> {
+    let checkpoint = find(dir, session_id, checkpoint_id)?;
+    let before = create(dir, session_id, &format!("Before restoring {}", checkpoint.label))?;
+    // Diff paths are relative to the top level, not the session's directory
+    let dir = &super::repo_root(dir)?;
+
+    // Going from now to the checkpoint: deleted files were created since
+    // and have to go; everything else is rewritten from the checkpoint.
+    // Unchanged files are left alone to avoid needless rebuilds.
+    let changes = run_git(dir, &[
+        "diff", "--name-status", "--no-renames", &before.commit, &checkpoint.commit,
+    ])?;
+    let mut paths = Vec::new();
+    for line in changes.lines() {
+        let Some((status, path)) = line.split_once('\t') else { continue };
+        if status == "D" {
+            let _ = std::fs::remove_file(dir.join(path));
+        } else {
+            paths.push(path);
+        }
+    }
+    if !paths.is_empty() {
+        let index = TempIndex::new(dir)?;
+        git_with_index(dir, &index.0, &["read-tree", &checkpoint.commit])?;
+        let mut args = vec!["checkout-index", "--force", "--"];
+        args.extend(paths);
+        git_with_index(dir, &index.0, &args)?;
+    }
+
+    Ok(before)
+}
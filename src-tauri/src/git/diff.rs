@@ -0,0 +1,504 @@
+use super::{repo_root, run_git};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Which side of the working tree a change lives on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffStatus {
+    /// In the index, relative to HEAD (or the requested base).
+    Staged,
+    /// In the working tree, relative to the index.
+    Unstaged,
+    /// A file git does not track yet.
+    Untracked,
+    /// Between two snapshots, e.g. a checkpoint and the current tree.
+    Snapshot,
+}
+
+impl DiffStatus {
+    fn label(&self) -> &'static str {
+        match self {
+            DiffStatus::Staged => "staged",
+            DiffStatus::Unstaged => "unstaged",
+            DiffStatus::Untracked => "untracked",
+            DiffStatus::Snapshot => "snapshot",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffLine {
+    pub kind: LineKind,
+    pub content: String,
+    pub old_line: Option<u32>,
+    pub new_line: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffHunk {
+    pub index: usize,
+    /// The `@@ -a,b +c,d @@ ...` line, used to detect stale requests.
+    pub header: String,
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub lines: Vec<DiffLine>,
+    /// Raw hunk text (header included) for building patches.
+    #[serde(skip)]
+    raw: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileDiff {
+    pub path: String,
+    pub status: DiffStatus,
+    /// `added`, `deleted` or `modified`.
+    pub change: String,
+    pub binary: bool,
+    pub hunks: Vec<DiffHunk>,
+    /// Raw `diff --git` header lines for building patches.
+    #[serde(skip)]
+    header: Vec<String>,
+}
+
+impl FileDiff {
+    /// A patch containing only the given hunk (or every hunk).
+    fn patch(&self, hunk: Option<usize>) -> String {
+        let mut lines = self.header.clone();
+        for h in &self.hunks {
+            if hunk.is_none() || hunk == Some(h.index) {
+                lines.extend(h.raw.iter().cloned());
+            }
+        }
+        let mut patch = lines.join("\n");
+        patch.push('\n');
+        patch
+    }
+}
+
+/// Structured staged, unstaged and untracked changes in the repository
+/// containing `dir`, with paths relative to its top level. With `base`,
+/// staged changes are shown relative to that ref instead of HEAD.
+pub fn working_tree_diff(dir: &Path, base: Option<&str>) -> Result<Vec<FileDiff>, String> {
+    changes(&repo_root(dir)?, base)
+}
+
+/// `working_tree_diff` run from the top-level directory `dir`. From a
+/// subdirectory, `ls-files` would list paths relative to it and `apply`
+/// would skip changes outside it.
+fn changes(dir: &Path, base: Option<&str>) -> Result<Vec<FileDiff>, String> {
+    let mut staged_args = vec!["diff", "--cached", "--no-color", "--no-ext-diff", "--no-renames"];
+    if let Some(base) = base {
+        staged_args.push(base);
+    }
+    let mut files = parse(&diff_output(dir, &staged_args)?, DiffStatus::Staged);
+    files.extend(parse(
+        &diff_output(dir, &["diff", "--no-color", "--no-ext-diff", "--no-renames"])?,
+        DiffStatus::Unstaged,
+    ));
+
+    for path in run_git(dir, &["ls-files", "--others", "--exclude-standard"])?.lines() {
+        files.extend(parse(&untracked_patch(dir, path)?, DiffStatus::Untracked));
+    }
+
+    Ok(files)
+}
+
+/// Structured diff between two commits or trees.
+pub fn between(dir: &Path, from: &str, to: &str, status: DiffStatus) -> Result<Vec<FileDiff>, String> {
+    let text = diff_output(dir, &["diff", "--no-color", "--no-ext-diff", "--no-renames", from, to])?;
+    Ok(parse(&text, status))
+}
+
+/// Like `run_git`, but untrimmed: patches are rebuilt from this text, so
+/// a trailing `\r` on the last line must survive.
+fn diff_output(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// `git diff --no-index` exits 1 when files differ, so run it directly.
+fn untracked_patch(dir: &Path, path: &str) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(["diff", "--no-color", "--no-ext-diff", "--no-index", "--", "/dev/null", path])
+        .current_dir(dir)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    match output.status.code() {
+        Some(0) | Some(1) => Ok(String::from_utf8_lossy(&output.stdout).to_string()),
+        _ => Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+    }
+}
+
+/// Parse `git diff` output into files and hunks. Lines are split on `\n`
+/// only: the raw text keeps CRLF endings so rebuilt patches still apply.
+fn parse(text: &str, status: DiffStatus) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    let (mut old_no, mut new_no) = (0u32, 0u32);
+
+    for raw_line in text.split_terminator('\n') {
+        let line = raw_line.strip_suffix('\r').unwrap_or(raw_line);
+        if line.starts_with("diff --git ") {
+            files.push(FileDiff {
+                path: path_from_diff_line(line),
+                status,
+                change: "modified".to_string(),
+                binary: false,
+                hunks: Vec::new(),
+                header: vec![raw_line.to_string()],
+            });
+            continue;
+        }
+        let Some(file) = files.last_mut() else { continue };
+
+        if line.starts_with("@@") {
+            let (old_start, old_lines, new_start, new_lines) = parse_hunk_header(line);
+            old_no = old_start;
+            new_no = new_start;
+            file.hunks.push(DiffHunk {
+                index: file.hunks.len(),
+                header: raw_line.to_string(),
+                old_start,
+                old_lines,
+                new_start,
+                new_lines,
+                lines: Vec::new(),
+                raw: vec![raw_line.to_string()],
+            });
+            continue;
+        }
+
+        let Some(hunk) = file.hunks.last_mut() else {
+            // Still in the file header
+            if line.starts_with("new file mode") {
+                file.change = "added".to_string();
+            } else if line.starts_with("deleted file mode") {
+                file.change = "deleted".to_string();
+            } else if line.starts_with("Binary files") {
+                file.binary = true;
+            } else if let Some(p) = line.strip_prefix("+++ b/") {
+                file.path = p.to_string();
+            } else if let Some(p) = line.strip_prefix("--- a/") {
+                file.path = p.to_string();
+            }
+            file.header.push(raw_line.to_string());
+            continue;
+        };
+
+        hunk.raw.push(raw_line.to_string());
+        let (kind, content) = match line.chars().next() {
+            Some('+') => (LineKind::Added, &line[1..]),
+            Some('-') => (LineKind::Removed, &line[1..]),
+            Some(' ') => (LineKind::Context, &line[1..]),
+            // "\ No newline at end of file" stays in the raw patch only
+            _ => continue,
+        };
+        let (old_line, new_line) = match kind {
+            LineKind::Added => (None, Some(new_no)),
+            LineKind::Removed => (Some(old_no), None),
+            LineKind::Context => (Some(old_no), Some(new_no)),
+        };
+        if old_line.is_some() {
+            old_no += 1;
+        }
+        if new_line.is_some() {
+            new_no += 1;
+        }
+        hunk.lines.push(DiffLine {
+            kind,
+            content: content.to_string(),
+            old_line,
+            new_line,
+        });
+    }
+
+    files
+}
+
+/// Path from `diff --git a/<path> b/<path>`, used until `+++`/`---` refine it.
+fn path_from_diff_line(line: &str) -> String {
+    let rest = line.trim_start_matches("diff --git ");
+    match rest.rfind(" b/") {
+        Some(i) => rest[i + 3..].to_string(),
+        None => rest.to_string(),
+    }
+}
+
+/// `@@ -a,b +c,d @@` → (a, b, c, d). Counts default to 1 when omitted.
+fn parse_hunk_header(line: &str) -> (u32, u32, u32, u32) {
+    let mut ranges = line.split_whitespace().skip(1).take(2);
+    let mut range = |prefix: char| {
+        let r = ranges.next().unwrap_or("").trim_start_matches(prefix);
+        let mut parts = r.splitn(2, ',');
+        let start = parts.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+        let count = parts.next().and_then(|s| s.parse().ok()).unwrap_or(1);
+        (start, count)
+    };
+    let (old_start, old_lines) = range('-');
+    let (new_start, new_lines) = range('+');
+    (old_start, old_lines, new_start, new_lines)
+}
+
+/// Pipe `patch` into `git apply` with the given flags.
+fn apply(dir: &Path, patch: &str, flags: &[&str]) -> Result<(), String> {
+    let mut child = Command::new("git")
+        .arg("apply")
+        .args(flags)
+        .arg("-")
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run git apply: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(patch.as_bytes()).map_err(|e| e.to_string())?;
+    }
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Find a file's diff (and optionally a hunk in it) in a fresh diff, so
+/// requests made against an outdated view fail instead of hitting the
+/// wrong lines.
+fn locate(
+    dir: &Path,
+    path: &str,
+    status: DiffStatus,
+    hunk: Option<(usize, Option<&str>)>,
+    base: Option<&str>,
+) -> Result<FileDiff, String> {
+    pick(changes(dir, base)?, path, status, hunk)
+}
+
+/// `path` (and the hunk, if given) in `files`.
+fn pick(
+    files: Vec<FileDiff>,
+    path: &str,
+    status: DiffStatus,
+    hunk: Option<(usize, Option<&str>)>,
+) -> Result<FileDiff, String> {
+    let file = files
+        .into_iter()
+        .find(|f| f.path == path && f.status == status)
+        .ok_or_else(|| format!("No {} changes for {}", status.label(), path))?;
+    if let Some((index, header)) = hunk {
+        let found = file
+            .hunks
+            .get(index)
+            .ok_or_else(|| format!("Hunk {} not found in {}", index, path))?;
+        if header.is_some_and(|h| h != found.header) {
+            return Err(format!("Hunk {} in {} has changed; refresh the diff", index, path));
+        }
+    }
+    Ok(file)
+}
+
+/// Flags that undo a change of the given status.
+fn revert_flags(status: DiffStatus) -> &'static [&'static str] {
+    match status {
+        // Drop the change from both the index and the working tree
+        DiffStatus::Staged => &["-R", "--index"],
+        DiffStatus::Unstaged | DiffStatus::Untracked | DiffStatus::Snapshot => &["-R"],
+    }
+}
+
+/// Undo one hunk.
+pub fn revert_hunk(
+    dir: &Path,
+    path: &str,
+    status: DiffStatus,
+    index: usize,
+    header: Option<&str>,
+    base: Option<&str>,
+) -> Result<(), String> {
+    let dir = &repo_root(dir)?;
+    let file = locate(dir, path, status, Some((index, header)), base)?;
+    apply(dir, &file.patch(Some(index)), revert_flags(status))
+}
+
+/// Undo every change of `status` in a file. Untracked files are deleted.
+pub fn revert_file(dir: &Path, path: &str, status: DiffStatus, base: Option<&str>) -> Result<(), String> {
+    let dir = &repo_root(dir)?;
+    let file = locate(dir, path, status, None, base)?;
+    apply(dir, &file.patch(None), revert_flags(status))
+}
+
+/// Undo, in the working tree, one hunk (or all) of `path`'s changes from
+/// snapshot `from` to snapshot `to`, where `to` is the current tree.
+pub fn revert_between(
+    dir: &Path,
+    from: &str,
+    to: &str,
+    path: &str,
+    hunk: Option<(usize, Option<&str>)>,
+) -> Result<(), String> {
+    let dir = &repo_root(dir)?;
+    let status = DiffStatus::Snapshot;
+    let file = pick(between(dir, from, to, status)?, path, status, hunk)?;
+    apply(dir, &file.patch(hunk.map(|(index, _)| index)), revert_flags(status))
+}
+
+/// Stage one unstaged (or untracked) hunk.
+pub fn stage_hunk(
+    dir: &Path,
+    path: &str,
+    status: DiffStatus,
+    index: usize,
+    header: Option<&str>,
+) -> Result<(), String> {
+    if status == DiffStatus::Staged {
+        return Err("Hunk is already staged".to_string());
+    }
+    let dir = &repo_root(dir)?;
+    let file = locate(dir, path, status, Some((index, header)), None)?;
+    apply(dir, &file.patch(Some(index)), &["--cached"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crlf_endings_survive_in_rebuilt_patches() {
+        let output = concat!(
+            "diff --git a/win.txt b/win.txt\n",
+            "index 1111111..2222222 100644\n",
+            "--- a/win.txt\n",
+            "+++ b/win.txt\n",
+            "@@ -1,2 +1,2 @@\n",
+            " keep\r\n",
+            "-old\r\n",
+            "+new\r\n",
+        );
+        let files = parse(output, DiffStatus::Unstaged);
+        assert_eq!(files.len(), 1);
+        let file = &files[0];
+        assert_eq!((file.path.as_str(), file.change.as_str()), ("win.txt", "modified"));
+
+        let lines: Vec<(LineKind, &str, Option<u32>, Option<u32>)> = file.hunks[0]
+            .lines
+            .iter()
+            .map(|l| (l.kind, l.content.as_str(), l.old_line, l.new_line))
+            .collect();
+        assert_eq!(lines, vec![
+            (LineKind::Context, "keep", Some(1), Some(1)),
+            (LineKind::Removed, "old", Some(2), None),
+            (LineKind::Added, "new", None, Some(2)),
+        ]);
+        assert_eq!(file.patch(None), output);
+    }
+
+    #[test]
+    fn new_and_deleted_files() {
+        let output = "\
+diff --git a/added.rs b/added.rs
+new file mode 100644
+index 0000000..3333333
+--- /dev/null
++++ b/added.rs
+@@ -0,0 +1,2 @@
++fn main() {}
++// done
+\\ No newline at end of file
+diff --git a/gone.rs b/gone.rs
+deleted file mode 100644
+index 4444444..0000000
+--- a/gone.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-fn old() {}
+diff --git a/logo.png b/logo.png
+new file mode 100644
+index 0000000..5555555
+Binary files /dev/null and b/logo.png differ
+";
+        let files = parse(output, DiffStatus::Staged);
+        let summary: Vec<(&str, &str, bool)> = files
+            .iter()
+            .map(|f| (f.path.as_str(), f.change.as_str(), f.binary))
+            .collect();
+        assert_eq!(summary, vec![
+            ("added.rs", "added", false),
+            ("gone.rs", "deleted", false),
+            ("logo.png", "added", true),
+        ]);
+
+        let added = &files[0].hunks[0];
+        assert_eq!((added.old_start, added.old_lines, added.new_start, added.new_lines), (0, 0, 1, 2));
+        assert_eq!(added.lines.len(), 2);
+        assert!(files[0].patch(None).ends_with("+// done\n\\ No newline at end of file\n"));
+
+        // A missing count means one line
+        let gone = &files[1].hunks[0];
+        assert_eq!((gone.old_start, gone.old_lines, gone.new_start, gone.new_lines), (1, 1, 0, 0));
+        assert_eq!(gone.lines[0].old_line, Some(1));
+        assert!(files[2].hunks.is_empty());
+    }
+
+    #[test]
+    fn picks_one_hunk_and_rejects_stale_requests() {
+        let output = "\
+diff --git a/lib.rs b/lib.rs
+index 1111111..2222222 100644
+--- a/lib.rs
++++ b/lib.rs
+@@ -1,3 +1,3 @@ mod a;
+ use a;
+-fn one() {}
++fn uno() {}
+ 
+@@ -20,2 +20,3 @@ fn tail() {
+ }
++// two
+ 
+";
+        let files = parse(output, DiffStatus::Unstaged);
+        let second = "@@ -20,2 +20,3 @@ fn tail() {";
+
+        let file = pick(files.clone(), "lib.rs", DiffStatus::Unstaged, Some((1, Some(second)))).unwrap();
+        assert_eq!(file.hunks[1].lines[1].new_line, Some(21));
+        assert_eq!(
+            file.patch(Some(1)),
+            concat!(
+                "diff --git a/lib.rs b/lib.rs\n",
+                "index 1111111..2222222 100644\n",
+                "--- a/lib.rs\n",
+                "+++ b/lib.rs\n",
+                "@@ -20,2 +20,3 @@ fn tail() {\n",
+                " }\n",
+                "+// two\n",
+                " \n",
+            )
+        );
+
+        let stale = pick(files.clone(), "lib.rs", DiffStatus::Unstaged, Some((0, Some(second))));
+        assert!(stale.unwrap_err().contains("has changed"));
+        assert!(pick(files.clone(), "lib.rs", DiffStatus::Unstaged, Some((2, None))).is_err());
+        assert!(pick(files, "lib.rs", DiffStatus::Staged, None).is_err());
+    }
+}
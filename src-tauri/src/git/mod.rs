@@ -0,0 +1,45 @@
+pub mod checkpoint;
+pub mod diff;
+pub mod patches;
+pub mod pool;
+pub mod status;
+pub mod worktree;
+
+use std::path::Path;
+use std::process::Command;
+
+/// Run `git` in `dir` and return its trimmed stdout.
+/// On failure the error is git's stderr (or the spawn error).
+pub fn run_git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(if stderr.is_empty() {
+            format!("git {} failed with {}", args.join(" "), output.status)
+        } else {
+            stderr
+        });
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim_end().to_string())
+}
+
+/// Top-level directory of the repository containing `dir`.
+pub fn repo_root(dir: &Path) -> Result<std::path::PathBuf, String> {
+    run_git(dir, &["rev-parse", "--show-toplevel"]).map(std::path::PathBuf::from)
+}
+
+/// Top-level directory of the main repository, even when `dir` is inside
+/// one of its linked worktrees.
+pub fn main_repo_root(dir: &Path) -> Result<std::path::PathBuf, String> {
+    let common = run_git(dir, &["rev-parse", "--path-format=absolute", "--git-common-dir"])?;
+    match Path::new(&common).parent() {
+        Some(parent) => Ok(parent.to_path_buf()),
+        None => repo_root(dir),
+    }
+}
@@ -0,0 +1,133 @@
+use super::run_git;
+use super::worktree::slugify;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Placeholders `git format-patch --cover-letter` leaves for us to fill in.
+const SUBJECT_PLACEHOLDER: &str = "*** SUBJECT HERE ***";
+const BLURB_PLACEHOLDER: &str = "*** BLURB HERE ***";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatchFormat {
+    /// One `.patch` file per commit plus `0000-cover-letter.patch`.
+    #[default]
+    Patches,
+    /// The same series as a single mbox file.
+    Mbox,
+    /// A `git bundle` plus a plain-text cover letter.
+    Bundle,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PatchExport {
+    pub format: PatchFormat,
+    /// Files written, cover letter first.
+    pub files: Vec<String>,
+    /// `<short hash> <subject>` for each exported commit, oldest first.
+    pub commits: Vec<String>,
+    /// The worktree had uncommitted changes, which are not exported.
+    pub uncommitted_changes: bool,
+}
+
+/// Export the commits in `base..HEAD` of the repo at `dir` into `dest`.
+/// `title` becomes the cover letter subject and `action_log` (one line per
+/// entry) is embedded in its body.
+pub fn export(
+    dir: &Path,
+    base: &str,
+    dest: &Path,
+    format: PatchFormat,
+    title: &str,
+    action_log: &[String],
+) -> Result<PatchExport, String> {
+    let range = format!("{}..HEAD", base);
+    let commits: Vec<String> = run_git(dir, &["log", "--reverse", "--format=%h %s", &range])?
+        .lines()
+        .map(str::to_string)
+        .collect();
+    if commits.is_empty() {
+        return Err(format!("No commits to export between {} and HEAD", base));
+    }
+    let uncommitted_changes = super::worktree::is_dirty(dir)?;
+
+    std::fs::create_dir_all(dest).map_err(|e| format!("Cannot create {}: {}", dest.display(), e))?;
+    let branch = run_git(dir, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+    let name = match slugify(&branch) {
+        s if s.is_empty() || s == "head" => "deckmind".to_string(),
+        s => s,
+    };
+    let blurb = blurb(action_log, uncommitted_changes);
+
+    let files = match format {
+        PatchFormat::Patches => {
+            let dest_str = dest.to_string_lossy();
+            let out = run_git(dir, &["format-patch", "--cover-letter", "-o", &dest_str, &range])?;
+            let files: Vec<String> = out.lines().map(str::to_string).collect();
+            // The cover letter is always listed first
+            if let Some(cover) = files.first() {
+                let text = std::fs::read_to_string(cover).map_err(|e| e.to_string())?;
+                std::fs::write(cover, fill_cover_letter(&text, title, &blurb)).map_err(|e| e.to_string())?;
+            }
+            files
+        }
+        PatchFormat::Mbox => {
+            let mut text = run_git(dir, &["format-patch", "--cover-letter", "--stdout", &range])?;
+            text.push('\n');
+            let path = dest.join(format!("{}.mbox", name));
+            std::fs::write(&path, fill_cover_letter(&text, title, &blurb)).map_err(|e| e.to_string())?;
+            vec![path.to_string_lossy().to_string()]
+        }
+        PatchFormat::Bundle => {
+            let bundle = dest.join(format!("{}.bundle", name));
+            let bundle_str = bundle.to_string_lossy();
+            // A detached HEAD has no branch name to put in the bundle
+            let tip = if branch == "HEAD" { "HEAD" } else { branch.as_str() };
+            run_git(dir, &["bundle", "create", &bundle_str, &format!("{}..{}", base, tip)])?;
+
+            let stat = run_git(dir, &["diff", "--stat", base, "HEAD"])?;
+            let cover = dest.join(format!("{}-cover-letter.txt", name));
+            let text = format!(
+                "{}\n\n{}\n\nCommits ({}..{}):\n{}\n\nChanged files:\n{}\n\nFetch with: git fetch {} {}\n",
+                title,
+                blurb,
+                base,
+                tip,
+                commits.iter().map(|c| format!("  {}", c)).collect::<Vec<_>>().join("\n"),
+                stat,
+                bundle.file_name().unwrap_or_default().to_string_lossy(),
+                tip,
+            );
+            std::fs::write(&cover, text).map_err(|e| e.to_string())?;
+            vec![cover.to_string_lossy().to_string(), bundle_str.to_string()]
+        }
+    };
+
+    Ok(PatchExport {
+        format,
+        files,
+        commits,
+        uncommitted_changes,
+    })
+}
+
+/// Cover letter body: the session's action log and a note about anything
+/// left out. format-patch adds the shortlog and diffstat itself.
+fn blurb(action_log: &[String], uncommitted_changes: bool) -> String {
+    let mut text = String::from("Exported from DeckMind.\n\nAction log:\n");
+    if action_log.is_empty() {
+        text.push_str("  (no actions recorded)\n");
+    }
+    for line in action_log {
+        text.push_str(&format!("  {}\n", line));
+    }
+    if uncommitted_changes {
+        text.push_str("\nNote: the worktree had uncommitted changes, which are not included.\n");
+    }
+    text.trim_end().to_string()
+}
+
+fn fill_cover_letter(text: &str, title: &str, blurb: &str) -> String {
+    text.replacen(SUBJECT_PLACEHOLDER, title, 1)
+        .replacen(BLURB_PLACEHOLDER, blurb, 1)
+}
@@ -0,0 +1,353 @@
+use super::worktree::{self, ManagedWorktree};
+use super::{main_repo_root, run_git};
+use crate::config::WorktreePoolConfig;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Start of the branch/directory name spares have until a session claims
+/// them. Session names are slugified to `[a-z0-9-]`, so a renamed branch
+/// never starts with it.
+const SPARE_PREFIX: &str = "_pool-";
+
+/// Written into a spare's git dir once its setup command has succeeded, so
+/// spares left over from a previous run can be reused safely.
+const READY_MARKER: &str = "deckmind-pool-ready";
+
+/// Upper bound for a spare's setup command (dependency installs can be slow).
+const SETUP_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+struct RepoPool {
+    config: WorktreePoolConfig,
+    spares: VecDeque<ManagedWorktree>,
+    filling: bool,
+    /// Set when creating or setting up a spare failed; refilling stops
+    /// until the next claim or config change.
+    last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolStatus {
+    pub repo: String,
+    pub size: usize,
+    pub ready: usize,
+    pub filling: bool,
+    pub last_error: Option<String>,
+}
+
+/// Background pool of pre-created worktrees, keyed by main repo path.
+pub struct WorktreePool {
+    root: PathBuf,
+    repos: HashMap<String, RepoPool>,
+}
+
+impl WorktreePool {
+    pub fn new() -> Self {
+        WorktreePool {
+            root: PathBuf::new(),
+            repos: HashMap::new(),
+        }
+    }
+
+    pub fn status(&self) -> Vec<PoolStatus> {
+        self.repos
+            .iter()
+            .map(|(repo, p)| PoolStatus {
+                repo: repo.clone(),
+                size: p.config.size,
+                ready: p.spares.len(),
+                filling: p.filling,
+                last_error: p.last_error.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Apply the pool config: drop repos no longer listed, pick up ready spares
+/// from a previous run for new ones, and start refilling.
+pub async fn configure(pool: &Arc<Mutex<WorktreePool>>, root: PathBuf, configs: Vec<WorktreePoolConfig>) {
+    let mut resolved = Vec::new();
+    for config in configs {
+        let dir = PathBuf::from(&config.repo);
+        match tokio::task::spawn_blocking(move || main_repo_root(&dir)).await {
+            Ok(Ok(repo)) => resolved.push((repo.to_string_lossy().to_string(), config)),
+            Ok(Err(e)) => log::warn!("Worktree pool: skipping {}: {}", config.repo, e),
+            Err(e) => log::warn!("Worktree pool: skipping {}: {}", config.repo, e),
+        }
+    }
+
+    let (dropped, added) = {
+        let mut p = pool.lock().await;
+        p.root = root;
+
+        let keep: Vec<&String> = resolved.iter().map(|(repo, _)| repo).collect();
+        let dropped_repos: Vec<String> = p.repos.keys().filter(|r| !keep.contains(r)).cloned().collect();
+        let dropped: Vec<ManagedWorktree> = dropped_repos
+            .iter()
+            .filter_map(|r| p.repos.remove(r))
+            .flat_map(|rp| rp.spares)
+            .collect();
+
+        let mut added = Vec::new();
+        for (repo, config) in &resolved {
+            match p.repos.get_mut(repo) {
+                Some(existing) => {
+                    existing.config = config.clone();
+                    existing.last_error = None;
+                }
+                None => {
+                    p.repos.insert(repo.clone(), RepoPool {
+                        config: config.clone(),
+                        spares: VecDeque::new(),
+                        filling: false,
+                        last_error: None,
+                    });
+                    added.push(repo.clone());
+                }
+            }
+        }
+        (dropped, added)
+    };
+
+    for spare in dropped {
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = spare.remove(true) {
+                log::warn!("Worktree pool: failed to remove {}: {}", spare.path, e);
+            }
+        });
+    }
+
+    for repo in &added {
+        let repo_c = repo.clone();
+        let leftovers = tokio::task::spawn_blocking(move || find_leftover_spares(Path::new(&repo_c)))
+            .await
+            .unwrap_or_default();
+        if !leftovers.is_empty() {
+            log::info!("Worktree pool: reusing {} spare(s) for {}", leftovers.len(), repo);
+            if let Some(rp) = pool.lock().await.repos.get_mut(repo) {
+                rp.spares.extend(leftovers);
+            }
+        }
+    }
+
+    for (repo, _) in resolved {
+        tokio::spawn(refill(pool.clone(), repo));
+    }
+}
+
+/// Take a ready spare for the repo containing `dir`, brought up to date
+/// with the default branch and renamed after `name`. Returns `None` when
+/// the repo has no pool or no spare is ready; the pool refills in the
+/// background either way.
+pub async fn claim(pool: &Arc<Mutex<WorktreePool>>, dir: &str, name: Option<String>) -> Option<ManagedWorktree> {
+    let dir = PathBuf::from(dir);
+    let repo = tokio::task::spawn_blocking(move || main_repo_root(&dir))
+        .await
+        .ok()?
+        .ok()?
+        .to_string_lossy()
+        .to_string();
+
+    let spare = {
+        let mut p = pool.lock().await;
+        let rp = p.repos.get_mut(&repo)?;
+        rp.last_error = None;
+        rp.spares.pop_front()
+    };
+    tokio::spawn(refill(pool.clone(), repo.clone()));
+
+    let mut spare = spare?;
+    let claimed = spare.clone();
+    let prepared = tokio::task::spawn_blocking(move || {
+        // Once handed out it's a session's worktree, never a spare again
+        let git_dir = run_git(Path::new(&spare.path), &["rev-parse", "--absolute-git-dir"])?;
+        std::fs::remove_file(Path::new(&git_dir).join(READY_MARKER)).map_err(|e| e.to_string())?;
+        freshen(&mut spare)?;
+        spare.rename_branch(name.as_deref())?;
+        Ok::<_, String>(spare)
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|r| r);
+
+    match prepared {
+        Ok(wt) => {
+            log::info!("Worktree pool: claimed {} for {}", wt.path, repo);
+            Some(wt)
+        }
+        Err(e) => {
+            log::warn!("Worktree pool: spare for {} unusable: {}", repo, e);
+            // Its ready marker may already be gone, so refill wouldn't
+            // pick it up again either
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = claimed.remove(true) {
+                    log::warn!("Worktree pool: failed to remove {}: {}", claimed.path, e);
+                }
+            });
+            None
+        }
+    }
+}
+
+/// Create spares until the repo's pool is full. Only one refill runs per
+/// repo at a time.
+async fn refill(pool: Arc<Mutex<WorktreePool>>, repo: String) {
+    {
+        let mut p = pool.lock().await;
+        let Some(rp) = p.repos.get_mut(&repo) else { return };
+        if rp.filling || rp.last_error.is_some() {
+            return;
+        }
+        rp.filling = true;
+    }
+
+    loop {
+        // Re-read each round so config changes apply to a running refill
+        let (root, config) = {
+            let p = pool.lock().await;
+            match p.repos.get(&repo) {
+                Some(rp) if rp.spares.len() < rp.config.size => (p.root.clone(), rp.config.clone()),
+                _ => break,
+            }
+        };
+
+        match create_spare(&repo, &root, config.setup_command.as_deref()).await {
+            Ok(spare) => {
+                let mut p = pool.lock().await;
+                match p.repos.get_mut(&repo) {
+                    Some(rp) => rp.spares.push_back(spare),
+                    None => {
+                        tokio::task::spawn_blocking(move || spare.remove(true));
+                    }
+                }
+            }
+            Err(e) => {
+                log::warn!("Worktree pool: failed to prepare spare for {}: {}", repo, e);
+                if let Some(rp) = pool.lock().await.repos.get_mut(&repo) {
+                    rp.last_error = Some(e);
+                }
+                break;
+            }
+        }
+    }
+
+    if let Some(rp) = pool.lock().await.repos.get_mut(&repo) {
+        rp.filling = false;
+    }
+}
+
+/// Create one spare off the default branch and run the setup command in it.
+async fn create_spare(repo: &str, root: &Path, setup_command: Option<&str>) -> Result<ManagedWorktree, String> {
+    let (repo_c, root_c) = (PathBuf::from(repo), root.to_path_buf());
+    let spare = tokio::task::spawn_blocking(move || {
+        let base = default_branch(&repo_c);
+        let leaf = format!("{}{}", SPARE_PREFIX, &Uuid::new_v4().to_string()[..8]);
+        worktree::create_managed_named(&repo_c, &root_c, &leaf, &base)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    if let Some(cmd) = setup_command {
+        if let Err(e) = run_setup(Path::new(&spare.path), cmd).await {
+            let failed = spare.clone();
+            tokio::task::spawn_blocking(move || failed.remove(true));
+            return Err(e);
+        }
+    }
+
+    let path = PathBuf::from(&spare.path);
+    tokio::task::spawn_blocking(move || -> Result<(), String> {
+        let git_dir = run_git(&path, &["rev-parse", "--absolute-git-dir"])?;
+        std::fs::write(Path::new(&git_dir).join(READY_MARKER), b"")
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    log::info!("Worktree pool: spare ready at {}", spare.path);
+    Ok(spare)
+}
+
+async fn run_setup(dir: &Path, command: &str) -> Result<(), String> {
+    let child = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(dir)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to start setup command: {}", e))?;
+
+    match tokio::time::timeout(SETUP_TIMEOUT, child.wait_with_output()).await {
+        Ok(Ok(output)) if output.status.success() => Ok(()),
+        Ok(Ok(output)) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let last = stderr.lines().last().unwrap_or("").trim();
+            Err(format!("Setup command `{}` failed ({}): {}", command, output.status, last))
+        }
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("Setup command `{}` timed out", command)),
+    }
+}
+
+/// The branch new work should start from: origin's HEAD if known,
+/// otherwise whatever the main worktree has checked out.
+fn default_branch(repo: &Path) -> String {
+    run_git(repo, &["symbolic-ref", "--short", "refs/remotes/origin/HEAD"])
+        .or_else(|_| run_git(repo, &["rev-parse", "--abbrev-ref", "HEAD"]))
+        .unwrap_or_else(|_| "HEAD".to_string())
+}
+
+/// Move an unused spare up to the default branch's current tip, keeping
+/// untracked and ignored files (installed dependencies) in place.
+fn freshen(spare: &mut ManagedWorktree) -> Result<(), String> {
+    let path = PathBuf::from(&spare.path);
+    let tip = run_git(&path, &["rev-parse", &format!("{}^{{commit}}", default_branch(Path::new(&spare.repo)))])?;
+    if tip == spare.base_commit {
+        return Ok(());
+    }
+    let tracked_changes = run_git(&path, &["status", "--porcelain", "--untracked-files=no"])?;
+    if !tracked_changes.trim().is_empty() {
+        log::info!("Worktree pool: {} has local changes, not updating", spare.path);
+        return Ok(());
+    }
+    run_git(&path, &["reset", "--hard", &tip])?;
+    spare.base_commit = tip;
+    Ok(())
+}
+
+/// Spares created by an earlier run that finished setup and are unused.
+/// Half-prepared ones are removed.
+fn find_leftover_spares(repo: &Path) -> Vec<ManagedWorktree> {
+    let prefix = format!("deckmind/{}", SPARE_PREFIX);
+    let entries = match worktree::list(repo, |_| None) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut spares = Vec::new();
+    for entry in entries {
+        let Some(branch) = entry.branch.filter(|b| b.starts_with(&prefix)) else { continue };
+        let path = PathBuf::from(&entry.path);
+        let ready = run_git(&path, &["rev-parse", "--absolute-git-dir"])
+            .map(|git_dir| Path::new(&git_dir).join(READY_MARKER).exists())
+            .unwrap_or(false);
+        let spare = ManagedWorktree {
+            repo: repo.to_string_lossy().to_string(),
+            path: entry.path.clone(),
+            branch,
+            base_commit: entry.head.clone(),
+        };
+        if ready && !entry.locked && !entry.prunable {
+            spares.push(spare);
+        } else if let Err(e) = spare.remove(true) {
+            log::warn!("Worktree pool: failed to remove stale spare {}: {}", entry.path, e);
+        }
+    }
+    spares
+}
@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Number of recent commits kept in `RepoState`.
+const RECENT_COMMITS: usize = 5;
+
+/// An operation git is in the middle of.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Operation {
+    Merge,
+    Rebase {
+        /// Step being applied and total steps, when git records them.
+        step: Option<usize>,
+        total: Option<usize>,
+        /// Branch being rebased.
+        branch: Option<String>,
+    },
+    CherryPick,
+    Revert,
+    Bisect,
+}
+
+impl Operation {
+    pub fn describe(&self) -> String {
+        match self {
+            Operation::Merge => "merge in progress".to_string(),
+            Operation::Rebase { step, total, branch } => {
+                let mut text = match branch {
+                    Some(branch) => format!("rebase of {} in progress", branch),
+                    None => "rebase in progress".to_string(),
+                };
+                if let (Some(step), Some(total)) = (step, total) {
+                    text.push_str(&format!(" (step {}/{})", step, total));
+                }
+                text
+            }
+            Operation::CherryPick => "cherry-pick in progress".to_string(),
+            Operation::Revert => "revert in progress".to_string(),
+            Operation::Bisect => "bisect in progress".to_string(),
+        }
+    }
+}
+
+/// Where a repository's HEAD is and what's pending in it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoState {
+    /// Checked-out branch; `None` on a detached HEAD.
+    pub branch: Option<String>,
+    /// Short hash of HEAD; `None` before the first commit.
+    pub head: Option<String>,
+    pub detached: bool,
+    pub upstream: Option<String>,
+    pub ahead: usize,
+    pub behind: usize,
+    pub operation: Option<Operation>,
+    /// Paths with unresolved conflicts.
+    pub conflicts: Vec<String>,
+    /// Changed and untracked files as `git status --short` shows them.
+    pub changes: Vec<String>,
+    pub stash_count: usize,
+    /// `<short hash> <subject>` for the latest commits, newest first.
+    pub recent_commits: Vec<String>,
+}
+
+impl RepoState {
+    /// Build the state from the output of `git status --porcelain=v2
+    /// --branch`, `git rev-parse --absolute-git-dir`, `git stash list` and
+    /// `git log -5 --format='%h %s'`, leaving out what a missing one would
+    /// have provided.
+    pub fn parse(status: &str, git_dir: Option<&Path>, stash_list: Option<&str>, log: Option<&str>) -> Self {
+        let mut state = RepoState {
+            branch: None,
+            head: None,
+            detached: false,
+            upstream: None,
+            ahead: 0,
+            behind: 0,
+            operation: None,
+            conflicts: Vec::new(),
+            changes: Vec::new(),
+            stash_count: 0,
+            recent_commits: Vec::new(),
+        };
+
+        for line in status.lines() {
+            if let Some(oid) = line.strip_prefix("# branch.oid ") {
+                if oid != "(initial)" {
+                    state.head = Some(oid.chars().take(7).collect());
+                }
+            } else if let Some(head) = line.strip_prefix("# branch.head ") {
+                if head == "(detached)" {
+                    state.detached = true;
+                } else {
+                    state.branch = Some(head.to_string());
+                }
+            } else if let Some(upstream) = line.strip_prefix("# branch.upstream ") {
+                state.upstream = Some(upstream.to_string());
+            } else if let Some(ab) = line.strip_prefix("# branch.ab ") {
+                for count in ab.split_whitespace() {
+                    if let Some(n) = count.strip_prefix('+') {
+                        state.ahead = n.parse().unwrap_or(0);
+                    } else if let Some(n) = count.strip_prefix('-') {
+                        state.behind = n.parse().unwrap_or(0);
+                    }
+                }
+            } else if let Some(change) = short_status(line) {
+                if line.starts_with("u ") {
+                    // `UU path`
+                    state.conflicts.push(change[3..].to_string());
+                }
+                state.changes.push(change);
+            }
+        }
+
+        state.operation = git_dir.and_then(operation);
+        state.stash_count = stash_list.map(|out| out.lines().count()).unwrap_or(0);
+        if state.head.is_some() {
+            state.recent_commits = log
+                .map(|out| out.lines().take(RECENT_COMMITS).map(str::to_string).collect())
+                .unwrap_or_default();
+        }
+        state
+    }
+
+    /// `main (2 ahead, 1 behind origin/main)`, `detached HEAD at 1a2b3c4`.
+    pub fn describe_head(&self) -> String {
+        let name = match (&self.branch, &self.head) {
+            (Some(branch), _) => branch.clone(),
+            (None, Some(head)) if self.detached => format!("detached HEAD at {}", head),
+            _ => "no commits yet".to_string(),
+        };
+        match &self.upstream {
+            Some(upstream) if self.ahead == 0 && self.behind == 0 => {
+                format!("{} (up to date with {})", name, upstream)
+            }
+            Some(upstream) => format!("{} ({} ahead, {} behind {})", name, self.ahead, self.behind, upstream),
+            None if self.branch.is_some() => format!("{} (no upstream)", name),
+            None => name,
+        }
+    }
+}
+
+/// Turn a porcelain v2 entry into its `git status --short` form, e.g.
+/// `M  src/lib.rs`, `R  old.rs -> new.rs`, `?? notes.txt`.
+fn short_status(line: &str) -> Option<String> {
+    let (kind, rest) = line.split_once(' ')?;
+    let (path_field, xy) = match kind {
+        "?" => return Some(format!("?? {}", rest)),
+        // 1 <XY> <sub> <mH> <mI> <mW> <hH> <hI> <path>
+        "1" => (7, rest.get(..2)?),
+        // 2 <XY> <sub> <mH> <mI> <mW> <hH> <hI> <X><score> <path>\t<origPath>
+        "2" => (8, rest.get(..2)?),
+        // u <XY> <sub> <m1> <m2> <m3> <mW> <h1> <h2> <h3> <path>
+        "u" => (9, rest.get(..2)?),
+        _ => return None,
+    };
+    let path = rest.splitn(path_field + 1, ' ').nth(path_field)?;
+    let path = match path.split_once('\t') {
+        Some((path, orig)) => format!("{} -> {}", orig, path),
+        None => path.to_string(),
+    };
+    Some(format!("{} {}", xy.replace('.', " "), path))
+}
+
+/// Detect an in-progress operation from the marker files git leaves in
+/// its directory.
+fn operation(git_dir: &Path) -> Option<Operation> {
+    for (dir, step_file, total_file) in [("rebase-merge", "msgnum", "end"), ("rebase-apply", "next", "last")] {
+        let dir = git_dir.join(dir);
+        // `git am` also uses rebase-apply
+        if dir.is_dir() && !dir.join("applying").exists() {
+            let read = |name: &str| fs::read_to_string(dir.join(name)).ok().map(|s| s.trim().to_string());
+            return Some(Operation::Rebase {
+                step: read(step_file).and_then(|s| s.parse().ok()),
+                total: read(total_file).and_then(|s| s.parse().ok()),
+                branch: read("head-name").map(|s| s.trim_start_matches("refs/heads/").to_string()),
+            });
+        }
+    }
+
+    [
+        ("MERGE_HEAD", Operation::Merge),
+        ("CHERRY_PICK_HEAD", Operation::CherryPick),
+        ("REVERT_HEAD", Operation::Revert),
+        ("BISECT_LOG", Operation::Bisect),
+    ]
+    .into_iter()
+    .find(|(marker, _)| git_dir.join(marker).exists())
+    .map(|(_, operation)| operation)
+}
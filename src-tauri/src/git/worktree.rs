@@ -0,0 +1,273 @@
+use super::{main_repo_root, repo_root, run_git};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Branch namespace for worktrees DeckMind creates.
+const BRANCH_PREFIX: &str = "deckmind/";
+
+/// A worktree DeckMind created and is responsible for cleaning up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManagedWorktree {
+    /// Main repository the worktree belongs to.
+    pub repo: String,
+    pub path: String,
+    pub branch: String,
+    /// Commit the branch was created from.
+    pub base_commit: String,
+}
+
+/// An entry from `git worktree list`, annotated with DeckMind state.
+#[derive(Debug, Clone, Serialize)]
+pub struct WorktreeInfo {
+    pub path: String,
+    /// `None` for a detached HEAD.
+    pub branch: Option<String>,
+    pub head: String,
+    pub is_main: bool,
+    pub dirty: bool,
+    pub locked: bool,
+    /// The worktree directory is gone and `prune` would drop it.
+    pub prunable: bool,
+    /// Created by DeckMind (branch under `deckmind/`).
+    pub managed: bool,
+    /// Session currently running in this worktree, if any.
+    pub session_id: Option<String>,
+}
+
+/// Per-file change counts relative to a base commit.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileChange {
+    pub path: String,
+    /// Single-letter git status: A, M, D, R, ... or `?` for untracked.
+    pub status: String,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+/// Create a worktree at `path` on a new `branch` starting from `base`.
+pub fn add(repo: &Path, path: &Path, branch: &str, base: &str) -> Result<(), String> {
+    let path_str = path.to_string_lossy();
+    run_git(repo, &["worktree", "add", "-b", branch, &path_str, base]).map(|_| ())
+}
+
+/// Remove the worktree at `path`. `force` discards uncommitted changes.
+pub fn remove(repo: &Path, path: &Path, force: bool) -> Result<(), String> {
+    let path_str = path.to_string_lossy();
+    let mut args = vec!["worktree", "remove"];
+    if force {
+        args.push("--force");
+    }
+    args.push(&path_str);
+    run_git(repo, &args).map(|_| ())
+}
+
+/// Delete a local branch, even if it is not merged.
+pub fn delete_branch(repo: &Path, branch: &str) -> Result<(), String> {
+    run_git(repo, &["branch", "-D", branch]).map(|_| ())
+}
+
+/// Changes in the worktree at `dir` relative to `base`, including
+/// uncommitted edits and untracked files.
+pub fn changes_since(dir: &Path, base: &str) -> Result<Vec<FileChange>, String> {
+    let mut changes: Vec<FileChange> = Vec::new();
+
+    // Renames are reported as delete + add so both listings share paths
+    for line in run_git(dir, &["diff", "--name-status", "--no-renames", base])?.lines() {
+        if let Some((status, path)) = line.split_once('\t') {
+            changes.push(FileChange {
+                path: path.to_string(),
+                status: status.chars().next().unwrap_or('M').to_string(),
+                insertions: 0,
+                deletions: 0,
+            });
+        }
+    }
+
+    for line in run_git(dir, &["diff", "--numstat", "--no-renames", base])?.lines() {
+        let cols: Vec<&str> = line.splitn(3, '\t').collect();
+        if cols.len() < 3 {
+            continue;
+        }
+        // Binary files report "-" for both counts
+        if let Some(change) = changes.iter_mut().find(|c| c.path == cols[2]) {
+            change.insertions = cols[0].parse().unwrap_or(0);
+            change.deletions = cols[1].parse().unwrap_or(0);
+        }
+    }
+
+    for path in run_git(dir, &["ls-files", "--others", "--exclude-standard"])?.lines() {
+        let insertions = std::fs::read_to_string(dir.join(path))
+            .map(|content| content.lines().count())
+            .unwrap_or(0);
+        changes.push(FileChange {
+            path: path.to_string(),
+            status: "?".to_string(),
+            insertions,
+            deletions: 0,
+        });
+    }
+
+    Ok(changes)
+}
+
+/// Turn a free-form name into something safe for a branch and directory.
+pub(super) fn slugify(name: &str) -> String {
+    let slug: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let slug = slug
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    slug.chars().take(40).collect()
+}
+
+/// Unique branch/directory name: `<slug>-<id>`, or just `<id>`.
+fn branch_leaf(name: Option<&str>) -> String {
+    let short_id = Uuid::new_v4().to_string()[..8].to_string();
+    match name.map(slugify).filter(|s| !s.is_empty()) {
+        Some(slug) => format!("{}-{}", slug, short_id),
+        None => short_id,
+    }
+}
+
+/// Create a worktree for the repo containing `dir` under `root`, on a new
+/// generated branch (`deckmind/<name>-<id>`) starting from the current HEAD.
+pub fn create_managed(dir: &Path, root: &Path, name: Option<&str>) -> Result<ManagedWorktree, String> {
+    create_managed_from(dir, root, name, "HEAD")
+}
+
+/// Like `create_managed`, but branching from `base` instead of HEAD.
+pub fn create_managed_from(
+    dir: &Path,
+    root: &Path,
+    name: Option<&str>,
+    base: &str,
+) -> Result<ManagedWorktree, String> {
+    create_managed_named(dir, root, &branch_leaf(name), base)
+}
+
+/// Like `create_managed_from`, with `leaf` used as is for the branch
+/// (`deckmind/<leaf>`) and directory names.
+pub fn create_managed_named(dir: &Path, root: &Path, leaf: &str, base: &str) -> Result<ManagedWorktree, String> {
+    let repo = main_repo_root(dir)?;
+    let base_commit = run_git(&repo, &["rev-parse", &format!("{}^{{commit}}", base)])?;
+
+    let branch = format!("{}{}", BRANCH_PREFIX, leaf);
+
+    let repo_name = repo
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "repo".to_string());
+    let path = root.join(repo_name).join(leaf);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Cannot create {}: {}", parent.display(), e))?;
+    }
+
+    add(&repo, &path, &branch, &base_commit)?;
+    log::info!("Created worktree {} on {}", path.display(), branch);
+
+    Ok(ManagedWorktree {
+        repo: repo.to_string_lossy().to_string(),
+        path: path.to_string_lossy().to_string(),
+        branch,
+        base_commit,
+    })
+}
+
+/// Whether the worktree at `path` has uncommitted or untracked changes.
+pub fn is_dirty(path: &Path) -> Result<bool, String> {
+    run_git(path, &["status", "--porcelain"]).map(|out| !out.trim().is_empty())
+}
+
+/// List the worktrees of the repo containing `dir`. `owner` maps a
+/// worktree path to the session running in it.
+pub fn list(dir: &Path, owner: impl Fn(&str) -> Option<String>) -> Result<Vec<WorktreeInfo>, String> {
+    let repo = repo_root(dir)?;
+    let porcelain = run_git(&repo, &["worktree", "list", "--porcelain"])?;
+
+    let mut worktrees = Vec::new();
+    // Records are separated by blank lines; the first is the main worktree
+    for (index, record) in porcelain.split("\n\n").enumerate() {
+        let mut path = None;
+        let mut head = String::new();
+        let mut branch = None;
+        let mut locked = false;
+        let mut prunable = false;
+        for line in record.lines() {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "worktree" => path = Some(value.to_string()),
+                "HEAD" => head = value.to_string(),
+                "branch" => branch = Some(value.trim_start_matches("refs/heads/").to_string()),
+                "locked" => locked = true,
+                "prunable" => prunable = true,
+                _ => {}
+            }
+        }
+        let Some(path) = path else { continue };
+
+        let dirty = !prunable && is_dirty(Path::new(&path)).unwrap_or(false);
+        let managed = branch.as_deref().is_some_and(|b| b.starts_with(BRANCH_PREFIX));
+        let session_id = owner(&path);
+        worktrees.push(WorktreeInfo {
+            path,
+            branch,
+            head,
+            is_main: index == 0,
+            dirty,
+            locked,
+            prunable,
+            managed,
+            session_id,
+        });
+    }
+
+    Ok(worktrees)
+}
+
+/// Drop administrative entries for worktrees whose directories are gone.
+pub fn prune(dir: &Path) -> Result<(), String> {
+    let repo = repo_root(dir)?;
+    run_git(&repo, &["worktree", "prune"]).map(|_| ())
+}
+
+impl ManagedWorktree {
+    /// Rename the worktree's branch to a fresh `deckmind/<name>-<id>`.
+    pub fn rename_branch(&mut self, name: Option<&str>) -> Result<(), String> {
+        let branch = format!("{}{}", BRANCH_PREFIX, branch_leaf(name));
+        run_git(Path::new(&self.path), &["branch", "-m", &self.branch, &branch])?;
+        self.branch = branch;
+        Ok(())
+    }
+
+    /// Remove the worktree, and its branch if nothing was committed on it.
+    /// Refuses to touch a dirty worktree unless `force` is set, in which case
+    /// uncommitted changes and the branch are both discarded.
+    pub fn remove(&self, force: bool) -> Result<(), String> {
+        let repo = PathBuf::from(&self.repo);
+        let path = PathBuf::from(&self.path);
+
+        if !force && path.exists() && is_dirty(&path)? {
+            return Err(format!("Worktree {} has uncommitted changes", self.path));
+        }
+        remove(&repo, &path, force)?;
+
+        // Only delete the branch when it holds no work beyond its base
+        let ahead = run_git(&repo, &["rev-list", "--count", &format!("{}..{}", self.base_commit, self.branch)])
+            .ok()
+            .and_then(|n| n.trim().parse::<u32>().ok())
+            .unwrap_or(1);
+        if force || ahead == 0 {
+            delete_branch(&repo, &self.branch)?;
+        } else {
+            log::info!("Keeping branch {} ({} commits ahead of base)", self.branch, ahead);
+        }
+        Ok(())
+    }
+}
@@ -10,25 +10,12 @@ impl KeyboardInput {
         for mapping in mappings {
             if let Some(ref kb) = mapping.keyboard {
                 if kb.key.eq_ignore_ascii_case(key) && modifiers_match(&kb.modifiers, modifiers) {
-                    return Self::action_from_name(&mapping.action);
+                    return SemanticAction::from_id(&mapping.action);
                 }
             }
         }
         None
     }
-
-    fn action_from_name(name: &str) -> Option<SemanticAction> {
-        match name {
-            "explain" => Some(SemanticAction::Explain),
-            "fix" => Some(SemanticAction::Fix),
-            "continue" => Some(SemanticAction::Continue),
-            "plan" => Some(SemanticAction::Plan),
-            "summarize" => Some(SemanticAction::Summarize),
-            "context" => Some(SemanticAction::Context),
-            "interrupt" => Some(SemanticAction::Interrupt),
-            _ => None,
-        }
-    }
 }
 
 #[allow(dead_code)]
@@ -1,14 +1,27 @@
 mod actions;
 mod config;
 mod context;
+mod git;
 mod input;
+mod macros;
+mod procfs;
+mod project;
+mod responses;
 mod session;
 mod storage;
+mod testing;
+mod text;
 mod voice;
 
+use actions::ActionLibrary;
 use config::AppConfig;
+use context::{ContextCollector, TestResultsSource};
+use git::pool::WorktreePool;
+use macros::MacroRunner;
+use session::best_of_n::BestOfNManager;
 use session::SessionManager;
 use storage::StorageManager;
+use testing::TestRunner;
 use voice::VoiceEngine;
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
@@ -17,8 +30,16 @@ use tokio::sync::Mutex;
 pub struct AppState {
     pub session_manager: Arc<Mutex<SessionManager>>,
     pub config: Arc<Mutex<AppConfig>>,
+    /// Action templates from `~/.deckmind/actions`.
+    pub actions: Arc<Mutex<ActionLibrary>>,
     pub storage: Arc<Mutex<StorageManager>>,
     pub voice_engine: Arc<Mutex<VoiceEngine>>,
+    pub best_of_n: Arc<Mutex<BestOfNManager>>,
+    pub worktree_pool: Arc<Mutex<WorktreePool>>,
+    /// Registered context sources; fixed after startup.
+    pub context: Arc<ContextCollector>,
+    pub tests: Arc<TestRunner>,
+    pub macros: Arc<MacroRunner>,
     pub download_cancel: Arc<AtomicBool>,
 }
 
@@ -26,19 +47,37 @@ pub struct AppState {
 pub fn run() {
     env_logger::init();
 
-    let config = AppConfig::load().unwrap_or_default();
+    let mut config = AppConfig::load().unwrap_or_default();
+    let actions = ActionLibrary::load().unwrap_or_else(|e| {
+        log::warn!("Using built-in actions: {}", e);
+        ActionLibrary::builtin()
+    });
+    actions.bind_buttons(&mut config.button_mappings);
     let storage = StorageManager::new().expect("Failed to initialize storage");
     let session_manager = SessionManager::new();
     let voice_engine = VoiceEngine::new(&config.whisper_model);
+    let worktree_root = config.worktree_root();
+    let worktree_pools = config.worktree_pools.clone();
+    let tests = Arc::new(TestRunner::new());
+    let mut context = ContextCollector::new();
+    context.register(Arc::new(TestResultsSource::new(tests.clone())));
 
     let app_state = AppState {
         session_manager: Arc::new(Mutex::new(session_manager)),
         config: Arc::new(Mutex::new(config)),
+        actions: Arc::new(Mutex::new(actions)),
         storage: Arc::new(Mutex::new(storage)),
         voice_engine: Arc::new(Mutex::new(voice_engine)),
+        best_of_n: Arc::new(Mutex::new(BestOfNManager::new())),
+        worktree_pool: Arc::new(Mutex::new(WorktreePool::new())),
+        context: Arc::new(context),
+        tests,
+        macros: Arc::new(MacroRunner::new()),
         download_cancel: Arc::new(AtomicBool::new(false)),
     };
 
+    let worktree_pool = app_state.worktree_pool.clone();
+
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .manage(app_state)
@@ -46,10 +85,38 @@ pub fn run() {
             commands::create_session,
             commands::close_session,
             commands::list_sessions,
+            commands::set_session_tags,
+            commands::broadcast,
+            commands::start_best_of_n,
+            commands::get_best_of_n,
+            commands::adopt_best_of_n,
+            commands::create_worktree,
+            commands::list_worktrees,
+            commands::remove_worktree,
+            commands::prune_worktrees,
+            commands::get_worktree_pool_status,
+            commands::get_session_diff,
+            commands::revert_hunk,
+            commands::revert_file,
+            commands::stage_hunk,
+            commands::get_action_history,
+            commands::list_checkpoints,
+            commands::diff_checkpoint,
+            commands::revert_checkpoint_change,
+            commands::restore_checkpoint,
+            commands::export_patches,
             commands::send_action,
             commands::send_message,
             commands::interrupt_session,
             commands::get_context,
+            commands::list_context_sources,
+            commands::run_tests,
+            commands::cancel_tests,
+            commands::get_test_results,
+            commands::record_visit,
+            commands::run_macro,
+            commands::cancel_macro,
+            commands::get_macro_run,
             commands::get_config,
             commands::update_config,
             commands::get_safety_mode,
@@ -59,6 +126,10 @@ pub fn run() {
             commands::pty_write,
             commands::pty_write_bytes,
             commands::build_action_prompt,
+            commands::list_actions,
+            commands::reload_actions,
+            commands::get_action_params,
+            commands::render_action,
             commands::get_claude_path,
             commands::get_session_flags,
             commands::list_directory,
@@ -84,37 +155,108 @@ pub fn run() {
             // Start gamepad polling thread (fire-and-forget, logs warning if no gamepad)
             input::gamepad::start_gamepad_thread(app.handle().clone());
 
+            // Start filling configured worktree pools in the background
+            tauri::async_runtime::spawn(async move {
+                git::pool::configure(&worktree_pool, worktree_root, worktree_pools).await;
+            });
+
             Ok(())
         })
         .run(tauri::generate_context!())
         .expect("error while running DeckMind");
 }
 
-mod commands {
+pub(crate) mod commands {
     use super::AppState;
-    use crate::actions::{SemanticAction, ActionRouter};
+    use crate::actions::{self, clipboard, params, ActionForm, ActionLibrary, ActionRouter, ActionTemplate, SemanticAction};
     use crate::config::SafetyMode;
-    use crate::context::ContextCollector;
-    use crate::session::SessionInfo;
+    use crate::context::{visits, ContextTarget, EnvironmentContext, Redactor, SourceStatus, VISIT_PLACEHOLDER};
+    use crate::procfs::ProcessScope;
+    use crate::session::best_of_n::{self, AgentProvider, BestOfNGroup, Candidate};
+    use crate::session::broadcast::{BroadcastPayload, BroadcastResult, BroadcastTarget};
+    use crate::session::interrupt::InterruptReport;
+    use crate::session::process::Delivery;
+    use crate::project::ProjectInfo;
+    use crate::session::{NewSession, ProjectConfig, SessionInfo};
+    use crate::responses::{self, ResponseMark};
+    use crate::storage::{MemoryEntry, VisitSnapshot};
+    use crate::testing::{self, TestReport, TestRun};
+    use crate::macros::MacroRun;
+    use crate::git::{self, checkpoint::{self, Checkpoint}, diff::{self, DiffStatus, FileDiff}, patches::{self, PatchExport, PatchFormat}, worktree::{self, ManagedWorktree, WorktreeInfo}};
     use serde::Serialize;
+    use std::collections::HashMap;
     use tauri::Emitter;
 
+    /// Create a session. With `worktree`, DeckMind creates a git worktree on
+    /// a fresh branch off `working_dir`'s repo and runs the session there.
     #[tauri::command]
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_session(
         app: tauri::AppHandle,
         state: tauri::State<'_, AppState>,
         name: Option<String>,
         working_dir: Option<String>,
         extra_flags: Option<String>,
+        tags: Option<Vec<String>>,
+        worktree: Option<bool>,
+        delete_worktree_on_close: Option<bool>,
     ) -> Result<SessionInfo, String> {
-        let mut manager = state.session_manager.lock().await;
         let config = state.config.lock().await;
         let claude_path = config.claude_path.clone();
+        let worktree_root = config.worktree_root();
+        let delete_worktree_on_close = delete_worktree_on_close.unwrap_or(config.delete_worktrees_on_close);
         drop(config);
-        manager
-            .create_session(name, working_dir, &claude_path, extra_flags, &app)
+
+        let managed = if worktree.unwrap_or(false) {
+            let dir = working_dir.clone().ok_or("A working directory is required for a worktree")?;
+            // Prefer a pre-warmed spare; fall back to creating one now
+            match git::pool::claim(&state.worktree_pool, &dir, name.clone()).await {
+                Some(wt) => Some(wt),
+                None => {
+                    let hint = name.clone();
+                    let wt = tokio::task::spawn_blocking(move || {
+                        worktree::create_managed(std::path::Path::new(&dir), &worktree_root, hint.as_deref())
+                    })
+                    .await
+                    .map_err(|e| e.to_string())??;
+                    Some(wt)
+                }
+            }
+        } else {
+            None
+        };
+
+        let mut manager = state.session_manager.lock().await;
+        let created = manager
+            .create_session(
+                NewSession {
+                    name,
+                    working_dir,
+                    claude_path,
+                    extra_flags,
+                    skip_permissions: true,
+                    tags: tags.unwrap_or_default(),
+                    worktree: managed.clone(),
+                    delete_worktree_on_close,
+                },
+                &app,
+            )
             .await
-            .map_err(|e| e.to_string())
+            .map_err(|e| e.to_string());
+        drop(manager);
+
+        // Don't leave the worktree and its branch behind for a session
+        // that never started
+        if let (Err(_), Some(wt)) = (&created, managed) {
+            let cleanup = tokio::task::spawn_blocking(move || wt.remove(true))
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|r| r);
+            if let Err(e) = cleanup {
+                log::warn!("Failed to remove worktree of a session that didn't start: {}", e);
+            }
+        }
+        created
     }
 
     #[tauri::command]
@@ -122,6 +264,8 @@ mod commands {
         state: tauri::State<'_, AppState>,
         session_id: String,
     ) -> Result<(), String> {
+        state.tests.forget(&session_id);
+        state.macros.forget(&session_id);
         let mut manager = state.session_manager.lock().await;
         manager
             .close_session(&session_id)
@@ -137,43 +281,419 @@ mod commands {
         Ok(manager.list_sessions())
     }
 
+    /// Replace the tags on a session (used to address sessions in a broadcast).
+    #[tauri::command]
+    pub async fn set_session_tags(
+        state: tauri::State<'_, AppState>,
+        session_id: String,
+        tags: Vec<String>,
+    ) -> Result<SessionInfo, String> {
+        let mut manager = state.session_manager.lock().await;
+        manager.set_tags(&session_id, tags).map_err(|e| e.to_string())
+    }
+
+    /// Send the same prompt or action to many sessions at once.
+    /// Each session gets a prompt built from its own context; busy sessions
+    /// queue it until they go idle. Returns one result per targeted session.
+    #[tauri::command]
+    pub async fn broadcast(
+        app: tauri::AppHandle,
+        state: tauri::State<'_, AppState>,
+        target: BroadcastTarget,
+        payload: BroadcastPayload,
+    ) -> Result<Vec<BroadcastResult>, String> {
+        // Interrupt has no prompt: each session escalates until its agent
+        // stops, all at once since that can take seconds
+        if let BroadcastPayload::Action { action: SemanticAction::Interrupt } = payload {
+            let session_ids = state.session_manager.lock().await.resolve_targets(&target);
+            let runs = session_ids.iter().map(|session_id| {
+                let state = &state;
+                async move {
+                    match interrupt(state, session_id).await {
+                        Ok(report) => BroadcastResult::interrupted(session_id, report),
+                        Err(e) => BroadcastResult::failed(session_id, e),
+                    }
+                }
+            });
+            return Ok(futures_util::future::join_all(runs).await);
+        }
+
+        let config = state.config.lock().await;
+        let safety_mode = config.safety_mode.clone();
+        drop(config);
+
+        let targets = {
+            let manager = state.session_manager.lock().await;
+            manager
+                .resolve_targets(&target)
+                .into_iter()
+                .map(|id| {
+                    let lookup = manager.get_prompt_sink(&id).map_err(|e| e.to_string());
+                    (id, lookup)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut results = Vec::with_capacity(targets.len());
+        for (session_id, lookup) in targets {
+            let sink = match lookup {
+                Ok(found) => found,
+                Err(e) => {
+                    results.push(BroadcastResult::failed(&session_id, e));
+                    continue;
+                }
+            };
+
+            if let BroadcastPayload::Action { action: SemanticAction::Macro { id } } = &payload {
+                results.push(match start_macro(&app, &state, &session_id, id).await {
+                    Ok(_) => BroadcastResult::delivered(&session_id, Delivery::Sent),
+                    Err(e) => BroadcastResult::failed(&session_id, e),
+                });
+                continue;
+            }
+
+            let prompt = match &payload {
+                BroadcastPayload::Prompt { text } => text_prompt(&state, Some(&session_id), text, None, None, HashMap::new()).await,
+                BroadcastPayload::Action { action } => action_prompt(&state, Some(&session_id), action, HashMap::new()).await,
+            };
+            let prompt = match prompt {
+                Ok(prompt) => prompt,
+                Err(e) => {
+                    results.push(BroadcastResult::failed(&session_id, e));
+                    continue;
+                }
+            };
+
+            let prompt_clone = prompt.clone();
+            let (written, mark) = tokio::sync::oneshot::channel();
+            let outcome = tokio::task::spawn_blocking(move || sink.deliver_tracked(&prompt_clone, written))
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|r| r);
+
+            match outcome {
+                Ok(delivery) => {
+                    if delivery == Delivery::Sent {
+                        let _ = app.emit("session-message-sent", serde_json::json!({
+                            "session_id": session_id,
+                            "message": prompt,
+                        }));
+                    }
+                    let entry_id = {
+                        let mut storage = state.storage.lock().await;
+                        match &payload {
+                            BroadcastPayload::Action { action } => storage.log_action(&session_id, action, &safety_mode, None),
+                            BroadcastPayload::Prompt { .. } => storage.log_message(&session_id, &safety_mode, None),
+                        }
+                    };
+                    responses::capture_delivered(&app, &session_id, entry_id, mark);
+                    results.push(BroadcastResult::delivered(&session_id, delivery));
+                }
+                Err(e) => results.push(BroadcastResult::failed(&session_id, e)),
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Run one task with N agents, each in its own git worktree off `base_dir`.
+    /// A `best-of-n-complete` event carries the comparison once all settle.
+    #[tauri::command]
+    pub async fn start_best_of_n(
+        app: tauri::AppHandle,
+        state: tauri::State<'_, AppState>,
+        task: String,
+        n: usize,
+        providers: Option<Vec<AgentProvider>>,
+        base_dir: String,
+        test_command: Option<String>,
+    ) -> Result<BestOfNGroup, String> {
+        let config = state.config.lock().await;
+        let claude_path = config.claude_path.clone();
+        let worktree_root = config.worktree_root();
+        let test_command = test_command.or_else(|| config.best_of_n_test_command.clone());
+        drop(config);
+
+        best_of_n::start(
+            task,
+            n,
+            providers.unwrap_or_default(),
+            base_dir,
+            worktree_root,
+            claude_path,
+            test_command,
+            state.session_manager.clone(),
+            state.best_of_n.clone(),
+            app,
+        )
+        .await
+    }
+
+    /// Get a best-of-N group, including the comparison once it is available.
+    /// Pass `refresh` to re-run the comparison (e.g. after nudging an agent).
+    #[tauri::command]
+    pub async fn get_best_of_n(
+        state: tauri::State<'_, AppState>,
+        group_id: String,
+        refresh: Option<bool>,
+    ) -> Result<BestOfNGroup, String> {
+        if refresh.unwrap_or(false) {
+            return best_of_n::compare(&group_id, &state.best_of_n).await;
+        }
+        let groups = state.best_of_n.lock().await;
+        groups
+            .get(&group_id)
+            .cloned()
+            .ok_or_else(|| "Group not found".to_string())
+    }
+
+    /// Keep one candidate's branch and clean up the other worktrees.
+    #[tauri::command]
+    pub async fn adopt_best_of_n(
+        state: tauri::State<'_, AppState>,
+        group_id: String,
+        session_id: String,
+    ) -> Result<Candidate, String> {
+        best_of_n::adopt(&group_id, &session_id, &state.session_manager, &state.best_of_n).await
+    }
+
+    /// Create a managed worktree for the repo containing `repo_dir`
+    /// without starting a session in it.
+    #[tauri::command]
+    pub async fn create_worktree(
+        state: tauri::State<'_, AppState>,
+        repo_dir: String,
+        name: Option<String>,
+    ) -> Result<ManagedWorktree, String> {
+        let root = state.config.lock().await.worktree_root();
+        tokio::task::spawn_blocking(move || {
+            worktree::create_managed(std::path::Path::new(&repo_dir), &root, name.as_deref())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    /// List worktrees of the repo containing `repo_dir`, with branch,
+    /// dirty state and the session running in each.
+    #[tauri::command]
+    pub async fn list_worktrees(
+        state: tauri::State<'_, AppState>,
+        repo_dir: String,
+    ) -> Result<Vec<WorktreeInfo>, String> {
+        let owners: std::collections::HashMap<String, String> = {
+            let manager = state.session_manager.lock().await;
+            manager
+                .list_sessions()
+                .into_iter()
+                .filter_map(|s| s.working_dir.map(|dir| (dir, s.id)))
+                .collect()
+        };
+        tokio::task::spawn_blocking(move || {
+            worktree::list(std::path::Path::new(&repo_dir), |path| owners.get(path).cloned())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    /// Remove a worktree (not the main one, and not while a session uses it).
+    /// `force` discards uncommitted changes; `delete_branch` also deletes
+    /// the worktree's branch.
+    #[tauri::command]
+    pub async fn remove_worktree(
+        state: tauri::State<'_, AppState>,
+        repo_dir: String,
+        path: String,
+        force: Option<bool>,
+        delete_branch: Option<bool>,
+    ) -> Result<(), String> {
+        if let Some(owner) = state.session_manager.lock().await.worktree_owner(&path) {
+            return Err(format!("Worktree is in use by session {}", owner));
+        }
+        tokio::task::spawn_blocking(move || {
+            let dir = std::path::Path::new(&repo_dir);
+            let entry = worktree::list(dir, |_| None)?
+                .into_iter()
+                .find(|w| w.path == path)
+                .ok_or_else(|| format!("No worktree at {}", path))?;
+            if entry.is_main {
+                return Err("Cannot remove the main worktree".to_string());
+            }
+            let repo = git::repo_root(dir)?;
+            worktree::remove(&repo, std::path::Path::new(&path), force.unwrap_or(false))?;
+            if let (Some(branch), true) = (entry.branch, delete_branch.unwrap_or(false)) {
+                worktree::delete_branch(&repo, &branch)?;
+            }
+            Ok(())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    /// Clean up git's records of worktrees whose directories were deleted.
+    #[tauri::command]
+    pub async fn prune_worktrees(repo_dir: String) -> Result<(), String> {
+        tokio::task::spawn_blocking(move || worktree::prune(std::path::Path::new(&repo_dir)))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
+    /// Spare worktrees ready per pooled repo.
+    #[tauri::command]
+    pub async fn get_worktree_pool_status(
+        state: tauri::State<'_, AppState>,
+    ) -> Result<Vec<git::pool::PoolStatus>, String> {
+        Ok(state.worktree_pool.lock().await.status())
+    }
+
+    /// Look up the directory a session works in, for git operations.
+    async fn session_dir(state: &tauri::State<'_, AppState>, session_id: &str) -> Result<std::path::PathBuf, String> {
+        let manager = state.session_manager.lock().await;
+        manager
+            .get_working_dir(session_id)
+            .map_err(|e| e.to_string())?
+            .map(std::path::PathBuf::from)
+            .ok_or_else(|| "Session has no working directory".to_string())
+    }
+
+    /// Staged, unstaged and untracked changes in a session's working tree,
+    /// split into hunks with line numbers. With `base`, staged changes are
+    /// relative to that ref instead of HEAD.
+    #[tauri::command]
+    pub async fn get_session_diff(
+        state: tauri::State<'_, AppState>,
+        session_id: String,
+        base: Option<String>,
+    ) -> Result<Vec<FileDiff>, String> {
+        let dir = session_dir(&state, &session_id).await?;
+        tokio::task::spawn_blocking(move || diff::working_tree_diff(&dir, base.as_deref()))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
+    /// Undo a single hunk. `header` (the hunk's `@@` line) guards against
+    /// acting on a diff that changed since it was fetched.
+    #[tauri::command]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn revert_hunk(
+        state: tauri::State<'_, AppState>,
+        session_id: String,
+        path: String,
+        status: DiffStatus,
+        hunk_index: usize,
+        header: Option<String>,
+        base: Option<String>,
+    ) -> Result<(), String> {
+        let dir = session_dir(&state, &session_id).await?;
+        tokio::task::spawn_blocking(move || {
+            diff::revert_hunk(&dir, &path, status, hunk_index, header.as_deref(), base.as_deref())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    /// Undo all changes of `status` in a file (untracked files are deleted).
+    #[tauri::command]
+    pub async fn revert_file(
+        state: tauri::State<'_, AppState>,
+        session_id: String,
+        path: String,
+        status: DiffStatus,
+        base: Option<String>,
+    ) -> Result<(), String> {
+        let dir = session_dir(&state, &session_id).await?;
+        tokio::task::spawn_blocking(move || diff::revert_file(&dir, &path, status, base.as_deref()))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
+    /// Stage a single unstaged or untracked hunk.
+    #[tauri::command]
+    pub async fn stage_hunk(
+        state: tauri::State<'_, AppState>,
+        session_id: String,
+        path: String,
+        status: DiffStatus,
+        hunk_index: usize,
+        header: Option<String>,
+    ) -> Result<(), String> {
+        let dir = session_dir(&state, &session_id).await?;
+        tokio::task::spawn_blocking(move || diff::stage_hunk(&dir, &path, status, hunk_index, header.as_deref()))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
+    /// Snapshot a session's working tree before delivering `label`, when
+    /// `requested` (or the `auto_checkpoint` setting) asks for it. Failures,
+    /// e.g. outside a git repo, are logged and never block delivery.
+    async fn take_checkpoint(
+        state: &tauri::State<'_, AppState>,
+        session_id: &str,
+        label: &str,
+        requested: Option<bool>,
+    ) -> Option<String> {
+        let enabled = match requested {
+            Some(enabled) => enabled,
+            None => state.config.lock().await.auto_checkpoint,
+        };
+        if !enabled {
+            return None;
+        }
+        let dir = session_dir(state, session_id).await.ok()?;
+        let (sid, label) = (session_id.to_string(), label.to_string());
+        match tokio::task::spawn_blocking(move || checkpoint::create(&dir, &sid, &label)).await {
+            Ok(Ok(cp)) => Some(cp.id),
+            Ok(Err(e)) => {
+                log::warn!("Checkpoint for session {} failed: {}", session_id, e);
+                None
+            }
+            Err(e) => {
+                log::warn!("Checkpoint for session {} failed: {}", session_id, e);
+                None
+            }
+        }
+    }
+
     #[tauri::command]
     pub async fn send_action(
         app: tauri::AppHandle,
         state: tauri::State<'_, AppState>,
         session_id: String,
         action: SemanticAction,
+        checkpoint: Option<bool>,
     ) -> Result<(), String> {
+        if let SemanticAction::Macro { id } = &action {
+            return start_macro(&app, &state, &session_id, id).await.map(|_| ());
+        }
+
         let config = state.config.lock().await;
         let safety_mode = config.safety_mode.clone();
         drop(config);
 
-        let context = ContextCollector::collect().await;
-        let prompt = ActionRouter::build_prompt(&action, &context);
-
-        // Get writer Arc, drop the manager lock, then do blocking write
-        let writer = {
+        // Get the prompt sink, then drop the manager lock before collecting
+        // context and doing the blocking write
+        let sink = {
             let manager = state.session_manager.lock().await;
-            manager.get_writer(&session_id).map_err(|e| e.to_string())?
+            manager.get_prompt_sink(&session_id).map_err(|e| e.to_string())?
         };
 
+        let prompt = action_prompt(&state, Some(&session_id), &action, HashMap::new()).await?;
+        let checkpoint_id = take_checkpoint(&state, &session_id, action.label(), checkpoint).await;
+        let mark = ResponseMark::take(&state, &session_id).await;
+
         let prompt_clone = prompt.clone();
-        tokio::task::spawn_blocking(move || {
-            let mut w = writer.lock().map_err(|e| e.to_string())?;
-            let mut data = prompt_clone.as_bytes().to_vec();
-            data.push(b'\r');
-            w.write(&data).map_err(|e| e.to_string())
-        })
-        .await
-        .map_err(|e| e.to_string())??;
+        tokio::task::spawn_blocking(move || sink.write_prompt(&prompt_clone))
+            .await
+            .map_err(|e| e.to_string())??;
 
         let _ = app.emit("session-message-sent", serde_json::json!({
             "session_id": session_id,
             "message": prompt,
         }));
 
-        let mut storage = state.storage.lock().await;
-        storage.log_action(&session_id, &action, &safety_mode);
+        let entry_id = state.storage.lock().await.log_action(&session_id, &action, &safety_mode, checkpoint_id);
+        if let Some(mark) = mark {
+            responses::capture(&app, &session_id, entry_id, mark);
+        }
 
         Ok(())
     }
@@ -184,54 +704,444 @@ mod commands {
         state: tauri::State<'_, AppState>,
         session_id: String,
         message: String,
+        checkpoint: Option<bool>,
     ) -> Result<(), String> {
-        // Get writer Arc, drop the manager lock, then do blocking write
-        let writer = {
+        // Get the prompt sink, drop the manager lock, then do blocking write
+        let sink = {
             let manager = state.session_manager.lock().await;
-            manager.get_writer(&session_id).map_err(|e| e.to_string())?
+            manager.get_prompt_sink(&session_id).map_err(|e| e.to_string())?
         };
 
+        let checkpoint_id = take_checkpoint(&state, &session_id, "Message", checkpoint).await;
+        let mark = ResponseMark::take(&state, &session_id).await;
+
         let msg = message.clone();
-        tokio::task::spawn_blocking(move || {
-            let mut w = writer.lock().map_err(|e| e.to_string())?;
-            let mut data = msg.as_bytes().to_vec();
-            data.push(b'\r');
-            w.write(&data).map_err(|e| e.to_string())
-        })
-        .await
-        .map_err(|e| e.to_string())??;
+        tokio::task::spawn_blocking(move || sink.write_prompt(&msg))
+            .await
+            .map_err(|e| e.to_string())??;
 
         let _ = app.emit("session-message-sent", serde_json::json!({
             "session_id": session_id,
             "message": message,
         }));
 
+        let safety_mode = state.config.lock().await.safety_mode.clone();
+        let entry_id = state.storage.lock().await.log_message(&session_id, &safety_mode, checkpoint_id);
+        if let Some(mark) = mark {
+            responses::capture(&app, &session_id, entry_id, mark);
+        }
+
         Ok(())
     }
 
+    /// Export the commits on a session's branch since `base` (default: the
+    /// commit its worktree was created from) to `dest`, with a cover letter
+    /// that embeds the session's action log.
     #[tauri::command]
-    pub async fn interrupt_session(
+    pub async fn export_patches(
         state: tauri::State<'_, AppState>,
         session_id: String,
-    ) -> Result<(), String> {
-        // Get writer Arc, then drop the async lock immediately
-        let writer = {
+        base: Option<String>,
+        dest: String,
+        format: Option<PatchFormat>,
+    ) -> Result<PatchExport, String> {
+        let info = {
             let manager = state.session_manager.lock().await;
-            manager.get_writer(&session_id).map_err(|e| e.to_string())?
+            manager
+                .list_sessions()
+                .into_iter()
+                .find(|s| s.id == session_id)
+                .ok_or_else(|| format!("Session not found: {}", session_id))?
+        };
+        let dir = info
+            .working_dir
+            .clone()
+            .map(std::path::PathBuf::from)
+            .ok_or_else(|| "Session has no working directory".to_string())?;
+        let base = base
+            .or_else(|| info.worktree.as_ref().map(|wt| wt.base_commit.clone()))
+            .ok_or_else(|| "No base given and the session has no managed worktree".to_string())?;
+
+        let action_log: Vec<String> = {
+            let storage = state.storage.lock().await;
+            storage
+                .get_session_entries(&session_id)
+                .into_iter()
+                .map(|e| match &e.checkpoint {
+                    Some(cp) => format!("{} {} (checkpoint {})", e.timestamp.to_rfc3339(), e.action, cp),
+                    None => format!("{} {}", e.timestamp.to_rfc3339(), e.action),
+                })
+                .collect()
         };
 
-        // Send Ctrl+C on a blocking thread
+        let title = format!("DeckMind session: {}", info.name);
         tokio::task::spawn_blocking(move || {
-            let mut w = writer.lock().map_err(|e| e.to_string())?;
-            w.write(&[0x03]).map_err(|e| e.to_string())
+            patches::export(&dir, &base, std::path::Path::new(&dest), format.unwrap_or_default(), &title, &action_log)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    /// What was sent to a session and what the agent replied, newest
+    /// first.
+    #[tauri::command]
+    pub async fn get_action_history(
+        state: tauri::State<'_, AppState>,
+        session_id: String,
+    ) -> Result<Vec<MemoryEntry>, String> {
+        let storage = state.storage.lock().await;
+        Ok(storage.get_session_entries(&session_id).into_iter().rev().cloned().collect())
+    }
+
+    /// Checkpoints taken for a session, newest first.
+    #[tauri::command]
+    pub async fn list_checkpoints(
+        state: tauri::State<'_, AppState>,
+        session_id: String,
+    ) -> Result<Vec<Checkpoint>, String> {
+        let dir = session_dir(&state, &session_id).await?;
+        tokio::task::spawn_blocking(move || checkpoint::list(&dir, &session_id))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
+    /// Changes made to the session's working tree since a checkpoint.
+    #[tauri::command]
+    pub async fn diff_checkpoint(
+        state: tauri::State<'_, AppState>,
+        session_id: String,
+        checkpoint_id: String,
+    ) -> Result<Vec<FileDiff>, String> {
+        let dir = session_dir(&state, &session_id).await?;
+        tokio::task::spawn_blocking(move || checkpoint::diff(&dir, &session_id, &checkpoint_id))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
+    /// Undo one hunk of the changes to a file since a checkpoint, or the
+    /// whole file without `hunk_index`. `header` guards against a stale view.
+    #[tauri::command]
+    pub async fn revert_checkpoint_change(
+        state: tauri::State<'_, AppState>,
+        session_id: String,
+        checkpoint_id: String,
+        path: String,
+        hunk_index: Option<usize>,
+        header: Option<String>,
+    ) -> Result<(), String> {
+        let dir = session_dir(&state, &session_id).await?;
+        tokio::task::spawn_blocking(move || {
+            let hunk = hunk_index.map(|index| (index, header.as_deref()));
+            checkpoint::revert(&dir, &session_id, &checkpoint_id, &path, hunk)
         })
         .await
         .map_err(|e| e.to_string())?
     }
 
+    /// Roll the session's working tree back to a checkpoint. Returns the
+    /// checkpoint taken of the state being replaced, so it can be undone.
+    #[tauri::command]
+    pub async fn restore_checkpoint(
+        state: tauri::State<'_, AppState>,
+        session_id: String,
+        checkpoint_id: String,
+    ) -> Result<Checkpoint, String> {
+        let dir = session_dir(&state, &session_id).await?;
+        tokio::task::spawn_blocking(move || checkpoint::restore(&dir, &session_id, &checkpoint_id))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
+    /// Stop what the agent is doing, escalating from Escape and Ctrl+C to
+    /// signals until the session goes idle. The shell is left running.
     #[tauri::command]
-    pub async fn get_context() -> Result<crate::context::EnvironmentContext, String> {
-        Ok(ContextCollector::collect().await)
+    pub async fn interrupt_session(
+        state: tauri::State<'_, AppState>,
+        session_id: String,
+    ) -> Result<InterruptReport, String> {
+        interrupt(&state, &session_id).await
+    }
+
+    pub(crate) async fn interrupt(state: &tauri::State<'_, AppState>, session_id: &str) -> Result<InterruptReport, String> {
+        let target = {
+            let manager = state.session_manager.lock().await;
+            manager.interrupt_target(session_id).map_err(|e| e.to_string())?
+        };
+        let config = state.config.lock().await.interrupt.clone();
+        let report = tokio::task::spawn_blocking(move || target.escalate(&config))
+            .await
+            .map_err(|e| e.to_string())?;
+        match report.succeeded {
+            Some(step) => log::info!("Interrupted session {} with {}", session_id, step.label()),
+            None => log::warn!("Could not interrupt session {}", session_id),
+        }
+        Ok(report)
+    }
+
+    /// Where to collect context for a session, or for the app's cwd
+    /// without one.
+    async fn context_target(state: &tauri::State<'_, AppState>, session_id: Option<&str>) -> Result<ContextTarget, String> {
+        Ok(match session_id {
+            Some(id) => {
+                let manager = state.session_manager.lock().await;
+                let dir = manager.get_working_dir(id).map_err(|e| e.to_string())?;
+                let mut target = ContextTarget::new(dir.as_deref(), Some(id), manager.process_scope(id));
+                target.shell = manager.shell(id).or(target.shell);
+                target.shell_pid = manager.shell_pid(id);
+                target.terminal = manager.terminal_output(id);
+                target
+            }
+            None => ContextTarget::new(None, None, ProcessScope::default()),
+        })
+    }
+
+    /// Collect context for a session (or DeckMind's own cwd without one),
+    /// limited to the `only` sources and cut down to `budget` characters
+    /// when given.
+    async fn gather_context(
+        state: &tauri::State<'_, AppState>,
+        session_id: Option<&str>,
+        only: Option<&[&str]>,
+        budget: Option<usize>,
+    ) -> Result<EnvironmentContext, String> {
+        let target = context_target(state, session_id).await?;
+        let (settings, redactor) = {
+            let config = state.config.lock().await;
+            (config.context_sources.clone(), Redactor::new(&config.redaction))
+        };
+        let context = state.context.collect(target, &settings, only, &redactor).await;
+        Ok(match budget {
+            Some(budget) => context.fit(budget),
+            None => context,
+        })
+    }
+
+    /// Context for filling `template`: `gather_context`, plus what changed
+    /// since the user's last visit if the template asks for it.
+    async fn prompt_context(
+        state: &tauri::State<'_, AppState>,
+        session_id: Option<&str>,
+        only: Option<&[&str]>,
+        budget: Option<usize>,
+        template: &str,
+    ) -> Result<EnvironmentContext, String> {
+        let mut context = gather_context(state, session_id, only, budget).await?;
+        if let Some(id) = session_id.filter(|_| template.contains(VISIT_PLACEHOLDER)) {
+            let (project, now) = visit_snapshot(state, id).await?;
+            let last = state.storage.lock().await.last_visit(&project).cloned();
+            if let Some(last) = last {
+                let dir = context.cwd.clone();
+                let changes = tokio::task::spawn_blocking(move || visits::describe_changes(&dir, &last, &now))
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let (changes, _) = Redactor::new(&state.config.lock().await.redaction).redact(&changes);
+                context.changes_since_last_visit = Some(changes);
+            }
+        }
+        Ok(context)
+    }
+
+    /// The prompt for `action`, from its template and the context the
+    /// template asks for. `vars` fills placeholders ahead of context.
+    pub(crate) async fn action_prompt(
+        state: &tauri::State<'_, AppState>,
+        session_id: Option<&str>,
+        action: &SemanticAction,
+        mut vars: HashMap<String, String>,
+    ) -> Result<String, String> {
+        let template = state
+            .actions
+            .lock()
+            .await
+            .template_for(action)
+            .cloned()
+            .ok_or_else(|| format!("No template for action {}", action.id()))?;
+        let budget = state.config.lock().await.context_budget_for(Some(action.id()));
+        let sources = template.sources();
+        let only: Option<Vec<&str>> = sources.as_ref().map(|s| s.iter().map(String::as_str).collect());
+        let context = prompt_context(state, session_id, only.as_deref(), Some(budget), &template.prompt).await?;
+        // Triggered without the form: parameters take their defaults
+        let values = params::values(&template.params, &vars)?;
+        vars.extend(values);
+        template_vars(state, &template.prompt, &mut vars).await?;
+        if let SemanticAction::Voice { transcription } = action {
+            vars.insert("input".to_string(), transcription.clone());
+        }
+        Ok(ActionRouter::build_prompt(&template, &context, &vars))
+    }
+
+    /// Fill a free-form prompt template, collecting context from `sources`
+    /// (plus those its placeholders need) when given.
+    pub(crate) async fn text_prompt(
+        state: &tauri::State<'_, AppState>,
+        session_id: Option<&str>,
+        template: &str,
+        sources: Option<&[String]>,
+        action_id: Option<&str>,
+        mut vars: HashMap<String, String>,
+    ) -> Result<String, String> {
+        let sources = actions::required_sources(template, sources);
+        let only: Option<Vec<&str>> = sources.as_ref().map(|s| s.iter().map(String::as_str).collect());
+        let budget = state.config.lock().await.context_budget_for(action_id);
+        let context = prompt_context(state, session_id, only.as_deref(), Some(budget), template).await?;
+        template_vars(state, template, &mut vars).await?;
+        Ok(context.fill_template_with(template, &vars))
+    }
+
+    /// Add placeholder values that don't come from context. The clipboard
+    /// is only read, and redacted, when `template` uses it.
+    async fn template_vars(
+        state: &tauri::State<'_, AppState>,
+        template: &str,
+        vars: &mut HashMap<String, String>,
+    ) -> Result<(), String> {
+        let wants_clipboard = template.contains("{clipboard}") || template.contains("{#if clipboard}");
+        if wants_clipboard && !vars.contains_key("clipboard") {
+            let text = tokio::task::spawn_blocking(clipboard::read).await.map_err(|e| e.to_string())?;
+            if let Some(text) = text {
+                let (text, _) = Redactor::new(&state.config.lock().await.redaction).redact(&text);
+                vars.insert("clipboard".to_string(), text);
+            }
+        }
+        Ok(())
+    }
+
+    /// The session's project key and its current state.
+    async fn visit_snapshot(state: &tauri::State<'_, AppState>, session_id: &str) -> Result<(String, VisitSnapshot), String> {
+        let target = context_target(state, Some(session_id)).await?;
+        let sessions = state.session_manager.lock().await.list_sessions();
+        tokio::task::spawn_blocking(move || {
+            let project = visits::project_key(&target.cwd);
+            let snapshot = visits::capture(&target, &project, &sessions);
+            (project, snapshot)
+        })
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    /// Note that the user is looking at a session, so a later Context
+    /// action can say what changed since.
+    #[tauri::command]
+    pub async fn record_visit(
+        state: tauri::State<'_, AppState>,
+        session_id: String,
+    ) -> Result<(), String> {
+        let (project, snapshot) = visit_snapshot(&state, &session_id).await?;
+        state.storage.lock().await.record_visit(&project, snapshot);
+        Ok(())
+    }
+
+    async fn start_macro(
+        app: &tauri::AppHandle,
+        state: &tauri::State<'_, AppState>,
+        session_id: &str,
+        macro_id: &str,
+    ) -> Result<MacroRun, String> {
+        let (def, mut actions) = {
+            let config = state.config.lock().await;
+            let def = config
+                .macros
+                .iter()
+                .find(|m| m.id == macro_id)
+                .cloned()
+                .ok_or_else(|| format!("No macro {}", macro_id))?;
+            let actions: std::collections::HashSet<String> = config.custom_actions.iter().map(|a| a.id.clone()).collect();
+            (def, actions)
+        };
+        actions.extend(state.actions.lock().await.list().iter().map(|t| t.id.clone()));
+        state.macros.start(app.clone(), session_id, def, &actions)
+    }
+
+    /// Run one of the configured macros in a session. Progress arrives as
+    /// `macro-progress` events and the result as `macro-finished`.
+    #[tauri::command]
+    pub async fn run_macro(
+        app: tauri::AppHandle,
+        state: tauri::State<'_, AppState>,
+        session_id: String,
+        macro_id: String,
+    ) -> Result<MacroRun, String> {
+        start_macro(&app, &state, &session_id, &macro_id).await
+    }
+
+    #[tauri::command]
+    pub async fn cancel_macro(
+        state: tauri::State<'_, AppState>,
+        session_id: String,
+    ) -> Result<bool, String> {
+        Ok(state.macros.cancel(&session_id))
+    }
+
+    #[tauri::command]
+    pub async fn get_macro_run(
+        state: tauri::State<'_, AppState>,
+        session_id: String,
+    ) -> Result<Option<MacroRun>, String> {
+        Ok(state.macros.latest(&session_id))
+    }
+
+    #[tauri::command]
+    pub async fn get_context(
+        state: tauri::State<'_, AppState>,
+        session_id: Option<String>,
+    ) -> Result<EnvironmentContext, String> {
+        gather_context(&state, session_id.as_deref(), None, None).await
+    }
+
+    /// Registered context sources with their effective settings.
+    #[tauri::command]
+    pub async fn list_context_sources(
+        state: tauri::State<'_, AppState>,
+    ) -> Result<Vec<SourceStatus>, String> {
+        let settings = state.config.lock().await.context_sources.clone();
+        Ok(state.context.sources(&settings))
+    }
+
+    /// Run the session's tests in the background: `command` if given,
+    /// else `test` from `.deckmind.yaml`, else the command detected from
+    /// the project's manifest. Progress arrives as `test-progress` events
+    /// and the result as `test-finished`.
+    #[tauri::command]
+    pub async fn run_tests(
+        app: tauri::AppHandle,
+        state: tauri::State<'_, AppState>,
+        session_id: String,
+        command: Option<String>,
+        timeout_secs: Option<u64>,
+    ) -> Result<TestReport, String> {
+        let dir = session_dir(&state, &session_id).await?;
+
+        let project = ProjectConfig::load(&dir)?.unwrap_or_default();
+        let command = command
+            .or(project.test)
+            .or_else(|| ProjectInfo::detect(&dir).and_then(|p| p.test_cmd))
+            .ok_or("No test command configured or detected")?;
+
+        let run = TestRun {
+            report_file: project.test_report.map(|f| dir.join(f)),
+            timeout: timeout_secs.map(std::time::Duration::from_secs).unwrap_or(testing::DEFAULT_TIMEOUT),
+            dir,
+            command,
+        };
+        state.tests.start(app, &session_id, run)
+    }
+
+    /// Stop the session's test run. Returns false if none was running.
+    #[tauri::command]
+    pub async fn cancel_tests(
+        state: tauri::State<'_, AppState>,
+        session_id: String,
+    ) -> Result<bool, String> {
+        Ok(state.tests.cancel(&session_id))
+    }
+
+    /// The session's latest (or current) test run.
+    #[tauri::command]
+    pub async fn get_test_results(
+        state: tauri::State<'_, AppState>,
+        session_id: String,
+    ) -> Result<Option<TestReport>, String> {
+        Ok(state.tests.latest(&session_id))
     }
 
     #[tauri::command]
@@ -248,8 +1158,19 @@ mod commands {
         new_config: crate::config::AppConfig,
     ) -> Result<(), String> {
         let mut config = state.config.lock().await;
+        let pools_changed = config.worktree_pools != new_config.worktree_pools
+            || config.worktree_root() != new_config.worktree_root();
         *config = new_config.clone();
-        config.save().map_err(|e| e.to_string())
+        config.save().map_err(|e| e.to_string())?;
+        drop(config);
+
+        if pools_changed {
+            let pool = state.worktree_pool.clone();
+            tokio::spawn(async move {
+                git::pool::configure(&pool, new_config.worktree_root(), new_config.worktree_pools).await;
+            });
+        }
+        Ok(())
     }
 
     #[tauri::command]
@@ -347,11 +1268,87 @@ mod commands {
     /// text input) instead of writing directly to the PTY.
     #[tauri::command]
     pub async fn build_action_prompt(
+        state: tauri::State<'_, AppState>,
         action: SemanticAction,
+        session_id: Option<String>,
     ) -> Result<String, String> {
-        let context = ContextCollector::collect().await;
-        let prompt = ActionRouter::build_prompt(&action, &context);
-        Ok(prompt)
+        action_prompt(&state, session_id.as_deref(), &action, HashMap::new()).await
+    }
+
+    /// Action templates, built-in and from `~/.deckmind/actions`.
+    #[tauri::command]
+    pub async fn list_actions(state: tauri::State<'_, AppState>) -> Result<Vec<ActionTemplate>, String> {
+        Ok(state.actions.lock().await.list().to_vec())
+    }
+
+    /// A custom action from config.yaml, or else an action template.
+    async fn find_action(state: &tauri::State<'_, AppState>, action_id: &str) -> Result<ActionTemplate, String> {
+        if let Some(custom) = state.config.lock().await.custom_actions.iter().find(|a| a.id == action_id) {
+            return Ok(ActionTemplate::from_custom(custom));
+        }
+        state
+            .actions
+            .lock()
+            .await
+            .get(action_id)
+            .cloned()
+            .ok_or_else(|| format!("Unknown action {}", action_id))
+    }
+
+    /// First step of running a parameterised action: what to ask the user
+    /// for, with file and directory choices listed for the session.
+    #[tauri::command]
+    pub async fn get_action_params(
+        state: tauri::State<'_, AppState>,
+        action_id: String,
+        session_id: Option<String>,
+    ) -> Result<ActionForm, String> {
+        let template = find_action(&state, &action_id).await?;
+        let cwd = context_target(&state, session_id.as_deref()).await?.cwd;
+        let mut recent: Vec<String> = state
+            .storage
+            .lock()
+            .await
+            .recent_projects()
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        for session in state.session_manager.lock().await.list_sessions() {
+            if let Some(dir) = session.working_dir {
+                if !recent.contains(&dir) {
+                    recent.push(dir);
+                }
+            }
+        }
+        tokio::task::spawn_blocking(move || params::form(&template, std::path::Path::new(&cwd), &recent))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
+    /// Second step: build the action's prompt with the values collected
+    /// for its parameters.
+    #[tauri::command]
+    pub async fn render_action(
+        state: tauri::State<'_, AppState>,
+        action_id: String,
+        session_id: Option<String>,
+        values: HashMap<String, String>,
+    ) -> Result<String, String> {
+        let template = find_action(&state, &action_id).await?;
+        let vars = params::values(&template.params, &values)?;
+        let prompt = text_prompt(&state, session_id.as_deref(), &template.prompt, template.sources.as_deref(), Some(&action_id), vars).await?;
+        Ok(redact_prompt(&state, &prompt).await)
+    }
+
+    /// Re-read `~/.deckmind/actions` after its files were edited.
+    #[tauri::command]
+    pub async fn reload_actions(state: tauri::State<'_, AppState>) -> Result<Vec<ActionTemplate>, String> {
+        let library = tokio::task::spawn_blocking(|| ActionLibrary::load().map_err(|e| e.to_string()))
+            .await
+            .map_err(|e| e.to_string())??;
+        let templates = library.list().to_vec();
+        *state.actions.lock().await = library;
+        Ok(templates)
     }
 
     /// Get the extra launch flags stored for a session so the frontend
@@ -494,14 +1491,30 @@ mod commands {
         config.save().map_err(|e| e.to_string())
     }
 
-    /// Build a custom action prompt by replacing {context} in the template.
+    /// Build a custom action prompt by filling the template's placeholders,
+    /// with context collected from `sources` only when given and sized by
+    /// the budget configured for `action_id`.
     #[tauri::command]
     pub async fn build_custom_prompt(
+        state: tauri::State<'_, AppState>,
         template: String,
+        session_id: Option<String>,
+        sources: Option<Vec<String>>,
+        action_id: Option<String>,
     ) -> Result<String, String> {
-        let context = ContextCollector::collect().await;
-        let prompt = template.replace("{context}", &context.to_prompt_string());
-        Ok(prompt)
+        let prompt = text_prompt(&state, session_id.as_deref(), &template, sources.as_deref(), action_id.as_deref(), HashMap::new()).await?;
+        Ok(redact_prompt(&state, &prompt).await)
+    }
+
+    /// Redact a whole custom prompt: the template itself, or values typed
+    /// for its parameters, may carry pasted credentials.
+    async fn redact_prompt(state: &tauri::State<'_, AppState>, prompt: &str) -> String {
+        let redactor = Redactor::new(&state.config.lock().await.redaction);
+        let (prompt, count) = redactor.redact(prompt);
+        if count > 0 {
+            log::info!("Redacted {} secret(s) from custom prompt", count);
+        }
+        prompt
     }
 
     /// Delete a downloaded whisper model file.
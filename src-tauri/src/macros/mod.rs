@@ -0,0 +1,533 @@
+//! Running macros: scripted sequences of actions, waits and shell
+//! commands against one session.
+
+use crate::actions::SemanticAction;
+use crate::commands;
+use crate::config::{MacroDef, MacroStepKind, WaitFor};
+use crate::context::Redactor;
+use crate::responses;
+use crate::session::process::{Delivery, WriteMark};
+use crate::text::tail;
+use crate::AppState;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::{oneshot, watch};
+
+/// Limit for a `wait` step without `timeout_secs`.
+const WAIT_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Limit for a `shell` step without `timeout_secs`.
+const SHELL_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// How often waits check the session.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Output kept from a `shell` or `wait` step for `{output}` and branches.
+const OUTPUT_TAIL: usize = 4000;
+
+/// Lines held while a wait runs; `OUTPUT_TAIL` is cut from their end.
+const MAX_SEEN_LINES: usize = 500;
+
+/// Branch target that stops the macro.
+const END: &str = "end";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MacroStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// A step that has run.
+#[derive(Debug, Clone, Serialize)]
+pub struct StepResult {
+    /// Position in the macro's `steps`.
+    pub index: usize,
+    pub step: String,
+    pub outcome: String,
+}
+
+/// A session's latest macro run.
+#[derive(Debug, Clone, Serialize)]
+pub struct MacroRun {
+    pub session_id: String,
+    pub macro_id: String,
+    pub label: String,
+    pub status: MacroStatus,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Step being run.
+    pub current: Option<usize>,
+    pub steps: Vec<StepResult>,
+    pub error: Option<String>,
+}
+
+/// A macro checked and ready to run: labels resolved, regexes compiled.
+struct Plan {
+    def: MacroDef,
+    labels: HashMap<String, usize>,
+    patterns: HashMap<usize, Regex>,
+}
+
+impl Plan {
+    /// `actions` holds the ids of the custom actions a step may name.
+    fn new(def: MacroDef, actions: &HashSet<String>) -> Result<Self, String> {
+        let mut labels = HashMap::new();
+        for (i, step) in def.steps.iter().enumerate() {
+            if let Some(label) = &step.label {
+                if label == END || labels.insert(label.clone(), i).is_some() {
+                    return Err(format!("Step label {:?} is reserved or used twice", label));
+                }
+            }
+        }
+
+        let mut patterns = HashMap::new();
+        for (i, step) in def.steps.iter().enumerate() {
+            let pattern = match &step.kind {
+                MacroStepKind::Action(id) => {
+                    match SemanticAction::from_id(id) {
+                        Some(SemanticAction::Macro { .. }) => return Err(format!("Step {}: macros can't run other macros", i + 1)),
+                        Some(SemanticAction::Custom { id }) if !actions.contains(&id) => {
+                            return Err(format!("Step {}: no action {:?}", i + 1, id))
+                        }
+                        Some(_) => {}
+                        // Only voice has no action of its own
+                        None => return Err(format!("Step {}: {} needs a transcription, which a macro can't give", i + 1, id)),
+                    }
+                    None
+                }
+                MacroStepKind::Wait(WaitFor::Pattern(pattern)) => Some(pattern),
+                MacroStepKind::Branch(branch) => {
+                    for target in [&branch.then, &branch.otherwise].into_iter().flatten() {
+                        if target != END && !labels.contains_key(target) {
+                            return Err(format!("Step {}: no step is labelled {:?}", i + 1, target));
+                        }
+                    }
+                    branch.matches.as_ref()
+                }
+                _ => None,
+            };
+            if let Some(pattern) = pattern {
+                let regex = Regex::new(pattern).map_err(|e| format!("Step {}: {}", i + 1, e))?;
+                patterns.insert(i, regex);
+            }
+        }
+
+        Ok(Plan { def, labels, patterns })
+    }
+}
+
+/// Runs macros per session, one at a time, and keeps each session's
+/// latest run.
+pub struct MacroRunner {
+    running: Mutex<HashMap<String, watch::Sender<bool>>>,
+    runs: Mutex<HashMap<String, MacroRun>>,
+}
+
+impl MacroRunner {
+    pub fn new() -> Self {
+        MacroRunner {
+            running: Mutex::new(HashMap::new()),
+            runs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check `def` against the known `actions` and start it in the
+    /// background. Each step is reported as a `macro-progress` event, the
+    /// end as `macro-finished`, and `notify` steps as `macro-notify`.
+    pub fn start(
+        self: &Arc<Self>,
+        app: AppHandle,
+        session_id: &str,
+        def: MacroDef,
+        actions: &HashSet<String>,
+    ) -> Result<MacroRun, String> {
+        let plan = Plan::new(def, actions)?;
+        let (cancel_tx, cancel_rx) = watch::channel(false);
+        {
+            let mut running = self.running.lock().map_err(|e| e.to_string())?;
+            if running.contains_key(session_id) {
+                return Err("A macro is already running in this session".to_string());
+            }
+            running.insert(session_id.to_string(), cancel_tx);
+        }
+
+        let run = MacroRun {
+            session_id: session_id.to_string(),
+            macro_id: plan.def.id.clone(),
+            label: plan.def.label.clone(),
+            status: MacroStatus::Running,
+            started_at: Utc::now(),
+            finished_at: None,
+            current: None,
+            steps: Vec::new(),
+            error: None,
+        };
+        self.store(run.clone());
+
+        let runner = self.clone();
+        let initial = run.clone();
+        tokio::spawn(async move {
+            let run = execute(&app, &runner, plan, initial, cancel_rx).await;
+            if let Ok(mut running) = runner.running.lock() {
+                running.remove(&run.session_id);
+            }
+            runner.store(run.clone());
+            let _ = app.emit("macro-finished", &run);
+        });
+
+        Ok(run)
+    }
+
+    /// Stop a session's macro. `false` if none was running.
+    pub fn cancel(&self, session_id: &str) -> bool {
+        let sender = self.running.lock().ok().and_then(|mut r| r.remove(session_id));
+        match sender {
+            Some(sender) => sender.send(true).is_ok(),
+            None => false,
+        }
+    }
+
+    pub fn latest(&self, session_id: &str) -> Option<MacroRun> {
+        self.runs.lock().ok()?.get(session_id).cloned()
+    }
+
+    /// Cancel any macro and drop the session's run.
+    pub fn forget(&self, session_id: &str) {
+        self.cancel(session_id);
+        if let Ok(mut runs) = self.runs.lock() {
+            runs.remove(session_id);
+        }
+    }
+
+    fn store(&self, run: MacroRun) {
+        if let Ok(mut runs) = self.runs.lock() {
+            runs.insert(run.session_id.clone(), run);
+        }
+    }
+}
+
+/// Result of the last `shell` or `wait` step, for `{exit_code}`,
+/// `{output}` and branches.
+#[derive(Default)]
+struct LastResult {
+    exit_code: Option<i32>,
+    output: String,
+}
+
+enum Next {
+    Step(usize),
+    End,
+}
+
+async fn execute(
+    app: &AppHandle,
+    runner: &MacroRunner,
+    plan: Plan,
+    mut run: MacroRun,
+    mut cancel: watch::Receiver<bool>,
+) -> MacroRun {
+    let mut last = LastResult::default();
+    let mut index = 0;
+    let mut executed = 0;
+
+    while index < plan.def.steps.len() {
+        if executed == plan.def.max_steps {
+            run.status = MacroStatus::Failed;
+            run.error = Some(format!("Stopped after {} steps", executed));
+            break;
+        }
+        executed += 1;
+
+        run.current = Some(index);
+        runner.store(run.clone());
+        let _ = app.emit("macro-progress", &run);
+
+        let step = tokio::select! {
+            result = run_step(app, &plan, index, &run.session_id, &mut last) => Some(result),
+            _ = cancel.wait_for(|cancelled| *cancelled) => None,
+        };
+        let (outcome, next) = match step {
+            Some(Ok(done)) => done,
+            Some(Err(e)) => {
+                run.status = MacroStatus::Failed;
+                run.error = Some(format!("Step {}: {}", index + 1, e));
+                break;
+            }
+            None => {
+                run.status = MacroStatus::Cancelled;
+                break;
+            }
+        };
+        run.steps.push(StepResult {
+            index,
+            step: describe(&plan.def.steps[index].kind),
+            outcome,
+        });
+        match next {
+            Next::Step(next) => index = next,
+            Next::End => break,
+        }
+    }
+
+    if run.status == MacroStatus::Running {
+        run.status = MacroStatus::Completed;
+    }
+    run.current = None;
+    run.finished_at = Some(Utc::now());
+    run
+}
+
+/// Run one step; returns what happened and where to go next.
+async fn run_step(
+    app: &AppHandle,
+    plan: &Plan,
+    index: usize,
+    session_id: &str,
+    last: &mut LastResult,
+) -> Result<(String, Next), String> {
+    let step = &plan.def.steps[index];
+    let next = Next::Step(index + 1);
+    let timeout = step.timeout_secs.map(Duration::from_secs);
+    let state = app.state::<AppState>();
+
+    match &step.kind {
+        MacroStepKind::Action(id) => {
+            let action = SemanticAction::from_id(id).ok_or("Unknown action")?;
+            if let SemanticAction::Interrupt = action {
+                let report = commands::interrupt(&state, session_id).await?;
+                let step = report.succeeded.ok_or("Could not interrupt the agent")?;
+                return Ok((format!("Interrupted with {}", step.label()), next));
+            }
+            let prompt = commands::action_prompt(&state, Some(session_id), &action, last.vars()).await?;
+            let (delivery, written) = deliver(app, &state, session_id, prompt).await?;
+            let safety_mode = state.config.lock().await.safety_mode.clone();
+            let entry_id = state.storage.lock().await.log_action(session_id, &action, &safety_mode, None);
+            responses::capture_delivered(app, session_id, entry_id, written);
+            Ok((describe_delivery(delivery), next))
+        }
+        MacroStepKind::Prompt(template) => {
+            let prompt = commands::text_prompt(&state, Some(session_id), template, None, None, last.vars()).await?;
+            let (delivery, written) = deliver(app, &state, session_id, prompt).await?;
+            let safety_mode = state.config.lock().await.safety_mode.clone();
+            let entry_id = state.storage.lock().await.log_message(session_id, &safety_mode, None);
+            responses::capture_delivered(app, session_id, entry_id, written);
+            Ok((describe_delivery(delivery), next))
+        }
+        MacroStepKind::Wait(wait) => {
+            let pattern = plan.patterns.get(&index);
+            let output = wait_for(&state, session_id, pattern, timeout.unwrap_or(WAIT_TIMEOUT)).await?;
+            *last = LastResult {
+                exit_code: None,
+                output: redact(&state, &output).await,
+            };
+            let outcome = match wait {
+                WaitFor::Idle => "Session idle",
+                WaitFor::Pattern(_) => "Output matched",
+            };
+            Ok((outcome.to_string(), next))
+        }
+        MacroStepKind::Shell(command) => {
+            let dir = {
+                let manager = state.session_manager.lock().await;
+                manager.get_working_dir(session_id).map_err(|e| e.to_string())?
+            };
+            let dir = dir.map(PathBuf::from).unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
+            let (exit_code, output) = run_shell(&dir, command, timeout.unwrap_or(SHELL_TIMEOUT)).await?;
+            *last = LastResult {
+                exit_code,
+                output: redact(&state, &output).await,
+            };
+            let outcome = match exit_code {
+                Some(code) => format!("Exited with {}", code),
+                None => "Killed by a signal".to_string(),
+            };
+            Ok((outcome, next))
+        }
+        MacroStepKind::Branch(branch) => {
+            let exit_ok = branch.exit_code.is_none_or(|code| last.exit_code == Some(code));
+            let match_ok = plan.patterns.get(&index).is_none_or(|re| re.is_match(&last.output));
+            let held = exit_ok && match_ok;
+            let target = if held { &branch.then } else { &branch.otherwise };
+            let next = match target.as_deref() {
+                None => next,
+                Some(END) => Next::End,
+                Some(label) => Next::Step(plan.labels[label]),
+            };
+            let outcome = match target {
+                Some(label) => format!("{} → {}", if held { "Yes" } else { "No" }, label),
+                None => (if held { "Yes" } else { "No" }).to_string(),
+            };
+            Ok((outcome, next))
+        }
+        MacroStepKind::Notify(message) => {
+            let _ = app.emit("macro-notify", serde_json::json!({
+                "session_id": session_id,
+                "macro_id": plan.def.id,
+                "message": message,
+            }));
+            Ok(("Notified".to_string(), next))
+        }
+    }
+}
+
+impl LastResult {
+    fn vars(&self) -> HashMap<String, String> {
+        let mut vars = HashMap::new();
+        if let Some(code) = self.exit_code {
+            vars.insert("exit_code".to_string(), code.to_string());
+        }
+        vars.insert("output".to_string(), self.output.clone());
+        vars
+    }
+}
+
+/// Short description of a step for progress reports.
+fn describe(kind: &MacroStepKind) -> String {
+    match kind {
+        MacroStepKind::Action(id) => format!("Action {}", id),
+        MacroStepKind::Prompt(_) => "Prompt".to_string(),
+        MacroStepKind::Wait(WaitFor::Idle) => "Wait until idle".to_string(),
+        MacroStepKind::Wait(WaitFor::Pattern(pattern)) => format!("Wait for /{}/", pattern),
+        MacroStepKind::Shell(command) => format!("$ {}", command),
+        MacroStepKind::Branch(_) => "Branch".to_string(),
+        MacroStepKind::Notify(message) => format!("Notify: {}", message),
+    }
+}
+
+fn describe_delivery(delivery: Delivery) -> String {
+    match delivery {
+        Delivery::Sent => "Sent".to_string(),
+        Delivery::Queued => "Queued until the session is idle".to_string(),
+    }
+}
+
+async fn prompt_sink(state: &tauri::State<'_, AppState>, session_id: &str) -> Result<crate::session::process::PromptSink, String> {
+    let manager = state.session_manager.lock().await;
+    manager.get_prompt_sink(session_id).map_err(|e| e.to_string())
+}
+
+/// Hand a prompt to the session, queued if it's busy. The receiver gets
+/// where the output stood once the prompt is actually written.
+async fn deliver(
+    app: &AppHandle,
+    state: &tauri::State<'_, AppState>,
+    session_id: &str,
+    prompt: String,
+) -> Result<(Delivery, oneshot::Receiver<WriteMark>), String> {
+    let sink = prompt_sink(state, session_id).await?;
+    let message = prompt.clone();
+    let (written, receiver) = oneshot::channel();
+    let delivery = tokio::task::spawn_blocking(move || sink.deliver_tracked(&message, written))
+        .await
+        .map_err(|e| e.to_string())??;
+    if delivery == Delivery::Sent {
+        let _ = app.emit("session-message-sent", serde_json::json!({
+            "session_id": session_id,
+            "message": prompt,
+        }));
+    }
+    Ok((delivery, receiver))
+}
+
+/// Wait until the session is idle, or until a new line of its output
+/// matches `pattern`. Returns the output since the wait began.
+async fn wait_for(
+    state: &tauri::State<'_, AppState>,
+    session_id: &str,
+    pattern: Option<&Regex>,
+    timeout: Duration,
+) -> Result<String, String> {
+    let output = |since: u64| async move {
+        let manager = state.session_manager.lock().await;
+        let output = manager.output_since(session_id, since).ok_or("Session closed")?;
+        Ok::<_, String>((output, manager.is_settled(session_id)))
+    };
+    let started = Instant::now();
+    // Finished lines already checked; only an unfinished line is rechecked
+    let mut scanned = output(u64::MAX).await?.0.completed;
+    let mut seen: VecDeque<String> = VecDeque::new();
+    loop {
+        let (new, settled) = output(scanned).await?;
+        let done = match pattern {
+            Some(re) => new.lines.iter().any(|line| re.is_match(line)),
+            None => settled,
+        };
+        let finished = new.lines.len() - usize::from(new.partial);
+        seen.extend(new.lines[..finished].iter().cloned());
+        while seen.len() > MAX_SEEN_LINES {
+            seen.pop_front();
+        }
+        scanned = new.completed;
+        if done {
+            let mut text: Vec<&str> = seen.iter().map(String::as_str).collect();
+            text.extend(new.lines[finished..].iter().map(String::as_str));
+            return Ok(tail(&text.join("\n"), OUTPUT_TAIL));
+        }
+        if started.elapsed() >= timeout {
+            return Err(format!("Timed out after {}s", timeout.as_secs()));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Run `command` with `sh -c` in `dir`; its exit code and the end of its
+/// combined output. The process group is killed on timeout, or when the
+/// macro is cancelled and this future dropped.
+async fn run_shell(dir: &std::path::Path, command: &str, timeout: Duration) -> Result<(Option<i32>, String), String> {
+    let child = tokio::process::Command::new("sh")
+        .arg("-c")
+        // Merge stderr into stdout to keep the two in order
+        .arg(format!("exec 2>&1; {}", command))
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to start: {}", e))?;
+    let group = ProcessGroup(child.id());
+
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| format!("Timed out after {}s", timeout.as_secs()))?
+        .map_err(|e| e.to_string())?;
+    group.disarm();
+    let text = String::from_utf8_lossy(&output.stdout);
+    Ok((output.status.code(), tail(&crate::testing::parse::strip_ansi(&text), OUTPUT_TAIL)))
+}
+
+/// Sends SIGTERM to a process group when dropped, unless disarmed, so a
+/// shell step's children don't outlive it.
+struct ProcessGroup(Option<u32>);
+
+impl ProcessGroup {
+    fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        if let Some(pid) = self.0 {
+            unsafe {
+                libc::kill(-(pid as i32), libc::SIGTERM);
+            }
+        }
+    }
+}
+
+async fn redact(state: &tauri::State<'_, AppState>, text: &str) -> String {
+    let redactor = Redactor::new(&state.config.lock().await.redaction);
+    redactor.redact(text).0
+}
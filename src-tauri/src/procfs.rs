@@ -0,0 +1,306 @@
+//! Minimal readers for Linux `/proc`, used to see what a session's
+//! processes are doing. Everything degrades to empty results elsewhere.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::time::Duration;
+
+/// TCP state code for LISTEN in `/proc/net/tcp`.
+const TCP_LISTEN: &str = "0A";
+
+/// Window over which process CPU usage is measured.
+const CPU_SAMPLE: Duration = Duration::from_millis(200);
+
+/// Longest command line kept per process.
+const MAX_CMDLINE: usize = 200;
+
+/// The processes that make up a session: trees under `roots`, minus
+/// `exclude` (the hosting shell and the agent itself).
+#[derive(Debug, Clone, Default)]
+pub struct ProcessScope {
+    pub roots: Vec<u32>,
+    pub exclude: Vec<u32>,
+}
+
+/// A process running in a session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub ppid: u32,
+    pub command: String,
+    /// `running`, `sleeping`, `disk sleep`, `stopped`, `zombie`, ...
+    pub state: String,
+    pub age_secs: u64,
+    /// Share of one CPU used over a short sample window.
+    pub cpu_percent: f32,
+}
+
+/// A TCP port some process in a session is listening on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListeningPort {
+    pub port: u16,
+    /// Bound address, e.g. `127.0.0.1`, `0.0.0.0` or `::`.
+    pub address: String,
+    pub pid: u32,
+    /// Process name from `/proc/<pid>/comm`.
+    pub command: String,
+}
+
+/// Parent pid of every process, from `/proc/<pid>/stat`.
+fn parent_map() -> HashMap<u32, u32> {
+    let mut parents = HashMap::new();
+    let Ok(entries) = fs::read_dir("/proc") else { return parents };
+    for entry in entries.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse::<u32>().ok()) else { continue };
+        if let Some(ppid) = read_stat(pid).and_then(|fields| fields.get(1)?.parse().ok()) {
+            parents.insert(pid, ppid);
+        }
+    }
+    parents
+}
+
+/// Fields of `/proc/<pid>/stat` after the command name, so index 0 is the
+/// state and index 1 the parent pid. The name is skipped because it may
+/// contain spaces and parentheses.
+pub fn read_stat(pid: u32) -> Option<Vec<String>> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let rest = &stat[stat.rfind(')')? + 1..];
+    Some(rest.split_whitespace().map(str::to_string).collect())
+}
+
+/// `roots` and every process descended from them.
+pub fn process_tree(roots: &[u32]) -> Vec<u32> {
+    let parents = parent_map();
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (&pid, &ppid) in &parents {
+        children.entry(ppid).or_default().push(pid);
+    }
+
+    let mut tree = Vec::new();
+    let mut seen = HashSet::new();
+    let mut stack: Vec<u32> = roots.iter().copied().filter(|p| parents.contains_key(p)).collect();
+    while let Some(pid) = stack.pop() {
+        if !seen.insert(pid) {
+            continue;
+        }
+        tree.push(pid);
+        if let Some(kids) = children.get(&pid) {
+            stack.extend(kids);
+        }
+    }
+    tree
+}
+
+/// A variable from the environment `pid` was started with. Changes the
+/// process made to its own environment afterwards aren't visible.
+pub fn env_var(pid: u32, name: &str) -> Option<String> {
+    let raw = fs::read(format!("/proc/{}/environ", pid)).ok()?;
+    raw.split(|b| *b == 0).find_map(|entry| {
+        let entry = String::from_utf8_lossy(entry);
+        let (key, value) = entry.split_once('=')?;
+        (key == name).then(|| value.to_string())
+    })
+}
+
+/// Full command line, falling back to `[comm]` for kernel threads and zombies.
+pub fn cmdline(pid: u32) -> String {
+    let raw = fs::read(format!("/proc/{}/cmdline", pid)).unwrap_or_default();
+    let args: Vec<String> = raw
+        .split(|b| *b == 0)
+        .filter(|a| !a.is_empty())
+        .map(|a| String::from_utf8_lossy(a).to_string())
+        .collect();
+    if args.is_empty() {
+        return format!("[{}]", comm(pid));
+    }
+    let line = args.join(" ");
+    if line.chars().count() > MAX_CMDLINE {
+        format!("{}…", line.chars().take(MAX_CMDLINE).collect::<String>())
+    } else {
+        line
+    }
+}
+
+/// Direct child of `parent` that runs `program` (matched by name or by the
+/// basename of one of its first two arguments, since node-based CLIs run
+/// as `node /path/to/program`).
+pub fn find_child(parent: u32, program: &str) -> Option<u32> {
+    let name = program.rsplit('/').next().unwrap_or(program);
+    parent_map()
+        .into_iter()
+        .filter(|(_, ppid)| *ppid == parent)
+        .map(|(pid, _)| pid)
+        .find(|&pid| {
+            if comm(pid) == name {
+                return true;
+            }
+            let raw = fs::read(format!("/proc/{}/cmdline", pid)).unwrap_or_default();
+            raw.split(|b| *b == 0)
+                .take(2)
+                .any(|arg| String::from_utf8_lossy(arg).rsplit('/').next() == Some(name))
+        })
+}
+
+fn state_name(code: &str) -> &'static str {
+    match code {
+        "R" => "running",
+        "S" => "sleeping",
+        "D" => "disk sleep",
+        "T" | "t" => "stopped",
+        "Z" => "zombie",
+        "X" => "dead",
+        "I" => "idle",
+        _ => "unknown",
+    }
+}
+
+/// Seconds since boot, from `/proc/uptime`.
+fn uptime_secs() -> Option<f64> {
+    fs::read_to_string("/proc/uptime")
+        .ok()?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// user + system CPU ticks (stat fields 14 and 15).
+fn cpu_ticks(fields: &[String]) -> u64 {
+    let field = |i: usize| fields.get(i).and_then(|f| f.parse::<u64>().ok()).unwrap_or(0);
+    field(11) + field(12)
+}
+
+/// Processes in `scope`, oldest first. Blocks for `CPU_SAMPLE` to measure
+/// CPU usage, so call it off the async runtime.
+pub fn processes(scope: &ProcessScope) -> Vec<ProcessInfo> {
+    let pids: Vec<u32> = process_tree(&scope.roots)
+        .into_iter()
+        .filter(|pid| !scope.exclude.contains(pid))
+        .collect();
+    if pids.is_empty() {
+        return Vec::new();
+    }
+
+    let before: HashMap<u32, u64> = pids
+        .iter()
+        .filter_map(|&pid| Some((pid, cpu_ticks(&read_stat(pid)?))))
+        .collect();
+    std::thread::sleep(CPU_SAMPLE);
+
+    let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as f64;
+    let uptime = uptime_secs().unwrap_or(0.0);
+
+    let mut procs: Vec<ProcessInfo> = pids
+        .into_iter()
+        .filter_map(|pid| {
+            // Processes that exited during the sample are dropped
+            let fields = read_stat(pid)?;
+            let used = cpu_ticks(&fields).saturating_sub(*before.get(&pid)?);
+            let started = fields.get(19)?.parse::<f64>().ok()? / ticks_per_sec;
+            Some(ProcessInfo {
+                pid,
+                ppid: fields.get(1)?.parse().ok()?,
+                command: cmdline(pid),
+                state: state_name(fields.first()?).to_string(),
+                age_secs: (uptime - started).max(0.0) as u64,
+                cpu_percent: (used as f64 / ticks_per_sec / CPU_SAMPLE.as_secs_f64() * 100.0) as f32,
+            })
+        })
+        .collect();
+    procs.sort_by_key(|p| std::cmp::Reverse(p.age_secs));
+    procs
+}
+
+pub fn comm(pid: u32) -> String {
+    fs::read_to_string(format!("/proc/{}/comm", pid))
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Socket inodes held open by a process. Other users' processes are
+/// unreadable and yield nothing.
+fn socket_inodes(pid: u32) -> HashSet<u64> {
+    let mut inodes = HashSet::new();
+    let Ok(fds) = fs::read_dir(format!("/proc/{}/fd", pid)) else { return inodes };
+    for fd in fds.flatten() {
+        let Ok(target) = fs::read_link(fd.path()) else { continue };
+        let target = target.to_string_lossy();
+        if let Some(inode) = target.strip_prefix("socket:[").and_then(|s| s.strip_suffix(']')) {
+            if let Ok(inode) = inode.parse() {
+                inodes.insert(inode);
+            }
+        }
+    }
+    inodes
+}
+
+/// Listening TCP sockets system-wide: inode → (address, port).
+fn listening_sockets() -> HashMap<u64, (String, u16)> {
+    let mut sockets = HashMap::new();
+    for (file, v6) in [("/proc/net/tcp", false), ("/proc/net/tcp6", true)] {
+        let Ok(table) = fs::read_to_string(file) else { continue };
+        for line in table.lines().skip(1) {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            // sl local_address rem_address st tx:rx tr:when retrnsmt uid timeout inode
+            if cols.len() < 10 || cols[3] != TCP_LISTEN {
+                continue;
+            }
+            let Some((addr, port)) = cols[1].split_once(':') else { continue };
+            let (Ok(port), Ok(inode)) = (u16::from_str_radix(port, 16), cols[9].parse::<u64>()) else { continue };
+            if inode == 0 {
+                continue;
+            }
+            sockets.insert(inode, (decode_address(addr, v6), port));
+        }
+    }
+    sockets
+}
+
+/// Addresses in `/proc/net/tcp*` are hex in host byte order, 32 bits at a time.
+fn decode_address(hex: &str, v6: bool) -> String {
+    let words: Vec<u32> = (0..hex.len() / 8)
+        .filter_map(|i| u32::from_str_radix(&hex[i * 8..i * 8 + 8], 16).ok())
+        .collect();
+    let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_ne_bytes()).collect();
+    if v6 && bytes.len() == 16 {
+        let mut octets = [0u8; 16];
+        octets.copy_from_slice(&bytes);
+        std::net::Ipv6Addr::from(octets).to_string()
+    } else if bytes.len() == 4 {
+        std::net::Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]).to_string()
+    } else {
+        hex.to_string()
+    }
+}
+
+/// Ports listened on by `roots` or their descendants, sorted by port.
+pub fn listening_ports(roots: &[u32]) -> Vec<ListeningPort> {
+    if roots.is_empty() {
+        return Vec::new();
+    }
+    let sockets = listening_sockets();
+    if sockets.is_empty() {
+        return Vec::new();
+    }
+
+    let mut ports: Vec<ListeningPort> = Vec::new();
+    let mut seen = HashSet::new();
+    for pid in process_tree(roots) {
+        for inode in socket_inodes(pid) {
+            let Some((address, port)) = sockets.get(&inode) else { continue };
+            // A parent and child can share an inherited listening socket
+            if !seen.insert(inode) {
+                continue;
+            }
+            ports.push(ListeningPort {
+                port: *port,
+                address: address.clone(),
+                pid,
+                command: comm(pid),
+            });
+        }
+    }
+    ports.sort_by(|a, b| (a.port, &a.address).cmp(&(b.port, &b.address)));
+    ports
+}
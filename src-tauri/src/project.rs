@@ -0,0 +1,314 @@
+//! Project type detection from manifest files.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Manifests checked in each directory, most specific first.
+const MANIFESTS: &[&str] = &["Cargo.toml", "package.json", "pyproject.toml", "go.mod"];
+
+/// What kind of project a directory belongs to and how to work with it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectInfo {
+    /// Directory holding the manifest (may be an ancestor of the one asked
+    /// about).
+    pub root: String,
+    /// `Cargo.toml`, `package.json`, ... or `Makefile`.
+    pub manifest: String,
+    pub name: Option<String>,
+    /// `rust`, `typescript`, `javascript`, `python`, `go`; `None` for a
+    /// bare Makefile.
+    pub language: Option<String>,
+    pub package_manager: Option<String>,
+    /// Member paths or globs as written in the manifest.
+    pub workspace_members: Vec<String>,
+    pub test_cmd: Option<String>,
+    pub build_cmd: Option<String>,
+    pub lint_cmd: Option<String>,
+}
+
+impl ProjectInfo {
+    /// Look for a manifest in `dir`, then its ancestors up to the
+    /// repository root. A Makefile next to the manifest fills in commands
+    /// the manifest doesn't provide.
+    pub fn detect(dir: &Path) -> Option<Self> {
+        for root in dir.ancestors() {
+            let info = MANIFESTS
+                .iter()
+                .find(|m| root.join(m).is_file())
+                .and_then(|manifest| Self::from_manifest(root, manifest));
+            let make = make_targets(root);
+
+            let mut info = match info {
+                Some(info) => info,
+                None if !make.is_empty() => ProjectInfo {
+                    root: root.display().to_string(),
+                    manifest: "Makefile".to_string(),
+                    ..Default::default()
+                },
+                None if root.join(".git").exists() => return None,
+                None => continue,
+            };
+            for (slot, target) in [
+                (&mut info.test_cmd, "test"),
+                (&mut info.build_cmd, "build"),
+                (&mut info.lint_cmd, "lint"),
+            ] {
+                if slot.is_none() && make.iter().any(|t| t == target) {
+                    *slot = Some(format!("make {}", target));
+                }
+            }
+            return Some(info);
+        }
+        None
+    }
+
+    fn from_manifest(root: &Path, manifest: &str) -> Option<Self> {
+        let content = fs::read_to_string(root.join(manifest)).ok()?;
+        let mut info = match manifest {
+            "Cargo.toml" => cargo(&content)?,
+            "package.json" => node(root, &content)?,
+            "pyproject.toml" => python(root, &content)?,
+            "go.mod" => go(root, &content),
+            _ => return None,
+        };
+        info.root = root.display().to_string();
+        info.manifest = manifest.to_string();
+        Some(info)
+    }
+
+    /// One-line summary, e.g. `deckmind — rust (cargo, workspace of 3 members)`.
+    pub fn describe(&self) -> String {
+        let mut text = self.language.clone().unwrap_or_else(|| "make".to_string());
+        let mut details = Vec::new();
+        if let Some(pm) = &self.package_manager {
+            details.push(pm.clone());
+        }
+        if !self.workspace_members.is_empty() {
+            let count = self.workspace_members.len();
+            details.push(format!("workspace of {} member{}", count, if count == 1 { "" } else { "s" }));
+        }
+        if !details.is_empty() {
+            text.push_str(&format!(" ({})", details.join(", ")));
+        }
+        if let Some(name) = &self.name {
+            text = format!("{} — {}", name, text);
+        }
+        text
+    }
+
+    /// Value for a `{project.<field>}` template placeholder.
+    pub fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "root" => Some(self.root.clone()),
+            "manifest" => Some(self.manifest.clone()),
+            "name" => self.name.clone(),
+            "language" => self.language.clone(),
+            "package_manager" => self.package_manager.clone(),
+            "workspace_members" => Some(self.workspace_members.join(", ")).filter(|s| !s.is_empty()),
+            "test_cmd" => self.test_cmd.clone(),
+            "build_cmd" => self.build_cmd.clone(),
+            "lint_cmd" => self.lint_cmd.clone(),
+            _ => None,
+        }
+    }
+}
+
+fn cargo(content: &str) -> Option<ProjectInfo> {
+    let manifest: toml::Value = toml::from_str(content).ok()?;
+    let members = string_array(manifest.get("workspace").and_then(|w| w.get("members")));
+    let scope = if members.is_empty() { "" } else { " --workspace" };
+    Some(ProjectInfo {
+        name: str_at(&manifest, &["package", "name"]),
+        language: Some("rust".to_string()),
+        package_manager: Some("cargo".to_string()),
+        workspace_members: members,
+        test_cmd: Some(format!("cargo test{}", scope)),
+        build_cmd: Some(format!("cargo build{}", scope)),
+        lint_cmd: Some(format!("cargo clippy{} --all-targets", scope)),
+        ..Default::default()
+    })
+}
+
+fn node(root: &Path, content: &str) -> Option<ProjectInfo> {
+    let manifest: serde_json::Value = serde_json::from_str(content).ok()?;
+
+    let package_manager = manifest["packageManager"]
+        .as_str()
+        .and_then(|pm| pm.split('@').next())
+        .map(str::to_string)
+        .or_else(|| {
+            [
+                ("pnpm-lock.yaml", "pnpm"),
+                ("yarn.lock", "yarn"),
+                ("bun.lockb", "bun"),
+                ("bun.lock", "bun"),
+            ]
+            .iter()
+            .find(|(lock, _)| root.join(lock).exists())
+            .map(|(_, pm)| pm.to_string())
+        })
+        .unwrap_or_else(|| "npm".to_string());
+
+    let script = |name: &str| {
+        manifest["scripts"][name]
+            .as_str()
+            .map(|_| format!("{} run {}", package_manager, name))
+    };
+
+    // "workspaces": [...] or "workspaces": { "packages": [...] }
+    let workspaces = &manifest["workspaces"];
+    let members = match workspaces.get("packages") {
+        Some(packages) => json_strings(packages),
+        None => json_strings(workspaces),
+    };
+
+    let typescript = root.join("tsconfig.json").exists()
+        || !manifest["devDependencies"]["typescript"].is_null()
+        || !manifest["dependencies"]["typescript"].is_null();
+
+    Some(ProjectInfo {
+        name: manifest["name"].as_str().map(str::to_string),
+        language: Some(if typescript { "typescript" } else { "javascript" }.to_string()),
+        test_cmd: script("test"),
+        build_cmd: script("build"),
+        lint_cmd: script("lint"),
+        package_manager: Some(package_manager),
+        workspace_members: members,
+        ..Default::default()
+    })
+}
+
+fn python(root: &Path, content: &str) -> Option<ProjectInfo> {
+    let manifest: toml::Value = toml::from_str(content).ok()?;
+    let tool = |name: &str| manifest.get("tool").and_then(|t| t.get(name));
+
+    let package_manager = if tool("poetry").is_some() {
+        "poetry"
+    } else if root.join("uv.lock").exists() || tool("uv").is_some() {
+        "uv"
+    } else if tool("pdm").is_some() {
+        "pdm"
+    } else if tool("hatch").is_some() {
+        "hatch"
+    } else {
+        "pip"
+    };
+    // Tools run inside the project's environment
+    let run = |cmd: &str| match package_manager {
+        "pip" => cmd.to_string(),
+        pm => format!("{} run {}", pm, cmd),
+    };
+
+    let has_tests = tool("pytest").is_some() || root.join("tests").is_dir() || root.join("test").is_dir();
+    let lint_cmd = if tool("ruff").is_some() {
+        Some(run("ruff check ."))
+    } else if tool("flake8").is_some() || root.join(".flake8").exists() {
+        Some(run("flake8"))
+    } else {
+        None
+    };
+    let build_cmd = match package_manager {
+        "pip" => "python -m build".to_string(),
+        pm => format!("{} build", pm),
+    };
+
+    Some(ProjectInfo {
+        name: str_at(&manifest, &["project", "name"]).or_else(|| str_at(&manifest, &["tool", "poetry", "name"])),
+        language: Some("python".to_string()),
+        package_manager: Some(package_manager.to_string()),
+        workspace_members: string_array(tool("uv").and_then(|uv| uv.get("workspace")).and_then(|w| w.get("members"))),
+        test_cmd: has_tests.then(|| run("pytest")),
+        build_cmd: Some(build_cmd),
+        lint_cmd,
+        ..Default::default()
+    })
+}
+
+fn go(root: &Path, content: &str) -> ProjectInfo {
+    let name = content
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("module "))
+        .map(|m| m.trim().to_string());
+
+    // `use ./a` and `use ( ./a ./b )` in go.work
+    let members = fs::read_to_string(root.join("go.work"))
+        .map(|work| {
+            let mut members = Vec::new();
+            let mut in_block = false;
+            for line in work.lines().map(str::trim) {
+                if line == "use (" {
+                    in_block = true;
+                } else if in_block && line == ")" {
+                    in_block = false;
+                } else if in_block && !line.is_empty() {
+                    members.push(line.to_string());
+                } else if let Some(dir) = line.strip_prefix("use ") {
+                    members.push(dir.trim().to_string());
+                }
+            }
+            members
+        })
+        .unwrap_or_default();
+
+    let golangci = [".golangci.yml", ".golangci.yaml", ".golangci.toml"]
+        .iter()
+        .any(|f| root.join(f).exists());
+
+    ProjectInfo {
+        name,
+        language: Some("go".to_string()),
+        package_manager: Some("go".to_string()),
+        workspace_members: members,
+        test_cmd: Some("go test ./...".to_string()),
+        build_cmd: Some("go build ./...".to_string()),
+        lint_cmd: Some(if golangci { "golangci-lint run" } else { "go vet ./..." }.to_string()),
+        ..Default::default()
+    }
+}
+
+/// Explicit targets defined in the directory's Makefile.
+fn make_targets(dir: &Path) -> Vec<String> {
+    let path: PathBuf = ["GNUmakefile", "makefile", "Makefile"]
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|p| p.is_file())
+        .unwrap_or_default();
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter(|line| !line.starts_with(['\t', ' ', '#', '.']))
+        .filter_map(|line| {
+            let (targets, rest) = line.split_once(':')?;
+            // Skip variable assignments like `CC := gcc`
+            if rest.starts_with('=') {
+                return None;
+            }
+            Some(targets.split_whitespace().map(str::to_string).collect::<Vec<_>>())
+        })
+        .flatten()
+        .collect()
+}
+
+fn str_at(value: &toml::Value, path: &[&str]) -> Option<String> {
+    path.iter()
+        .try_fold(value, |v, key| v.get(key))
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+}
+
+fn string_array(value: Option<&toml::Value>) -> Vec<String> {
+    value
+        .and_then(|v| v.as_array())
+        .map(|items| items.iter().filter_map(|i| i.as_str().map(str::to_string)).collect())
+        .unwrap_or_default()
+}
+
+fn json_strings(value: &serde_json::Value) -> Vec<String> {
+    value
+        .as_array()
+        .map(|items| items.iter().filter_map(|i| i.as_str().map(str::to_string)).collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,196 @@
+mod transcript;
+
+use crate::context::Redactor;
+use crate::session::process::WriteMark;
+use crate::text::tail;
+use crate::AppState;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::oneshot;
+
+/// How long the agent may work before what it has said so far is stored.
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Characters of a response kept: its end, where the answer usually is.
+const MAX_RESPONSE_CHARS: usize = 4000;
+
+const MAX_SUMMARY_CHARS: usize = 200;
+
+/// Characters that start a line of agent output without being part of it.
+const MARKERS: &[char] = &['⏺', '●', '*', '-', ' '];
+
+/// Where an action's response begins. Taken just before its prompt is
+/// written.
+pub struct ResponseMark {
+    /// Output lines the session had finished.
+    completed: u64,
+    at: DateTime<Utc>,
+}
+
+impl ResponseMark {
+    /// `None` if the session doesn't exist.
+    pub async fn take(state: &tauri::State<'_, AppState>, session_id: &str) -> Option<Self> {
+        let terminal = state.session_manager.lock().await.terminal_output(session_id)?;
+        Some(ResponseMark {
+            completed: terminal.completed,
+            at: Utc::now(),
+        })
+    }
+}
+
+impl From<WriteMark> for ResponseMark {
+    fn from(mark: WriteMark) -> Self {
+        ResponseMark {
+            completed: mark.completed,
+            at: mark.at,
+        }
+    }
+}
+
+/// Follow the session until it settles, then store the agent's reply on
+/// the memory entry `entry_id` and emit `action-response`.
+pub fn capture(app: &AppHandle, session_id: &str, entry_id: String, mark: ResponseMark) {
+    let app = app.clone();
+    let session_id = session_id.to_string();
+    tokio::spawn(async move { store(&app, &session_id, &entry_id, &mark).await });
+}
+
+/// `capture` for a prompt handed to `PromptSink::deliver_tracked`. A
+/// queued prompt's response only starts once it leaves the queue; until
+/// then the output belongs to the task before it. Nothing is stored if
+/// the prompt is never written.
+pub fn capture_delivered(app: &AppHandle, session_id: &str, entry_id: String, written: oneshot::Receiver<WriteMark>) {
+    let app = app.clone();
+    let session_id = session_id.to_string();
+    tokio::spawn(async move {
+        let Ok(mark) = written.await else { return };
+        store(&app, &session_id, &entry_id, &mark.into()).await;
+    });
+}
+
+async fn store(app: &AppHandle, session_id: &str, entry_id: &str, mark: &ResponseMark) {
+    let Some((response, summary)) = follow(app, session_id, mark).await else { return };
+    let state = app.state::<AppState>();
+    state.storage.lock().await.set_response(entry_id, response, summary.clone());
+    let _ = app.emit("action-response", serde_json::json!({
+        "session_id": session_id,
+        "entry_id": entry_id,
+        "summary": summary,
+    }));
+}
+
+/// The reply and its summary, redacted. Taken from Claude's transcript
+/// when there is one, otherwise from the terminal output since `mark`.
+/// `None` if the session closed first or nothing was said.
+async fn follow(app: &AppHandle, session_id: &str, mark: &ResponseMark) -> Option<(String, Option<String>)> {
+    let state = app.state::<AppState>();
+    let started = Instant::now();
+    let (lines, cwd) = loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        let manager = state.session_manager.lock().await;
+        let lines = manager.output_since(session_id, mark.completed)?.lines;
+        if manager.is_settled(session_id) || started.elapsed() >= CAPTURE_TIMEOUT {
+            break (lines, manager.get_working_dir(session_id).ok().flatten());
+        }
+    };
+
+    let since = mark.at;
+    let messages = match cwd {
+        Some(cwd) => tokio::task::spawn_blocking(move || transcript::assistant_messages(&cwd, since))
+            .await
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+    let (response, summary) = match messages.last() {
+        // The last message is the agent's answer; earlier ones narrate its work
+        Some(answer) => (messages.join("\n\n"), summarize(answer)),
+        None => {
+            let text = clean(&lines);
+            let summary = summarize(&text);
+            (text, summary)
+        }
+    };
+    if response.is_empty() {
+        return None;
+    }
+
+    let redactor = Redactor::new(&state.config.lock().await.redaction);
+    let (response, _) = redactor.redact(&response);
+    let summary = summary.map(|s| redactor.redact(&s).0);
+    Some((tail(&response, MAX_RESPONSE_CHARS), summary))
+}
+
+/// Terminal lines as text: box-drawing borders and Claude's status lines
+/// dropped, repeated lines and runs of blank lines collapsed.
+fn clean(lines: &[String]) -> String {
+    let is_frame = |c: char| ('\u{2500}'..='\u{259f}').contains(&c);
+    let mut out: Vec<&str> = Vec::new();
+    for line in lines {
+        let line = line.trim_matches(is_frame).trim_end();
+        if line.contains("esc to interrupt") || line.contains("? for shortcuts") {
+            continue;
+        }
+        // An empty input box leaves just its prompt
+        let blank = matches!(line.trim(), "" | ">");
+        match out.last() {
+            None if blank => continue,
+            Some(last) if *last == line || (blank && last.is_empty()) => continue,
+            _ => {}
+        }
+        out.push(if blank { "" } else { line });
+    }
+    while out.last().is_some_and(|l| l.is_empty()) {
+        out.pop();
+    }
+    out.join("\n")
+}
+
+/// The first sentence of the first paragraph of prose in `text`, skipping
+/// headings, code blocks, tool calls and the echoed `> prompt`.
+fn summarize(text: &str) -> Option<String> {
+    static TOOL_CALL: OnceLock<Regex> = OnceLock::new();
+    let tool_call = TOOL_CALL.get_or_init(|| Regex::new(r"^(?:[⏺●]\s*[A-Z]\w*\(|⎿)").expect("tool call pattern"));
+
+    let mut in_code = false;
+    let mut paragraph: Vec<&str> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        let prose = !in_code && !line.starts_with(['#', '>']) && !tool_call.is_match(line);
+        let line = line.trim_start_matches(MARKERS);
+        if prose && !line.is_empty() {
+            paragraph.push(line);
+        } else if !paragraph.is_empty() {
+            break;
+        }
+    }
+    if paragraph.is_empty() {
+        return None;
+    }
+    Some(first_sentence(&paragraph.join(" ")))
+}
+
+/// `text` up to the end of its first full sentence, at most
+/// `MAX_SUMMARY_CHARS` characters.
+fn first_sentence(text: &str) -> String {
+    let end = [". ", "! ", "? "]
+        .iter()
+        .filter_map(|p| text.find(p))
+        .min()
+        .map(|i| i + 1)
+        .unwrap_or(text.len());
+    let sentence = &text[..end];
+    if sentence.chars().count() <= MAX_SUMMARY_CHARS {
+        return sentence.to_string();
+    }
+    let cut: String = sentence.chars().take(MAX_SUMMARY_CHARS - 1).collect();
+    format!("{}…", cut.trim_end())
+}
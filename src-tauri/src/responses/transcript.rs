@@ -0,0 +1,66 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Directory Claude Code keeps the transcripts of sessions started in
+/// `cwd`: `~/.claude/projects/<cwd with each non-alphanumeric character
+/// replaced by ->`.
+fn project_dir(cwd: &str) -> Option<PathBuf> {
+    let name: String = cwd.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '-' }).collect();
+    Some(dirs::home_dir()?.join(".claude").join("projects").join(name))
+}
+
+/// The one transcript that changed after `since`. With several, another
+/// session in the same directory was working too and there's no telling
+/// which is ours.
+fn changed_transcript(dir: &Path, since: DateTime<Utc>) -> Option<PathBuf> {
+    let since = SystemTime::from(since);
+    let mut changed = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|e| e.path().extension().and_then(|x| x.to_str()) == Some("jsonl"))
+        .filter(|e| e.metadata().and_then(|m| m.modified()).is_ok_and(|modified| modified >= since))
+        .map(|e| e.path());
+    let path = changed.next()?;
+    changed.next().is_none().then_some(path)
+}
+
+/// Text of the assistant messages Claude recorded for a session in `cwd`
+/// since `since`, one string per message. Empty when there's no single
+/// transcript to read, e.g. for other agents. Blocking.
+pub fn assistant_messages(cwd: &str, since: DateTime<Utc>) -> Vec<String> {
+    let Some(path) = project_dir(cwd).and_then(|dir| changed_transcript(&dir, since)) else {
+        return Vec::new();
+    };
+    let Ok(content) = fs::read_to_string(&path) else { return Vec::new() };
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|record| record["type"] == "assistant")
+        .filter(|record| {
+            record["timestamp"]
+                .as_str()
+                .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                .is_some_and(|t| t >= since)
+        })
+        .filter_map(|record| message_text(&record["message"]["content"]))
+        .collect()
+}
+
+/// The text blocks of a message's content; tool calls are left out.
+fn message_text(content: &Value) -> Option<String> {
+    let text = match content {
+        Value::String(text) => text.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter(|b| b["type"] == "text")
+            .filter_map(|b| b["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => return None,
+    };
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
@@ -0,0 +1,484 @@
+use super::{NewSession, SessionManager};
+use crate::git::{self, worktree, worktree::FileChange, worktree::ManagedWorktree};
+use crate::text::tail;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// How often the monitor checks whether every candidate has settled.
+const MONITOR_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Upper bound for a candidate's test command.
+const TEST_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Keep this much of the test output for display on the device.
+const TEST_OUTPUT_TAIL: usize = 4000;
+
+/// One agent configuration to run the task with.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AgentProvider {
+    /// Agent CLI to launch. Defaults to the configured claude path.
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Extra CLI flags for this candidate.
+    #[serde(default)]
+    pub flags: Option<String>,
+    /// Pass `--dangerously-skip-permissions`. Defaults to on for the
+    /// configured claude path and off for a custom `command`.
+    #[serde(default)]
+    pub skip_permissions: Option<bool>,
+}
+
+impl AgentProvider {
+    fn launch_flags(&self) -> String {
+        let mut flags = self.flags.clone().unwrap_or_default();
+        if let Some(ref model) = self.model {
+            flags = format!("{} --model {}", flags, model);
+        }
+        flags.trim().to_string()
+    }
+
+    fn label(&self, command: &str) -> String {
+        match self.model {
+            Some(ref model) => format!("{} ({})", command, model),
+            None => command.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Candidate {
+    pub session_id: String,
+    pub label: String,
+    pub worktree: ManagedWorktree,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TestOutcome {
+    pub command: String,
+    pub exit_code: Option<i32>,
+    pub passed: bool,
+    pub timed_out: bool,
+    pub output_tail: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CandidateResult {
+    pub session_id: String,
+    pub label: String,
+    pub branch: String,
+    pub files_changed: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub changes: Vec<FileChange>,
+    pub test: Option<TestOutcome>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupStatus {
+    /// Agents are still working.
+    Running,
+    /// All agents settled; diffs and tests are being collected.
+    Comparing,
+    Complete,
+    /// A candidate was adopted and the rest were cleaned up.
+    Adopted,
+}
+
+/// A set of sessions working on the same task in separate worktrees.
+#[derive(Debug, Clone, Serialize)]
+pub struct BestOfNGroup {
+    pub id: String,
+    pub task: String,
+    pub repo: String,
+    pub base_commit: String,
+    pub created_at: DateTime<Utc>,
+    pub status: GroupStatus,
+    pub test_command: Option<String>,
+    pub candidates: Vec<Candidate>,
+    pub results: Vec<CandidateResult>,
+    pub adopted: Option<String>,
+}
+
+pub struct BestOfNManager {
+    groups: HashMap<String, BestOfNGroup>,
+}
+
+impl BestOfNManager {
+    pub fn new() -> Self {
+        BestOfNManager {
+            groups: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, group_id: &str) -> Option<&BestOfNGroup> {
+        self.groups.get(group_id)
+    }
+}
+
+/// Create N worktrees and sessions for `task`, queue the task in each, and
+/// start a monitor that compares the results once every agent has settled.
+///
+/// Providers are assigned round-robin, so a single provider runs N copies
+/// of the same agent while a list of N runs one of each.
+#[allow(clippy::too_many_arguments)]
+pub async fn start(
+    task: String,
+    n: usize,
+    providers: Vec<AgentProvider>,
+    base_dir: String,
+    worktree_root: PathBuf,
+    default_command: String,
+    test_command: Option<String>,
+    sessions: Arc<Mutex<SessionManager>>,
+    groups: Arc<Mutex<BestOfNManager>>,
+    app: AppHandle,
+) -> Result<BestOfNGroup, String> {
+    if n == 0 {
+        return Err("n must be at least 1".to_string());
+    }
+    let providers = if providers.is_empty() {
+        vec![AgentProvider::default()]
+    } else {
+        providers
+    };
+
+    let repo = git::repo_root(Path::new(&base_dir))?;
+    let base_commit = git::run_git(&repo, &["rev-parse", "HEAD"])?;
+    let group_id = Uuid::new_v4().to_string()[..8].to_string();
+
+    let mut candidates = Vec::with_capacity(n);
+    for index in 0..n {
+        let provider = &providers[index % providers.len()];
+        let name = format!("bon-{}-{}", group_id, index + 1);
+        let started = start_candidate(
+            index,
+            provider,
+            &task,
+            &name,
+            &repo,
+            &worktree_root,
+            &default_command,
+            &group_id,
+            &sessions,
+            &app,
+        )
+        .await;
+        match started {
+            Ok(candidate) => candidates.push(candidate),
+            Err(e) => {
+                discard(&candidates, &sessions).await;
+                return Err(e);
+            }
+        }
+    }
+
+    let group = BestOfNGroup {
+        id: group_id.clone(),
+        task,
+        repo: repo.to_string_lossy().to_string(),
+        base_commit,
+        created_at: Utc::now(),
+        status: GroupStatus::Running,
+        test_command,
+        candidates,
+        results: Vec::new(),
+        adopted: None,
+    };
+
+    groups.lock().await.groups.insert(group_id.clone(), group.clone());
+    tokio::spawn(monitor(group_id, sessions, groups, app));
+
+    Ok(group)
+}
+
+/// Create one candidate's worktree and session and queue the task in it.
+/// Nothing is left behind if a step fails.
+#[allow(clippy::too_many_arguments)]
+async fn start_candidate(
+    index: usize,
+    provider: &AgentProvider,
+    task: &str,
+    name: &str,
+    repo: &Path,
+    worktree_root: &Path,
+    default_command: &str,
+    group_id: &str,
+    sessions: &Arc<Mutex<SessionManager>>,
+    app: &AppHandle,
+) -> Result<Candidate, String> {
+    let command = provider.command.clone().unwrap_or_else(|| default_command.to_string());
+    let (dir, root, name) = (repo.to_path_buf(), worktree_root.to_path_buf(), name.to_string());
+    let wt = tokio::task::spawn_blocking(move || worktree::create_managed(&dir, &root, Some(&name)))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let label = provider.label(&command);
+    let created = sessions
+        .lock()
+        .await
+        .create_session(
+            NewSession {
+                name: Some(format!("#{} {}", index + 1, label)),
+                working_dir: None,
+                claude_path: command,
+                extra_flags: Some(provider.launch_flags()),
+                skip_permissions: provider.skip_permissions.unwrap_or(provider.command.is_none()),
+                tags: vec![format!("best-of-n:{}", group_id)],
+                worktree: Some(wt.clone()),
+                // Losers are cleaned up explicitly by `adopt`
+                delete_worktree_on_close: false,
+            },
+            app,
+        )
+        .await
+        .map_err(|e| e.to_string());
+    let info = match created {
+        Ok(info) => info,
+        Err(e) => {
+            remove_worktree(&wt).await;
+            return Err(e);
+        }
+    };
+
+    let candidate = Candidate {
+        session_id: info.id,
+        label,
+        worktree: wt,
+    };
+
+    // New sessions start busy while the agent boots, so this queues the
+    // task until the agent's UI has settled.
+    let sink = sessions.lock().await.get_prompt_sink(&candidate.session_id).map_err(|e| e.to_string());
+    let task = task.to_string();
+    let queued = match sink {
+        Ok(sink) => tokio::task::spawn_blocking(move || sink.deliver(&task))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|r| r),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = queued {
+        discard(std::slice::from_ref(&candidate), sessions).await;
+        return Err(e);
+    }
+    Ok(candidate)
+}
+
+/// Close the candidates' sessions and remove their worktrees and branches.
+async fn discard(candidates: &[Candidate], sessions: &Arc<Mutex<SessionManager>>) {
+    for candidate in candidates {
+        {
+            let mut manager = sessions.lock().await;
+            let _ = manager.close_session(&candidate.session_id).await;
+        }
+        remove_worktree(&candidate.worktree).await;
+    }
+}
+
+async fn remove_worktree(wt: &ManagedWorktree) {
+    let worktree = wt.clone();
+    let cleanup = tokio::task::spawn_blocking(move || worktree.remove(true))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|r| r);
+    if let Err(e) = cleanup {
+        log::warn!("Failed to clean up best-of-n candidate {}: {}", wt.branch, e);
+    }
+}
+
+/// Wait until every candidate is idle (or gone), then build the comparison.
+/// A manual `compare` in the meantime doesn't end the wait: its results
+/// may predate the agents finishing, so they are rebuilt once they have.
+async fn monitor(
+    group_id: String,
+    sessions: Arc<Mutex<SessionManager>>,
+    groups: Arc<Mutex<BestOfNManager>>,
+    app: AppHandle,
+) {
+    loop {
+        tokio::time::sleep(MONITOR_INTERVAL).await;
+
+        let candidates = match groups.lock().await.groups.get(&group_id) {
+            Some(group) => match group.status {
+                GroupStatus::Running | GroupStatus::Complete => group.candidates.clone(),
+                // Let a manual comparison finish first
+                GroupStatus::Comparing => continue,
+                GroupStatus::Adopted => return,
+            },
+            None => return,
+        };
+
+        let settled = {
+            let manager = sessions.lock().await;
+            candidates.iter().all(|c| manager.is_settled(&c.session_id))
+        };
+        if !settled {
+            continue;
+        }
+
+        let _ = app.emit("best-of-n-comparing", serde_json::json!({ "group_id": group_id }));
+        if compare(&group_id, &groups).await.is_err() {
+            return;
+        }
+
+        if let Some(group) = groups.lock().await.groups.get(&group_id) {
+            let _ = app.emit("best-of-n-complete", group.clone());
+        }
+        return;
+    }
+}
+
+/// Collect diff stats and test results for every candidate in a group.
+pub async fn compare(group_id: &str, groups: &Arc<Mutex<BestOfNManager>>) -> Result<BestOfNGroup, String> {
+    let (candidates, base_commit, test_command) = {
+        let mut manager = groups.lock().await;
+        let group = manager.groups.get_mut(group_id).ok_or("Group not found")?;
+        if group.status == GroupStatus::Adopted {
+            return Err("Group has already been adopted".to_string());
+        }
+        group.status = GroupStatus::Comparing;
+        (group.candidates.clone(), group.base_commit.clone(), group.test_command.clone())
+    };
+
+    let mut results = Vec::with_capacity(candidates.len());
+    for candidate in &candidates {
+        results.push(compare_candidate(candidate, &base_commit, test_command.as_deref()).await);
+    }
+
+    let mut manager = groups.lock().await;
+    let group = manager.groups.get_mut(group_id).ok_or("Group not found")?;
+    group.results = results;
+    group.status = GroupStatus::Complete;
+    Ok(group.clone())
+}
+
+async fn compare_candidate(candidate: &Candidate, base_commit: &str, test_command: Option<&str>) -> CandidateResult {
+    let dir = PathBuf::from(&candidate.worktree.path);
+    let base = base_commit.to_string();
+    let dir_c = dir.clone();
+    let changes = tokio::task::spawn_blocking(move || worktree::changes_since(&dir_c, &base))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|r| r);
+
+    let (changes, error) = match changes {
+        Ok(changes) => (changes, None),
+        Err(e) => (Vec::new(), Some(e)),
+    };
+
+    let test = match test_command {
+        Some(cmd) if error.is_none() => Some(run_test(&dir, cmd).await),
+        _ => None,
+    };
+
+    CandidateResult {
+        session_id: candidate.session_id.clone(),
+        label: candidate.label.clone(),
+        branch: candidate.worktree.branch.clone(),
+        files_changed: changes.len(),
+        insertions: changes.iter().map(|c| c.insertions).sum(),
+        deletions: changes.iter().map(|c| c.deletions).sum(),
+        changes,
+        test,
+        error,
+    }
+}
+
+async fn run_test(dir: &Path, command: &str) -> TestOutcome {
+    let child = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(dir)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+
+    let child = match child {
+        Ok(c) => c,
+        Err(e) => {
+            return TestOutcome {
+                command: command.to_string(),
+                exit_code: None,
+                passed: false,
+                timed_out: false,
+                output_tail: format!("Failed to start: {}", e),
+            }
+        }
+    };
+
+    match tokio::time::timeout(TEST_TIMEOUT, child.wait_with_output()).await {
+        Ok(Ok(output)) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            TestOutcome {
+                command: command.to_string(),
+                exit_code: output.status.code(),
+                passed: output.status.success(),
+                timed_out: false,
+                output_tail: tail(&text, TEST_OUTPUT_TAIL),
+            }
+        }
+        Ok(Err(e)) => TestOutcome {
+            command: command.to_string(),
+            exit_code: None,
+            passed: false,
+            timed_out: false,
+            output_tail: e.to_string(),
+        },
+        Err(_) => TestOutcome {
+            command: command.to_string(),
+            exit_code: None,
+            passed: false,
+            timed_out: true,
+            output_tail: format!("Timed out after {}s", TEST_TIMEOUT.as_secs()),
+        },
+    }
+}
+
+
+/// Keep one candidate's branch and worktree; close the other sessions and
+/// remove their worktrees and branches.
+pub async fn adopt(
+    group_id: &str,
+    session_id: &str,
+    sessions: &Arc<Mutex<SessionManager>>,
+    groups: &Arc<Mutex<BestOfNManager>>,
+) -> Result<Candidate, String> {
+    let candidates = {
+        let manager = groups.lock().await;
+        let group = manager.groups.get(group_id).ok_or("Group not found")?;
+        if group.status == GroupStatus::Adopted {
+            return Err("Group has already been adopted".to_string());
+        }
+        group.candidates.clone()
+    };
+
+    let winner = candidates
+        .iter()
+        .find(|c| c.session_id == session_id)
+        .cloned()
+        .ok_or("Session is not a candidate in this group")?;
+
+    let losers: Vec<Candidate> = candidates.into_iter().filter(|c| c.session_id != session_id).collect();
+    discard(&losers, sessions).await;
+
+    let mut manager = groups.lock().await;
+    if let Some(group) = manager.groups.get_mut(group_id) {
+        group.status = GroupStatus::Adopted;
+        group.adopted = Some(winner.session_id.clone());
+    }
+
+    Ok(winner)
+}
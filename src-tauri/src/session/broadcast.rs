@@ -0,0 +1,80 @@
+use super::interrupt::InterruptReport;
+use super::process::Delivery;
+use crate::actions::SemanticAction;
+use serde::{Deserialize, Serialize};
+
+/// Which sessions a broadcast goes to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BroadcastTarget {
+    Sessions { session_ids: Vec<String> },
+    Tag { tag: String },
+    All,
+}
+
+/// What gets sent. Prompts may contain `{context}`, which is filled in with
+/// each session's own environment, exactly like custom actions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BroadcastPayload {
+    Prompt { text: String },
+    Action { action: SemanticAction },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BroadcastStatus {
+    Sent,
+    Queued,
+    Failed,
+}
+
+impl From<Delivery> for BroadcastStatus {
+    fn from(delivery: Delivery) -> Self {
+        match delivery {
+            Delivery::Sent => BroadcastStatus::Sent,
+            Delivery::Queued => BroadcastStatus::Queued,
+        }
+    }
+}
+
+/// Per-session outcome of a broadcast.
+#[derive(Debug, Clone, Serialize)]
+pub struct BroadcastResult {
+    pub session_id: String,
+    pub status: BroadcastStatus,
+    pub error: Option<String>,
+    /// How the session was stopped, for an interrupt.
+    pub interrupt: Option<InterruptReport>,
+}
+
+impl BroadcastResult {
+    pub fn delivered(session_id: &str, delivery: Delivery) -> Self {
+        BroadcastResult {
+            session_id: session_id.to_string(),
+            status: delivery.into(),
+            error: None,
+            interrupt: None,
+        }
+    }
+
+    /// Sent if a step of the ladder stopped the agent, failed otherwise.
+    pub fn interrupted(session_id: &str, report: InterruptReport) -> Self {
+        let stopped = report.succeeded.is_some();
+        BroadcastResult {
+            session_id: session_id.to_string(),
+            status: if stopped { BroadcastStatus::Sent } else { BroadcastStatus::Failed },
+            error: (!stopped).then(|| "Could not interrupt the agent".to_string()),
+            interrupt: Some(report),
+        }
+    }
+
+    pub fn failed(session_id: &str, error: impl Into<String>) -> Self {
+        BroadcastResult {
+            session_id: session_id.to_string(),
+            status: BroadcastStatus::Failed,
+            error: Some(error.into()),
+            interrupt: None,
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use super::process::PromptSink;
+use crate::config::InterruptConfig;
+use crate::procfs;
+use serde::Serialize;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Output must stop for this long after a step for the agent to count as
+/// stopped.
+const QUIET_PERIOD: Duration = Duration::from_secs(1);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Rungs of the interrupt ladder, gentlest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterruptStep {
+    /// Claude's TUI cancels the current turn on Escape.
+    Escape,
+    CtrlC,
+    /// SIGINT to the terminal's foreground process group.
+    Sigint,
+    /// SIGTERM to the agent and everything it started.
+    Sigterm,
+    /// SIGKILL to the agent and everything it started.
+    Sigkill,
+}
+
+impl InterruptStep {
+    const LADDER: [InterruptStep; 5] = [
+        InterruptStep::Escape,
+        InterruptStep::CtrlC,
+        InterruptStep::Sigint,
+        InterruptStep::Sigterm,
+        InterruptStep::Sigkill,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            InterruptStep::Escape => "Escape",
+            InterruptStep::CtrlC => "Ctrl+C",
+            InterruptStep::Sigint => "SIGINT",
+            InterruptStep::Sigterm => "SIGTERM",
+            InterruptStep::Sigkill => "SIGKILL",
+        }
+    }
+
+    fn wait(self, config: &InterruptConfig) -> Duration {
+        Duration::from_millis(match self {
+            InterruptStep::Escape => config.escape_wait_ms,
+            InterruptStep::CtrlC => config.ctrl_c_wait_ms,
+            InterruptStep::Sigint => config.sigint_wait_ms,
+            InterruptStep::Sigterm => config.sigterm_wait_ms,
+            InterruptStep::Sigkill => config.sigkill_wait_ms,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StepOutcome {
+    pub step: InterruptStep,
+    /// Why the step wasn't taken, e.g. nothing to signal.
+    pub skipped: Option<String>,
+    /// The session went idle, or the agent exited, within the step's wait.
+    pub stopped: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InterruptReport {
+    /// The step that stopped the agent; `None` if none did.
+    pub succeeded: Option<InterruptStep>,
+    /// Steps tried, in order.
+    pub steps: Vec<StepOutcome>,
+}
+
+/// What the ladder needs from a session, usable without the manager lock.
+pub struct InterruptTarget {
+    pub sink: PromptSink,
+    /// The shell hosting the agent. It is never signalled.
+    pub shell: Option<u32>,
+    /// The agent's program, to find its process under the shell.
+    pub agent: String,
+}
+
+impl InterruptTarget {
+    fn agent_pid(&self) -> Option<u32> {
+        procfs::find_child(self.shell?, &self.agent)
+    }
+
+    /// Escalate until the agent stops: keystrokes first, then signals.
+    /// Blocks for up to the sum of the configured waits.
+    pub fn escalate(&self, config: &InterruptConfig) -> InterruptReport {
+        let mut steps = Vec::new();
+        for step in InterruptStep::LADDER {
+            // An agent that wasn't found (already gone, or hidden behind a
+            // wrapper) can't count as stopped by disappearing
+            let agent = self.agent_pid();
+            let outcome = match self.take(step) {
+                Ok(()) => StepOutcome {
+                    step,
+                    skipped: None,
+                    stopped: self.wait_until_stopped(step, agent, step.wait(config)),
+                },
+                Err(reason) => StepOutcome {
+                    step,
+                    skipped: Some(reason),
+                    stopped: false,
+                },
+            };
+            let stopped = outcome.stopped;
+            steps.push(outcome);
+            if stopped {
+                return InterruptReport { succeeded: Some(step), steps };
+            }
+        }
+        InterruptReport { succeeded: None, steps }
+    }
+
+    fn take(&self, step: InterruptStep) -> Result<(), String> {
+        match step {
+            InterruptStep::Escape => self.sink.write_raw(b"\x1b"),
+            InterruptStep::CtrlC => self.sink.write_raw(&[0x03]),
+            InterruptStep::Sigint => {
+                let shell = self.shell.ok_or("Shell not running")?;
+                let fields = procfs::read_stat(shell).ok_or("Shell not running")?;
+                let field = |i: usize| fields.get(i).and_then(|f| f.parse::<i32>().ok());
+                // stat fields after the name: 2 is the process group, 5 the
+                // terminal's foreground group
+                let (group, foreground) = (field(2), field(5));
+                match foreground {
+                    Some(fg) if fg > 0 && Some(fg) != group => signal(-fg, libc::SIGINT),
+                    _ => Err("Nothing running in the foreground".to_string()),
+                }
+            }
+            InterruptStep::Sigterm | InterruptStep::Sigkill => {
+                let agent = self.agent_pid().ok_or("Agent not running")?;
+                let sig = if step == InterruptStep::Sigterm { libc::SIGTERM } else { libc::SIGKILL };
+                // Children too, so a hung tool doesn't outlive the agent
+                for pid in procfs::process_tree(&[agent]) {
+                    if Some(pid) != self.shell {
+                        let _ = signal(pid as i32, sig);
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether, within `wait`, the agent (`agent`, as found before the
+    /// step) exited or, for keystrokes and SIGINT, the session printed
+    /// nothing for `QUIET_PERIOD`.
+    fn wait_until_stopped(&self, step: InterruptStep, agent: Option<u32>, wait: Duration) -> bool {
+        let started = Instant::now();
+        let by_signal = matches!(step, InterruptStep::Sigterm | InterruptStep::Sigkill);
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            if agent.is_some() && self.agent_pid() != agent {
+                return true;
+            }
+            let quiet = started.elapsed() >= QUIET_PERIOD && self.sink.quiet_for() >= QUIET_PERIOD;
+            if quiet && !by_signal {
+                return true;
+            }
+            if started.elapsed() >= wait {
+                return false;
+            }
+        }
+    }
+}
+
+fn signal(pid: i32, sig: libc::c_int) -> Result<(), String> {
+    if unsafe { libc::kill(pid, sig) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error().to_string())
+    }
+}
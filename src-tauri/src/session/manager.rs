@@ -1,7 +1,15 @@
-use super::process::{ClaudeProcess, PtyWriter};
+use super::broadcast::BroadcastTarget;
+use super::interrupt::InterruptTarget;
+use super::output::{NewOutput, TerminalOutput};
+use super::ports::PortWatcher;
+use super::process::{ClaudeProcess, PromptSink, PtyWriter};
+use super::project::{self, ProjectConfig, ProjectScripts, ScriptStage};
+use crate::git::worktree::ManagedWorktree;
+use crate::procfs::{self, ListeningPort, ProcessScope};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use tauri::{AppHandle, Emitter};
 use uuid::Uuid;
@@ -14,6 +22,34 @@ pub struct SessionInfo {
     pub created_at: DateTime<Utc>,
     pub is_active: bool,
     pub is_busy: bool,
+    /// Free-form labels used to address groups of sessions (e.g. broadcast).
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Prompts waiting for the session to go idle.
+    #[serde(default)]
+    pub queued_prompts: usize,
+    /// DeckMind-managed worktree the session runs in, if any.
+    #[serde(default)]
+    pub worktree: Option<ManagedWorktree>,
+    /// TCP ports the session's processes are listening on.
+    #[serde(default)]
+    pub listening_ports: Vec<ListeningPort>,
+}
+
+/// Everything needed to start a session.
+pub struct NewSession {
+    pub name: Option<String>,
+    pub working_dir: Option<String>,
+    pub claude_path: String,
+    pub extra_flags: Option<String>,
+    /// Launch with `--dangerously-skip-permissions`, which only Claude
+    /// understands.
+    pub skip_permissions: bool,
+    pub tags: Vec<String>,
+    /// Managed worktree to run in; overrides `working_dir`.
+    pub worktree: Option<ManagedWorktree>,
+    /// Remove the worktree on close if it has no uncommitted changes.
+    pub delete_worktree_on_close: bool,
 }
 
 struct Session {
@@ -23,9 +59,26 @@ struct Session {
     /// Stored so the frontend can re-invoke claude after it exits
     /// (the shell stays alive, just type the command again).
     claude_path: String,
-    /// Extra CLI flags passed at session creation (e.g. --worktree --model opus).
+    /// Extra CLI flags passed at session creation (e.g. --worktree --model opus),
+    /// after `--dangerously-skip-permissions` when the session skips them.
     /// Stored so restarts include the same flags.
     launch_flags: String,
+    delete_worktree_on_close: bool,
+    /// `.deckmind.yaml` scripts for the session's project, if it has one.
+    project: Option<ProjectScripts>,
+    ports: PortWatcher,
+}
+
+impl Session {
+    /// Session info with live busy/queue state filled in.
+    fn snapshot(&self) -> SessionInfo {
+        SessionInfo {
+            is_busy: self.process.is_busy(),
+            queued_prompts: self.process.queued_prompts(),
+            listening_ports: self.ports.ports(),
+            ..self.info.clone()
+        }
+    }
 }
 
 pub struct SessionManager {
@@ -42,16 +95,19 @@ impl SessionManager {
     }
 
     /// Create a new session by spawning a shell with Claude running inside it.
+    /// A `.deckmind.yaml` in the working dir adds env vars, a setup script
+    /// run before Claude, a companion `run` process and a teardown script.
     pub async fn create_session(
         &mut self,
-        name: Option<String>,
-        working_dir: Option<String>,
-        claude_path: &str,
-        extra_flags: Option<String>,
+        new: NewSession,
         app_handle: &AppHandle,
     ) -> Result<SessionInfo, Box<dyn std::error::Error>> {
         let id = Uuid::new_v4().to_string();
-        let session_name = name.unwrap_or_else(|| format!("Session {}", self.sessions.len() + 1));
+        let session_name = new.name.unwrap_or_else(|| format!("Session {}", self.sessions.len() + 1));
+        let working_dir = match new.worktree {
+            Some(ref wt) => Some(wt.path.clone()),
+            None => new.working_dir,
+        };
 
         let info = SessionInfo {
             id: id.clone(),
@@ -60,22 +116,65 @@ impl SessionManager {
             created_at: Utc::now(),
             is_active: true,
             is_busy: false,
+            tags: new.tags,
+            queued_prompts: 0,
+            worktree: new.worktree,
+            listening_ports: Vec::new(),
         };
 
-        let flags = extra_flags.unwrap_or_default();
+        let project_config = working_dir.as_deref().and_then(|dir| {
+            match ProjectConfig::load(std::path::Path::new(dir)) {
+                Ok(config) => config,
+                Err(e) => {
+                    project::report(app_handle, &id, ScriptStage::Config, &Err(e));
+                    None
+                }
+            }
+        });
+
+        let mut flags = new.extra_flags.unwrap_or_default();
+        if new.skip_permissions {
+            flags = format!("--dangerously-skip-permissions {}", flags).trim_end().to_string();
+        }
         let process = ClaudeProcess::spawn(
-            claude_path,
+            &new.claude_path,
             working_dir.as_deref(),
             &flags,
+            project_config.as_ref(),
             id.clone(),
             app_handle.clone(),
         )?;
 
+        let project = match (project_config, &working_dir) {
+            (Some(config), Some(dir)) => Some(ProjectScripts::start(
+                config,
+                PathBuf::from(dir),
+                id.clone(),
+                app_handle.clone(),
+                process.setup_status(),
+            )),
+            _ => None,
+        };
+
+        let shell_pid = process.pid();
+        let companion_pid = project.as_ref().and_then(|p| p.companion_pid());
+        let ports = PortWatcher::start(
+            id.clone(),
+            move || {
+                let companion = companion_pid.as_ref().and_then(|slot| slot.lock().ok().and_then(|p| *p));
+                shell_pid.into_iter().chain(companion).collect()
+            },
+            app_handle.clone(),
+        );
+
         let session = Session {
             info: info.clone(),
             process,
-            claude_path: claude_path.to_string(),
+            claude_path: new.claude_path,
             launch_flags: flags,
+            delete_worktree_on_close: new.delete_worktree_on_close,
+            project,
+            ports,
         };
 
         self.sessions.insert(id.clone(), session);
@@ -90,6 +189,26 @@ impl SessionManager {
     ) -> Result<(), Box<dyn std::error::Error>> {
         if let Some(mut session) = self.sessions.remove(session_id) {
             session.process.kill();
+
+            let worktree = session.info.worktree.take().filter(|_| session.delete_worktree_on_close);
+            let project = session.project.take();
+            // Teardown can take a while; don't hold up the caller (or the
+            // manager lock) for it. The worktree goes only after teardown.
+            if project.is_some() || worktree.is_some() {
+                tokio::spawn(async move {
+                    if let Some(project) = project {
+                        project.shutdown().await;
+                    }
+                    if let Some(wt) = worktree {
+                        let path = wt.path.clone();
+                        match tokio::task::spawn_blocking(move || wt.remove(false)).await {
+                            Ok(Ok(())) => log::info!("Removed worktree {} on close", path),
+                            Ok(Err(e)) => log::info!("Keeping worktree {}: {}", path, e),
+                            Err(e) => log::warn!("Removing worktree {} failed: {}", path, e),
+                        }
+                    }
+                });
+            }
         }
         if self.active_session_id.as_deref() == Some(session_id) {
             self.active_session_id = self.sessions.keys().next().cloned();
@@ -98,7 +217,104 @@ impl SessionManager {
     }
 
     pub fn list_sessions(&self) -> Vec<SessionInfo> {
-        self.sessions.values().map(|s| s.info.clone()).collect()
+        self.sessions.values().map(|s| s.snapshot()).collect()
+    }
+
+    /// Replace the tags on a session.
+    pub fn set_tags(
+        &mut self,
+        session_id: &str,
+        tags: Vec<String>,
+    ) -> Result<SessionInfo, Box<dyn std::error::Error>> {
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or("Session not found")?;
+        session.info.tags = tags;
+        Ok(session.snapshot())
+    }
+
+    /// Resolve a broadcast target to the matching session IDs.
+    /// Unknown IDs are kept so the caller can report them as failures.
+    pub fn resolve_targets(&self, target: &BroadcastTarget) -> Vec<String> {
+        let mut ids: Vec<String> = match target {
+            BroadcastTarget::Sessions { session_ids } => return session_ids.clone(),
+            BroadcastTarget::Tag { tag } => self
+                .sessions
+                .values()
+                .filter(|s| s.info.tags.iter().any(|t| t == tag))
+                .map(|s| s.info.id.clone())
+                .collect(),
+            BroadcastTarget::All => self.sessions.keys().cloned().collect(),
+        };
+        // Stable order (creation time) so results line up with the session list
+        ids.sort_by_key(|id| self.sessions.get(id).map(|s| s.info.created_at));
+        ids
+    }
+
+    /// Session running in the worktree at `path`, if any.
+    pub fn worktree_owner(&self, path: &str) -> Option<String> {
+        self.sessions
+            .values()
+            .find(|s| s.info.working_dir.as_deref() == Some(path))
+            .map(|s| s.info.id.clone())
+    }
+
+    /// Whether a session has nothing left to do: idle with an empty prompt
+    /// queue, or already closed.
+    pub fn is_settled(&self, session_id: &str) -> bool {
+        match self.sessions.get(session_id) {
+            Some(s) => !s.process.is_busy() && s.process.queued_prompts() == 0,
+            None => true,
+        }
+    }
+
+    /// Get the working directory a session was started in.
+    pub fn get_working_dir(&self, session_id: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let session = self
+            .sessions
+            .get(session_id)
+            .ok_or("Session not found")?;
+        Ok(session.info.working_dir.clone())
+    }
+
+    /// The session's processes: everything under the shell hosting the
+    /// agent and under the project's `run` companion, if running, except
+    /// the shell and the agent themselves.
+    pub fn process_scope(&self, session_id: &str) -> ProcessScope {
+        let Some(session) = self.sessions.get(session_id) else { return ProcessScope::default() };
+        let shell = session.process.pid();
+        let companion = session
+            .project
+            .as_ref()
+            .and_then(|p| p.companion_pid())
+            .and_then(|slot| slot.lock().ok().and_then(|p| *p));
+        let agent = shell.and_then(|pid| procfs::find_child(pid, &session.claude_path));
+        ProcessScope {
+            roots: shell.into_iter().chain(companion).collect(),
+            exclude: shell.into_iter().chain(agent).collect(),
+        }
+    }
+
+    /// The session's recent terminal output, ANSI-stripped.
+    pub fn terminal_output(&self, session_id: &str) -> Option<TerminalOutput> {
+        self.sessions.get(session_id).map(|s| s.process.terminal_output())
+    }
+
+    /// Output lines written since `completed` lines had been; `None` once
+    /// the session is closed.
+    pub fn output_since(&self, session_id: &str, completed: u64) -> Option<NewOutput> {
+        self.sessions.get(session_id).map(|s| s.process.output_since(completed))
+    }
+
+    /// Process id of the shell the session runs in.
+    pub fn shell_pid(&self, session_id: &str) -> Option<u32> {
+        self.sessions.get(session_id)?.process.pid()
+    }
+
+    /// Path of the shell the session runs in.
+    pub fn shell(&self, session_id: &str) -> Option<String> {
+        self.sessions.get(session_id).map(|s| s.process.shell().to_string())
     }
 
     /// Get the claude CLI path for a session so the frontend can
@@ -131,6 +347,17 @@ impl SessionManager {
         Ok(session.process.pty_writer.clone())
     }
 
+    /// Get a prompt delivery handle for a session. Like `get_writer`, this
+    /// lets callers write off the async runtime, but also tracks busy state
+    /// and can queue prompts while the agent is working.
+    pub fn get_prompt_sink(&self, session_id: &str) -> Result<PromptSink, Box<dyn std::error::Error>> {
+        let session = self
+            .sessions
+            .get(session_id)
+            .ok_or("Session not found")?;
+        Ok(session.process.prompt_sink())
+    }
+
     pub async fn send_to_session(
         &mut self,
         session_id: &str,
@@ -142,7 +369,6 @@ impl SessionManager {
             .get_mut(session_id)
             .ok_or("Session not found")?;
 
-        session.info.is_busy = true;
         session.process.send(message)?;
 
         let _ = app.emit("session-message-sent", serde_json::json!({
@@ -167,17 +393,16 @@ impl SessionManager {
         Ok(())
     }
 
-    pub fn interrupt_session(
-        &mut self,
-        session_id: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    /// Handles for stopping a session's agent off the async runtime.
+    pub fn interrupt_target(&self, session_id: &str) -> Result<InterruptTarget, Box<dyn std::error::Error>> {
         let session = self
             .sessions
-            .get_mut(session_id)
+            .get(session_id)
             .ok_or("Session not found")?;
-
-        session.process.send_interrupt()?;
-        session.info.is_busy = false;
-        Ok(())
+        Ok(InterruptTarget {
+            sink: session.process.prompt_sink(),
+            shell: session.process.pid(),
+            agent: session.claude_path.clone(),
+        })
     }
 }
@@ -1,4 +1,11 @@
+pub mod best_of_n;
+pub mod broadcast;
+pub mod interrupt;
 mod manager;
+pub mod output;
 pub mod process;
+mod ports;
+mod project;
 
-pub use manager::{SessionManager, SessionInfo};
+pub use manager::{NewSession, SessionManager, SessionInfo};
+pub use project::ProjectConfig;
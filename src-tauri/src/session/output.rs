@@ -0,0 +1,373 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+
+/// Lines of plain text kept per session.
+const MAX_LINES: usize = 500;
+
+/// Lines handed out by `OutputTail::snapshot`.
+const SNAPSHOT_LINES: usize = 100;
+
+/// Characters kept per line; the rest of a longer line is dropped.
+const MAX_LINE_CHARS: usize = 2000;
+
+/// Longest OSC payload that is interpreted; longer ones are skipped.
+const MAX_OSC_LEN: usize = 4096;
+
+/// Where the parser is within an escape sequence.
+#[derive(Default)]
+enum Escape {
+    #[default]
+    None,
+    /// After ESC.
+    Esc,
+    /// After ESC and a character-set designator such as `(`.
+    Charset,
+    /// In `ESC [ ... <final>`.
+    Csi,
+    /// In `ESC ] ... BEL` (or `ESC \`).
+    Osc(String),
+    /// ESC inside an OSC: `\` ends it.
+    OscEsc(String),
+}
+
+/// Position in the prompt/command cycle, from shell integration marks
+/// (OSC 133, or VS Code's OSC 633).
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    Unknown,
+    /// Between `A` (prompt start) and `B` (prompt end).
+    Prompt,
+    /// Between `B` and `C`: the user is typing a command.
+    Input,
+    /// Between `C` (command started) and `D` (finished).
+    Output,
+}
+
+/// A command delimited by shell integration marks.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandOutput {
+    pub command: String,
+    pub output: Vec<String>,
+    /// `None` while running or if the shell didn't report it.
+    pub exit_code: Option<i32>,
+    pub finished: bool,
+}
+
+/// Recent plain-text output of a session.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TerminalOutput {
+    /// Last lines on screen, oldest first.
+    pub lines: Vec<String>,
+    /// The latest command, when the shell marks commands.
+    pub last_command: Option<CommandOutput>,
+    /// Lines finished since the session started.
+    pub completed: u64,
+    /// The last of `lines` is still being written.
+    pub partial: bool,
+}
+
+impl TerminalOutput {
+    /// Lines written after the first `completed` ones, including an
+    /// unfinished last line. Limited to what the snapshot holds.
+    pub fn lines_since(&self, completed: u64) -> &[String] {
+        let new = self.completed.saturating_sub(completed) as usize + usize::from(self.partial);
+        &self.lines[self.lines.len().saturating_sub(new)..]
+    }
+}
+
+/// Output written after a given point (see `OutputTail::lines_since`).
+#[derive(Debug, Clone, Default)]
+pub struct NewOutput {
+    pub lines: Vec<String>,
+    /// Lines finished since the session started; pass it back to continue
+    /// after these lines.
+    pub completed: u64,
+    /// The last of `lines` is still being written.
+    pub partial: bool,
+}
+
+/// Turns a session's PTY stream into plain lines: escape sequences are
+/// dropped, carriage returns overwrite the line (so progress bars leave
+/// their final state), and OSC 133 marks split out each command's output.
+pub struct OutputTail {
+    lines: VecDeque<String>,
+    completed: u64,
+    current: String,
+    /// A `\r` was seen; the next character starts the line over unless it
+    /// is `\n`.
+    carriage_return: bool,
+    escape: Escape,
+    phase: Phase,
+    /// What's been typed since the prompt ended.
+    input: String,
+    running: Option<CommandOutput>,
+    last_command: Option<CommandOutput>,
+}
+
+impl OutputTail {
+    pub fn new() -> Self {
+        OutputTail {
+            lines: VecDeque::new(),
+            completed: 0,
+            current: String::new(),
+            carriage_return: false,
+            escape: Escape::None,
+            phase: Phase::Unknown,
+            input: String::new(),
+            running: None,
+            last_command: None,
+        }
+    }
+
+    pub fn push(&mut self, text: &str) {
+        for c in text.chars() {
+            match std::mem::take(&mut self.escape) {
+                Escape::None => self.put(c),
+                Escape::Esc => {
+                    self.escape = match c {
+                        '[' => Escape::Csi,
+                        ']' => Escape::Osc(String::new()),
+                        '(' | ')' | '*' | '+' | '#' | '%' => Escape::Charset,
+                        _ => Escape::None,
+                    }
+                }
+                Escape::Charset => {}
+                Escape::Csi => {
+                    if !('\x40'..='\x7e').contains(&c) {
+                        self.escape = Escape::Csi;
+                    }
+                }
+                Escape::Osc(mut payload) => match c {
+                    '\x07' => self.osc(&payload),
+                    '\x1b' => self.escape = Escape::OscEsc(payload),
+                    _ => {
+                        if payload.len() < MAX_OSC_LEN {
+                            payload.push(c);
+                        }
+                        self.escape = Escape::Osc(payload);
+                    }
+                },
+                Escape::OscEsc(payload) => {
+                    if c == '\\' {
+                        self.osc(&payload);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The last lines (including an unfinished one) and the latest command.
+    pub fn snapshot(&self) -> TerminalOutput {
+        let start = self.lines.len().saturating_sub(SNAPSHOT_LINES);
+        let mut lines: Vec<String> = self.lines.iter().skip(start).cloned().collect();
+        let partial = !self.current.trim().is_empty();
+        if partial {
+            lines.push(self.current.trim_end().to_string());
+        }
+        let running = self.running.clone().map(|mut command| {
+            if !self.current.trim().is_empty() {
+                command.output.push(self.current.trim_end().to_string());
+            }
+            command
+        });
+        TerminalOutput {
+            lines,
+            last_command: running.or_else(|| self.last_command.clone()),
+            completed: self.completed,
+            partial,
+        }
+    }
+
+    /// Number of lines finished so far.
+    pub fn completed(&self) -> u64 {
+        self.completed
+    }
+
+    /// Lines finished after the first `completed`, plus an unfinished
+    /// one: as many of them as are still kept.
+    pub fn lines_since(&self, completed: u64) -> NewOutput {
+        let new = (self.completed.saturating_sub(completed) as usize).min(self.lines.len());
+        let mut lines: Vec<String> = self.lines.iter().skip(self.lines.len() - new).cloned().collect();
+        let partial = !self.current.trim().is_empty();
+        if partial {
+            lines.push(self.current.trim_end().to_string());
+        }
+        NewOutput {
+            lines,
+            completed: self.completed,
+            partial,
+        }
+    }
+
+    fn put(&mut self, c: char) {
+        match c {
+            '\x1b' => {
+                self.escape = Escape::Esc;
+                return;
+            }
+            '\r' => {
+                self.carriage_return = true;
+                return;
+            }
+            '\n' => {
+                self.carriage_return = false;
+                self.end_line();
+                return;
+            }
+            _ => {}
+        }
+        if std::mem::take(&mut self.carriage_return) {
+            self.current.clear();
+        }
+        match c {
+            '\x08' => {
+                self.current.pop();
+                if self.phase == Phase::Input {
+                    self.input.pop();
+                }
+            }
+            '\t' => self.append(' '),
+            c if c.is_control() => {}
+            c => self.append(c),
+        }
+    }
+
+    fn append(&mut self, c: char) {
+        if self.current.len() < MAX_LINE_CHARS {
+            self.current.push(c);
+        }
+        if self.phase == Phase::Input && self.input.len() < MAX_LINE_CHARS {
+            self.input.push(c);
+        }
+    }
+
+    fn end_line(&mut self) {
+        let line = std::mem::take(&mut self.current).trim_end().to_string();
+        if let (Phase::Output, Some(command)) = (self.phase, &mut self.running) {
+            command.output.push(line.clone());
+            if command.output.len() > MAX_LINES {
+                command.output.remove(0);
+            }
+        }
+        if self.phase == Phase::Input {
+            self.input.push('\n');
+        }
+        self.lines.push_back(line);
+        self.completed += 1;
+        if self.lines.len() > MAX_LINES {
+            self.lines.pop_front();
+        }
+    }
+
+    /// Act on shell integration marks; other OSC sequences are ignored.
+    fn osc(&mut self, payload: &str) {
+        let Some(mark) = payload.strip_prefix("133;").or_else(|| payload.strip_prefix("633;")) else {
+            return;
+        };
+        let mut fields = mark.split(';');
+        match fields.next() {
+            Some("A") => {
+                self.finish(None);
+                self.phase = Phase::Prompt;
+            }
+            Some("B") => {
+                self.phase = Phase::Input;
+                self.input.clear();
+            }
+            // VS Code reports the command line itself
+            Some("E") => {
+                if let Some(command) = fields.next() {
+                    self.input = command.to_string();
+                }
+            }
+            Some("C") => {
+                self.finish(None);
+                self.running = Some(CommandOutput {
+                    command: self.input.trim().to_string(),
+                    output: Vec::new(),
+                    exit_code: None,
+                    finished: false,
+                });
+                self.phase = Phase::Output;
+                // Output starts on a fresh line
+                self.current.clear();
+            }
+            Some("D") => {
+                let code = fields.next().and_then(|c| c.trim().parse().ok());
+                self.finish(code);
+                self.phase = Phase::Unknown;
+            }
+            _ => {}
+        }
+    }
+
+    /// Close the running command, including output without a final newline.
+    fn finish(&mut self, exit_code: Option<i32>) {
+        let Some(mut command) = self.running.take() else { return };
+        if self.phase == Phase::Output && !self.current.trim().is_empty() {
+            command.output.push(self.current.trim_end().to_string());
+        }
+        while command.output.last().is_some_and(|l| l.is_empty()) {
+            command.output.pop();
+        }
+        command.exit_code = exit_code;
+        command.finished = true;
+        self.last_command = Some(command);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tail(text: &str) -> OutputTail {
+        let mut tail = OutputTail::new();
+        tail.push(text);
+        tail
+    }
+
+    #[test]
+    fn drops_escapes_and_crlf() {
+        let output = tail("\x1b[1;31mred\x1b[0m text\r\n\x1b(Bnext\r\n").snapshot();
+        assert_eq!(output.lines, ["red text", "next"]);
+        assert_eq!(output.completed, 2);
+        assert!(!output.partial);
+    }
+
+    #[test]
+    fn carriage_return_keeps_final_state() {
+        let output = tail("10%\r50%\r100%\r\ndone\x08\x08ne").snapshot();
+        assert_eq!(output.lines, ["100%", "done"]);
+        assert!(output.partial);
+    }
+
+    #[test]
+    fn osc_ends_at_bel_or_st() {
+        let output = tail("\x1b]0;title\x07one\r\n\x1b]2;other title\x1b\\two\r\n").snapshot();
+        assert_eq!(output.lines, ["one", "two"]);
+    }
+
+    #[test]
+    fn shell_marks_split_out_commands() {
+        let mut output = tail("\x1b]133;A\x1b\\$ \x1b]133;B\x1b\\ls src\r\n\x1b]133;C\x07a.rs\r\nb.rs\r\n");
+        let running = output.snapshot().last_command.expect("running command");
+        assert_eq!(running.command, "ls src");
+        assert!(!running.finished);
+
+        output.push("\x1b]133;D;2\x1b\\\x1b]133;A\x07$ ");
+        let command = output.snapshot().last_command.expect("finished command");
+        assert_eq!(command.output, ["a.rs", "b.rs"]);
+        assert_eq!(command.exit_code, Some(2));
+        assert!(command.finished);
+    }
+
+    #[test]
+    fn lines_since_includes_the_unfinished_line() {
+        let output = tail("a\nb\nc\npartial");
+        let new = output.lines_since(1);
+        assert_eq!(new.lines, ["b", "c", "partial"]);
+        assert_eq!(new.completed, 3);
+        assert!(new.partial);
+        assert_eq!(output.snapshot().lines_since(2), ["c", "partial"]);
+    }
+}
@@ -0,0 +1,63 @@
+use crate::procfs::{self, ListeningPort};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// How often a session's process tree is checked for listening sockets.
+const PORT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Tracks the TCP ports a session's processes listen on and emits
+/// `session-ports` whenever the set changes (e.g. a dev server came up).
+pub struct PortWatcher {
+    ports: Arc<Mutex<Vec<ListeningPort>>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl PortWatcher {
+    /// `roots` yields the pids whose process trees belong to the session;
+    /// it is called on every poll since companion processes come and go.
+    pub fn start(
+        session_id: String,
+        roots: impl Fn() -> Vec<u32> + Send + 'static,
+        app_handle: AppHandle,
+    ) -> Self {
+        let ports = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let watcher_ports = ports.clone();
+        let watcher_stop = stop.clone();
+        thread::spawn(move || {
+            while !watcher_stop.load(Ordering::Relaxed) {
+                let current = procfs::listening_ports(&roots());
+                let changed = match watcher_ports.lock() {
+                    Ok(mut known) if *known != current => {
+                        *known = current.clone();
+                        true
+                    }
+                    _ => false,
+                };
+                if changed {
+                    let _ = app_handle.emit("session-ports", serde_json::json!({
+                        "session_id": session_id,
+                        "ports": current,
+                    }));
+                }
+                thread::sleep(PORT_POLL_INTERVAL);
+            }
+        });
+
+        PortWatcher { ports, stop }
+    }
+
+    pub fn ports(&self) -> Vec<ListeningPort> {
+        self.ports.lock().map(|p| p.clone()).unwrap_or_default()
+    }
+}
+
+impl Drop for PortWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
@@ -1,8 +1,14 @@
+use chrono::{DateTime, Utc};
 use portable_pty::{CommandBuilder, NativePtySystem, PtySize, PtySystem, MasterPty, PtyPair};
+use std::collections::VecDeque;
 use std::io::{Read, Write};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
+use super::output::{NewOutput, OutputTail, TerminalOutput};
+use super::project::{self, ProjectConfig, ScriptStage};
 use tauri::{AppHandle, Emitter};
+use tokio::sync::{oneshot, watch};
 
 /// OSC escape sequence used as an invisible sentinel after Claude exits.
 /// OSC (Operating System Command) sequences are terminal control codes that
@@ -14,6 +20,22 @@ use tauri::{AppHandle, Emitter};
 /// The reader thread detects it in the raw byte stream and emits `claude-exited`.
 const CLAUDE_EXIT_SENTINEL: &str = "\x1b]666;\x07";
 
+/// Start of the OSC sequence a project setup script emits when it finishes:
+/// ESC ] 667 ; <exit code> BEL. See `ProjectConfig::write_setup_script`.
+const SETUP_DONE_PREFIX: &str = "\x1b]667;";
+
+/// How much unmatched output the reader keeps to catch sequences split
+/// across reads.
+const PENDING_TAIL: usize = 32;
+
+/// How long the PTY must stay quiet after a prompt is delivered before the
+/// session counts as idle again. Claude's spinner redraws several times a
+/// second while it works, so a short silence is a reliable "done" signal.
+const IDLE_QUIET_PERIOD: Duration = Duration::from_millis(1500);
+
+/// How often the idle watcher checks for quiet sessions.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
 /// Direct PTY writer — no BufWriter. PTY writes go straight to the kernel
 /// pseudo-terminal device, where buffering adds latency for single-byte
 /// keystrokes (Escape, Enter, Ctrl+C).
@@ -34,12 +56,131 @@ impl PtyWriter {
     }
 }
 
+/// Busy/idle bookkeeping shared by the reader thread, the idle watcher
+/// and anything that writes prompts.
+struct Activity {
+    busy: bool,
+    last_output: Instant,
+    /// Keeps the session busy while a project setup script runs, so queued
+    /// prompts are not typed into it during a quiet stretch.
+    held: bool,
+}
+
+/// Outcome of handing a prompt to a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Delivery {
+    /// Written to the PTY immediately.
+    Sent,
+    /// The session was busy; the prompt will be written once it goes idle.
+    Queued,
+}
+
+/// How far a session's output had got when a prompt was written to it.
+#[derive(Debug, Clone, Copy)]
+pub struct WriteMark {
+    /// Output lines the session had finished.
+    pub completed: u64,
+    pub at: DateTime<Utc>,
+}
+
+/// A prompt waiting for the session to go idle.
+struct QueuedPrompt {
+    text: String,
+    /// Sent the output position just before the prompt is written.
+    written: Option<oneshot::Sender<WriteMark>>,
+}
+
+/// Cloneable handle for writing prompts to a session from outside the
+/// session manager lock (e.g. inside `spawn_blocking`).
+#[derive(Clone)]
+pub struct PromptSink {
+    writer: Arc<Mutex<PtyWriter>>,
+    activity: Arc<Mutex<Activity>>,
+    queue: Arc<Mutex<VecDeque<QueuedPrompt>>>,
+    output: Arc<Mutex<OutputTail>>,
+}
+
+impl PromptSink {
+    /// Write a prompt followed by Enter and mark the session busy.
+    pub fn write_prompt(&self, prompt: &str) -> Result<(), String> {
+        write_prompt(&self.writer, &self.activity, prompt)
+    }
+
+    /// Write the prompt now if the session is idle, otherwise queue it for
+    /// the idle watcher to deliver.
+    pub fn deliver(&self, prompt: &str) -> Result<Delivery, String> {
+        self.deliver_with(prompt, None)
+    }
+
+    /// `deliver`, sending `written` where the output stood just before the
+    /// prompt is written, now or once it leaves the queue. It's dropped
+    /// unsent if a queued prompt never is.
+    pub fn deliver_tracked(&self, prompt: &str, written: oneshot::Sender<WriteMark>) -> Result<Delivery, String> {
+        self.deliver_with(prompt, Some(written))
+    }
+
+    fn deliver_with(&self, prompt: &str, written: Option<oneshot::Sender<WriteMark>>) -> Result<Delivery, String> {
+        let busy = self.activity.lock().map(|a| a.busy).unwrap_or(false);
+        if busy {
+            let mut queue = self.queue.lock().map_err(|e| e.to_string())?;
+            queue.push_back(QueuedPrompt { text: prompt.to_string(), written });
+            return Ok(Delivery::Queued);
+        }
+        let mark = write_mark(&self.output);
+        self.write_prompt(prompt)?;
+        if let Some(written) = written {
+            let _ = written.send(mark);
+        }
+        Ok(Delivery::Sent)
+    }
+
+    /// Time since the session last printed anything.
+    pub fn quiet_for(&self) -> Duration {
+        self.activity.lock().map(|a| a.last_output.elapsed()).unwrap_or_default()
+    }
+
+    /// Write raw bytes (e.g. Ctrl+C) without touching busy state.
+    pub fn write_raw(&self, bytes: &[u8]) -> Result<(), String> {
+        let mut w = self.writer.lock().map_err(|e| e.to_string())?;
+        w.write(bytes).map_err(|e| e.to_string())
+    }
+}
+
+fn write_mark(output: &Arc<Mutex<OutputTail>>) -> WriteMark {
+    WriteMark {
+        completed: output.lock().map(|tail| tail.completed()).unwrap_or(0),
+        at: Utc::now(),
+    }
+}
+
+fn write_prompt(
+    writer: &Arc<Mutex<PtyWriter>>,
+    activity: &Arc<Mutex<Activity>>,
+    prompt: &str,
+) -> Result<(), String> {
+    let mut w = writer.lock().map_err(|e| e.to_string())?;
+    let mut data = prompt.as_bytes().to_vec();
+    data.push(b'\r');
+    w.write(&data).map_err(|e| e.to_string())?;
+    if let Ok(mut a) = activity.lock() {
+        a.busy = true;
+        a.last_output = Instant::now();
+    }
+    Ok(())
+}
+
 pub struct ClaudeProcess {
     pub pty_writer: Arc<Mutex<PtyWriter>>,
+    activity: Arc<Mutex<Activity>>,
+    queue: Arc<Mutex<VecDeque<QueuedPrompt>>>,
     _master: Box<dyn MasterPty + Send>,
     _child: Arc<Mutex<Box<dyn portable_pty::Child + Send + Sync>>>,
     reader_handle: Option<thread::JoinHandle<()>>,
     alive: Arc<Mutex<bool>>,
+    setup_status: watch::Receiver<Option<i32>>,
+    shell: String,
+    output: Arc<Mutex<OutputTail>>,
 }
 
 impl ClaudeProcess {
@@ -57,14 +198,21 @@ impl ClaudeProcess {
     /// The printf emits an OSC escape sequence that xterm.js silently
     /// discards (invisible to the user) but the reader thread detects.
     ///
+    /// With a project `setup` script, it runs first in the same terminal and
+    /// Claude only starts if it succeeds:
+    ///
+    ///   sh '/tmp/deckmind-setup-<id>.sh' && claude ...; printf '\033]666;\007'
+    ///
     /// The reader thread emits:
     ///   - `session-output` for all PTY data (displayed in xterm.js)
     ///   - `claude-exited` when the OSC sentinel is detected (Claude exited, shell alive)
     ///   - `session-done` on actual EOF (shell itself exited)
+    ///   - `project-script` when the setup script finishes
     pub fn spawn(
         claude_path: &str,
         working_dir: Option<&str>,
         extra_flags: &str,
+        project: Option<&ProjectConfig>,
         session_id: String,
         app_handle: AppHandle,
     ) -> Result<Self, Box<dyn std::error::Error>> {
@@ -88,6 +236,18 @@ impl ClaudeProcess {
         // Remove CLAUDECODE so Claude doesn't think it's nested
         cmd.env_remove("CLAUDECODE");
 
+        if let Some(project) = project {
+            for (key, value) in &project.env {
+                cmd.env(key, value);
+            }
+        }
+        let setup_script = match project {
+            Some(project) => project.write_setup_script(&session_id)?,
+            None => None,
+        };
+        // Without a setup script, the companion `run` can start right away
+        let (setup_tx, setup_status) = watch::channel(if setup_script.is_some() { None } else { Some(0) });
+
         let child = pair.slave.spawn_command(cmd)?;
         drop(pair.slave);
 
@@ -97,22 +257,33 @@ impl ClaudeProcess {
 
         let child = Arc::new(Mutex::new(child));
         let alive = Arc::new(Mutex::new(true));
+        // Start busy: the agent is booting, and prompts queued now should
+        // wait until its UI has finished drawing.
+        let activity = Arc::new(Mutex::new(Activity {
+            busy: true,
+            last_output: Instant::now(),
+            held: setup_script.is_some(),
+        }));
+        let queue: Arc<Mutex<VecDeque<QueuedPrompt>>> = Arc::new(Mutex::new(VecDeque::new()));
+        let output = Arc::new(Mutex::new(OutputTail::new()));
 
         // Build the initial command to launch Claude inside the shell.
         // The invisible OSC sentinel fires when Claude exits, letting us
         // detect exit without PTY EOF and without any visible output.
+        let mut claude_cmd = claude_path.to_string();
         let flags_str = extra_flags.trim();
-        let launch_cmd = if flags_str.is_empty() {
-            format!(
-                "{} --dangerously-skip-permissions; printf '\\033]666;\\007'\r",
-                claude_path
-            )
-        } else {
-            format!(
-                "{} --dangerously-skip-permissions {}; printf '\\033]666;\\007'\r",
-                claude_path, flags_str
-            )
+        if !flags_str.is_empty() {
+            claude_cmd = format!("{} {}", claude_cmd, flags_str);
+        }
+        let claude_cmd = match &setup_script {
+            Some(path) => format!(
+                "sh '{}' && {}",
+                path.to_string_lossy().replace('\'', "'\\''"),
+                claude_cmd
+            ),
+            None => claude_cmd,
         };
+        let launch_cmd = format!("{}; printf '\\033]666;\\007'\r", claude_cmd);
 
         // Send the launch command to the shell after a brief delay
         // to let the shell fully initialize.
@@ -127,7 +298,68 @@ impl ClaudeProcess {
             });
         }
 
+        // Idle watcher: flips busy sessions back to idle once output goes
+        // quiet, and delivers any prompts queued while the session was busy.
+        {
+            let watcher_alive = alive.clone();
+            let watcher_activity = activity.clone();
+            let watcher_queue = queue.clone();
+            let watcher_writer = pty_writer.clone();
+            let watcher_output = output.clone();
+            let watcher_app = app_handle.clone();
+            let watcher_session_id = session_id.clone();
+            thread::spawn(move || loop {
+                thread::sleep(IDLE_POLL_INTERVAL);
+                if !watcher_alive.lock().map(|f| *f).unwrap_or(false) {
+                    break;
+                }
+                let (went_idle, idle) = match watcher_activity.lock() {
+                    Ok(mut a) if a.busy && !a.held && a.last_output.elapsed() >= IDLE_QUIET_PERIOD => {
+                        a.busy = false;
+                        (true, true)
+                    }
+                    Ok(a) => (false, !a.busy),
+                    Err(_) => (false, false),
+                };
+                if went_idle {
+                    let _ = watcher_app.emit("session-busy", serde_json::json!({
+                        "session_id": watcher_session_id,
+                        "busy": false,
+                    }));
+                }
+                if !idle {
+                    continue;
+                }
+                let next = watcher_queue.lock().ok().and_then(|mut q| q.pop_front());
+                if let Some(QueuedPrompt { text: prompt, written }) = next {
+                    let mark = write_mark(&watcher_output);
+                    match write_prompt(&watcher_writer, &watcher_activity, &prompt) {
+                        Ok(()) => {
+                            if let Some(written) = written {
+                                let _ = written.send(mark);
+                            }
+                            let _ = watcher_app.emit("session-busy", serde_json::json!({
+                                "session_id": watcher_session_id,
+                                "busy": true,
+                            }));
+                            let _ = watcher_app.emit("session-message-sent", serde_json::json!({
+                                "session_id": watcher_session_id,
+                                "message": prompt,
+                            }));
+                        }
+                        Err(e) => log::warn!(
+                            "Failed to deliver queued prompt to session {}: {}",
+                            watcher_session_id, e
+                        ),
+                    }
+                }
+            });
+        }
+
+        let reader_output = output.clone();
         let reader_alive = alive.clone();
+        let reader_activity = activity.clone();
+        let reader_queue = queue.clone();
         let reader_session_id = session_id.clone();
 
         // Background reader thread: reads PTY output, emits Tauri events,
@@ -163,15 +395,42 @@ impl ClaudeProcess {
                     Ok(n) => {
                         let text = String::from_utf8_lossy(&buf[..n]).to_string();
 
+                        if let Ok(mut a) = reader_activity.lock() {
+                            a.last_output = Instant::now();
+                        }
+                        if let Ok(mut tail) = reader_output.lock() {
+                            tail.push(&text);
+                        }
+
                         // Emit all output to the frontend for xterm.js display
                         let _ = app_handle.emit("session-output", serde_json::json!({
                             "session_id": reader_session_id,
                             "data": text,
                         }));
 
-                        // Check for sentinel — Claude has exited but shell is alive.
-                        // Accumulate text to handle sentinel split across reads.
+                        // Accumulate text to handle sequences split across reads.
+                        // A failed setup's status and the exit sentinel usually
+                        // arrive together, so take the status before clearing.
                         pending.push_str(&text);
+                        if let Some(code) = take_setup_status(&mut pending) {
+                            let result = match code {
+                                0 => Ok(()),
+                                code => Err(format!("Setup script exited with {}", code)),
+                            };
+                            if let Ok(mut a) = reader_activity.lock() {
+                                a.held = false;
+                            }
+                            if result.is_err() {
+                                // The agent never started; don't type queued
+                                // prompts into the bare shell
+                                if let Ok(mut q) = reader_queue.lock() {
+                                    q.clear();
+                                }
+                            }
+                            project::report(&app_handle, &reader_session_id, ScriptStage::Setup, &result);
+                            let _ = setup_tx.send(Some(code));
+                        }
+                        // Check for sentinel — Claude has exited but shell is alive.
                         if pending.contains(CLAUDE_EXIT_SENTINEL) {
                             let _ = app_handle.emit("claude-exited", serde_json::json!({
                                 "session_id": reader_session_id,
@@ -179,9 +438,12 @@ impl ClaudeProcess {
                             pending.clear();
                         }
                         // Keep pending buffer from growing unbounded — only keep
-                        // the tail long enough to catch a split sentinel.
-                        if pending.len() > CLAUDE_EXIT_SENTINEL.len() * 2 {
-                            let keep_from = pending.len() - CLAUDE_EXIT_SENTINEL.len();
+                        // the tail long enough to catch a split sequence.
+                        if pending.len() > PENDING_TAIL * 2 {
+                            let mut keep_from = pending.len() - PENDING_TAIL;
+                            while !pending.is_char_boundary(keep_from) {
+                                keep_from += 1;
+                            }
                             pending = pending[keep_from..].to_string();
                         }
                     }
@@ -201,22 +463,70 @@ impl ClaudeProcess {
 
         Ok(ClaudeProcess {
             pty_writer,
+            activity,
+            queue,
             _master: pair.master,
             _child: child,
             reader_handle: Some(reader_handle),
             alive,
+            setup_status,
+            shell,
+            output,
         })
     }
 
+    /// Exit code of the project setup script once it has finished; starts
+    /// out as `Some(0)` when there is none.
+    pub fn setup_status(&self) -> watch::Receiver<Option<i32>> {
+        self.setup_status.clone()
+    }
+
     /// Send text to the PTY stdin followed by Enter (carriage return).
     pub fn send(&self, input: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let mut writer = self.pty_writer.lock().map_err(|e| e.to_string())?;
-        let mut data = input.as_bytes().to_vec();
-        data.push(b'\r');
-        writer.write(&data)?;
+        write_prompt(&self.pty_writer, &self.activity, input)?;
         Ok(())
     }
 
+    /// Get a cloneable handle for prompt delivery.
+    pub fn prompt_sink(&self) -> PromptSink {
+        PromptSink {
+            writer: self.pty_writer.clone(),
+            activity: self.activity.clone(),
+            queue: self.queue.clone(),
+            output: self.output.clone(),
+        }
+    }
+
+    /// Recent output as plain text.
+    pub fn terminal_output(&self) -> TerminalOutput {
+        self.output.lock().map(|tail| tail.snapshot()).unwrap_or_default()
+    }
+
+    /// Output lines written since `completed` lines had been, ANSI-stripped.
+    pub fn output_since(&self, completed: u64) -> NewOutput {
+        self.output.lock().map(|tail| tail.lines_since(completed)).unwrap_or_default()
+    }
+
+    /// Path of the shell hosting Claude.
+    pub fn shell(&self) -> &str {
+        &self.shell
+    }
+
+    /// Pid of the shell hosting Claude.
+    pub fn pid(&self) -> Option<u32> {
+        self._child.lock().ok().and_then(|c| c.process_id())
+    }
+
+    /// Whether a delivered prompt is still being worked on.
+    pub fn is_busy(&self) -> bool {
+        self.activity.lock().map(|a| a.busy).unwrap_or(false)
+    }
+
+    /// Number of prompts waiting for the session to go idle.
+    pub fn queued_prompts(&self) -> usize {
+        self.queue.lock().map(|q| q.len()).unwrap_or(0)
+    }
+
     /// Send raw bytes (e.g. Ctrl+C, Escape).
     pub fn send_raw(&self, bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
         let mut writer = self.pty_writer.lock().map_err(|e| e.to_string())?;
@@ -224,11 +534,6 @@ impl ClaudeProcess {
         Ok(())
     }
 
-    /// Send Ctrl+C interrupt.
-    pub fn send_interrupt(&self) -> Result<(), Box<dyn std::error::Error>> {
-        self.send_raw(&[0x03])
-    }
-
     #[allow(dead_code)]
     pub fn is_alive(&self) -> bool {
         self.alive.lock().map(|f| *f).unwrap_or(false)
@@ -244,6 +549,17 @@ impl ClaudeProcess {
     }
 }
 
+/// Remove a complete setup-done sequence from `pending` and return its exit
+/// code. An incomplete one is left in place for the next read.
+fn take_setup_status(pending: &mut String) -> Option<i32> {
+    let start = pending.find(SETUP_DONE_PREFIX)?;
+    let digits_from = start + SETUP_DONE_PREFIX.len();
+    let end = digits_from + pending[digits_from..].find('\x07')?;
+    let code = pending[digits_from..end].parse().unwrap_or(-1);
+    pending.replace_range(..=end, "");
+    Some(code)
+}
+
 impl Drop for ClaudeProcess {
     fn drop(&mut self) {
         self.kill();
@@ -0,0 +1,285 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::watch;
+
+/// Project-level lifecycle config, read from the session's working dir.
+pub const PROJECT_FILE: &str = ".deckmind.yaml";
+
+/// Upper bound for a teardown script; sessions close without waiting on it.
+const TEARDOWN_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Scripts and environment for sessions started in a project.
+///
+/// ```yaml
+/// setup: npm ci && cp -n .env.example .env
+/// run: npm run dev
+/// teardown: docker compose down
+/// env:
+///   NODE_ENV: development
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProjectConfig {
+    /// Runs in the session's terminal before the agent; the agent only
+    /// launches if it succeeds.
+    #[serde(default)]
+    pub setup: Option<String>,
+    /// Long-running companion (e.g. a dev server), started once setup has
+    /// succeeded and stopped when the session closes.
+    #[serde(default)]
+    pub run: Option<String>,
+    /// Runs when the session closes.
+    #[serde(default)]
+    pub teardown: Option<String>,
+    /// Extra environment for the agent's shell and every script.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Test command, overriding the one detected from the project's
+    /// manifest.
+    #[serde(default)]
+    pub test: Option<String>,
+    /// JUnit XML file the test command writes, relative to the project
+    /// directory.
+    #[serde(default)]
+    pub test_report: Option<String>,
+}
+
+impl ProjectConfig {
+    /// `Ok(None)` when the directory has no project file.
+    pub fn load(dir: &Path) -> Result<Option<Self>, String> {
+        let path = dir.join(PROJECT_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        serde_yaml::from_str(&content)
+            .map(Some)
+            .map_err(|e| format!("Invalid {}: {}", PROJECT_FILE, e))
+    }
+
+    /// Write the setup script to a temp file that runs it, reports its exit
+    /// code as `ESC ] 667 ; <code> BEL` and deletes itself. Going through a
+    /// file keeps multi-line scripts and quoting independent of the user's
+    /// shell.
+    pub fn write_setup_script(&self, session_id: &str) -> Result<Option<PathBuf>, String> {
+        let Some(setup) = self.setup.as_deref().filter(|s| !s.trim().is_empty()) else {
+            return Ok(None);
+        };
+        let path = std::env::temp_dir().join(format!("deckmind-setup-{}.sh", session_id));
+        // The subshell keeps an `exit` in the script from skipping the report
+        let script = format!(
+            "(\n{}\n)\ns=$?\nrm -f \"$0\"\nprintf '\\033]667;%d\\007' \"$s\"\nexit \"$s\"\n",
+            setup.trim_end()
+        );
+        std::fs::write(&path, script).map_err(|e| format!("Cannot write setup script: {}", e))?;
+        Ok(Some(path))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptStage {
+    /// Loading `.deckmind.yaml` itself.
+    Config,
+    Setup,
+    Run,
+    Teardown,
+}
+
+/// Emit `project-script` with the outcome of a lifecycle stage.
+pub fn report(app: &AppHandle, session_id: &str, stage: ScriptStage, result: &Result<(), String>) {
+    if let Err(e) = result {
+        log::warn!("Project {:?} failed for session {}: {}", stage, session_id, e);
+    }
+    let _ = app.emit("project-script", serde_json::json!({
+        "session_id": session_id,
+        "stage": stage,
+        "success": result.is_ok(),
+        "error": result.as_ref().err(),
+    }));
+}
+
+/// The `run` companion process of a session.
+struct Companion {
+    /// Process group of the running script, once started.
+    pgid: Arc<Mutex<Option<u32>>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Companion {
+    fn stop(self) {
+        // Abort first so the kill is not reported as a failure
+        self.task.abort();
+        if let Some(pgid) = self.pgid.lock().ok().and_then(|p| *p) {
+            unsafe {
+                libc::kill(-(pgid as i32), libc::SIGTERM);
+            }
+        }
+    }
+}
+
+/// Lifecycle scripts attached to a live session.
+pub struct ProjectScripts {
+    config: ProjectConfig,
+    dir: PathBuf,
+    session_id: String,
+    app: AppHandle,
+    companion: Option<Companion>,
+}
+
+impl ProjectScripts {
+    /// Start the `run` companion once `setup_status` reports success
+    /// (`Some(0)`); it never starts if setup fails.
+    pub fn start(
+        config: ProjectConfig,
+        dir: PathBuf,
+        session_id: String,
+        app: AppHandle,
+        setup_status: watch::Receiver<Option<i32>>,
+    ) -> Self {
+        let companion = config.run.clone().filter(|r| !r.trim().is_empty()).map(|run| {
+            let pgid = Arc::new(Mutex::new(None));
+            let task = tokio::spawn(run_companion(
+                run,
+                dir.clone(),
+                config.env.clone(),
+                session_id.clone(),
+                app.clone(),
+                setup_status,
+                pgid.clone(),
+            ));
+            Companion { pgid, task }
+        });
+        ProjectScripts {
+            config,
+            dir,
+            session_id,
+            app,
+            companion,
+        }
+    }
+
+    /// Pid of the running `run` companion, which leads its own process
+    /// group. The slot is filled once setup has finished.
+    pub fn companion_pid(&self) -> Option<Arc<Mutex<Option<u32>>>> {
+        self.companion.as_ref().map(|c| c.pgid.clone())
+    }
+
+    /// Stop the companion and run the teardown script.
+    pub async fn shutdown(mut self) {
+        if let Some(companion) = self.companion.take() {
+            companion.stop();
+        }
+        let Some(teardown) = self.config.teardown.as_deref().filter(|t| !t.trim().is_empty()) else {
+            return;
+        };
+        let result = run_script(teardown, &self.dir, &self.config.env).await;
+        report(&self.app, &self.session_id, ScriptStage::Teardown, &result);
+    }
+}
+
+async fn run_companion(
+    run: String,
+    dir: PathBuf,
+    env: HashMap<String, String>,
+    session_id: String,
+    app: AppHandle,
+    mut setup_status: watch::Receiver<Option<i32>>,
+    pgid: Arc<Mutex<Option<u32>>>,
+) {
+    let code = loop {
+        if let Some(code) = *setup_status.borrow_and_update() {
+            break code;
+        }
+        if setup_status.changed().await.is_err() {
+            return;
+        }
+    };
+    if code != 0 {
+        return;
+    }
+
+    let spawned = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(&run)
+        .current_dir(&dir)
+        .envs(&env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Own process group, so stopping it also stops whatever it started
+        .process_group(0)
+        .kill_on_drop(true)
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            report(&app, &session_id, ScriptStage::Run, &Err(format!("Failed to start `{}`: {}", run, e)));
+            return;
+        }
+    };
+    if let Ok(mut p) = pgid.lock() {
+        *p = child.id();
+    }
+    let _ = app.emit("project-script", serde_json::json!({
+        "session_id": session_id,
+        "stage": ScriptStage::Run,
+        "started": true,
+    }));
+
+    let mut stdout = child.stdout.take().map(|s| BufReader::new(s).lines());
+    let mut stderr = child.stderr.take().map(|s| BufReader::new(s).lines());
+    loop {
+        let (stream, line) = tokio::select! {
+            Some(line) = async { stdout.as_mut()?.next_line().await.ok().flatten() } => ("stdout", line),
+            Some(line) = async { stderr.as_mut()?.next_line().await.ok().flatten() } => ("stderr", line),
+            else => break,
+        };
+        let _ = app.emit("project-output", serde_json::json!({
+            "session_id": session_id,
+            "stream": stream,
+            "line": line,
+        }));
+    }
+
+    let status = child.wait().await;
+    if let Ok(mut p) = pgid.lock() {
+        *p = None;
+    }
+    let result = match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("`{}` exited with {}", run, status)),
+        Err(e) => Err(e.to_string()),
+    };
+    report(&app, &session_id, ScriptStage::Run, &result);
+}
+
+async fn run_script(script: &str, dir: &Path, env: &HashMap<String, String>) -> Result<(), String> {
+    let child = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(script)
+        .current_dir(dir)
+        .envs(env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to start `{}`: {}", script, e))?;
+
+    match tokio::time::timeout(TEARDOWN_TIMEOUT, child.wait_with_output()).await {
+        Ok(Ok(output)) if output.status.success() => Ok(()),
+        Ok(Ok(output)) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let last = stderr.lines().last().unwrap_or("").trim();
+            Err(format!("`{}` failed ({}): {}", script, output.status, last))
+        }
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("`{}` timed out", script)),
+    }
+}
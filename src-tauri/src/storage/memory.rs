@@ -1,3 +1,4 @@
+use super::visits::{Visits, VisitSnapshot};
 use crate::actions::SemanticAction;
 use crate::config::SafetyMode;
 use chrono::{DateTime, Utc};
@@ -5,13 +6,24 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use std::io::Write;
 use std::path::PathBuf;
+use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryEntry {
+    /// Empty for entries logged before responses were captured.
+    #[serde(default)]
+    pub id: String,
     pub timestamp: DateTime<Utc>,
     pub session_id: String,
     pub action: String,
+    /// What the agent replied with, in a sentence.
     pub summary: Option<String>,
+    /// The agent's reply, trimmed to its end.
+    #[serde(default)]
+    pub response: Option<String>,
+    /// Working-tree checkpoint taken just before the action was delivered.
+    #[serde(default)]
+    pub checkpoint: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -24,6 +36,7 @@ pub struct MemoryStore {
 pub struct StorageManager {
     base_path: PathBuf,
     memory: MemoryStore,
+    visits: Visits,
     log_file: Option<fs::File>,
 }
 
@@ -43,6 +56,14 @@ impl StorageManager {
             MemoryStore::default()
         };
 
+        let visits_path = base_path.join("visits.json");
+        let visits = if visits_path.exists() {
+            let content = fs::read_to_string(&visits_path)?;
+            serde_json::from_str(&content).unwrap_or_default()
+        } else {
+            Visits::default()
+        };
+
         let log_path = base_path.join("session.log");
         let log_file = fs::OpenOptions::new()
             .create(true)
@@ -53,16 +74,36 @@ impl StorageManager {
         Ok(StorageManager {
             base_path,
             memory,
+            visits,
             log_file,
         })
     }
 
-    pub fn log_action(&mut self, session_id: &str, action: &SemanticAction, safety_mode: &SafetyMode) {
+    pub fn log_action(
+        &mut self,
+        session_id: &str,
+        action: &SemanticAction,
+        safety_mode: &SafetyMode,
+        checkpoint: Option<String>,
+    ) -> String {
+        self.record(session_id, action.label(), safety_mode, checkpoint)
+    }
+
+    /// Free-form messages are logged as "Message".
+    pub fn log_message(&mut self, session_id: &str, safety_mode: &SafetyMode, checkpoint: Option<String>) -> String {
+        self.record(session_id, "Message", safety_mode, checkpoint)
+    }
+
+    /// Log an entry; returns its id.
+    fn record(&mut self, session_id: &str, action: &str, safety_mode: &SafetyMode, checkpoint: Option<String>) -> String {
         let entry = MemoryEntry {
+            id: Uuid::new_v4().to_string(),
             timestamp: Utc::now(),
             session_id: session_id.to_string(),
-            action: action.label().to_string(),
+            action: action.to_string(),
             summary: None,
+            response: None,
+            checkpoint,
         };
 
         self.memory.entries.push(entry.clone());
@@ -77,13 +118,23 @@ impl StorageManager {
         if let Some(ref mut log) = self.log_file {
             let _ = writeln!(
                 log,
-                "[{}] session={} action={} mode={:?}",
+                "[{}] session={} action={} mode={:?} checkpoint={}",
                 entry.timestamp.to_rfc3339(),
                 session_id,
-                action.label(),
+                action,
                 safety_mode,
+                entry.checkpoint.as_deref().unwrap_or("-"),
             );
         }
+        entry.id
+    }
+
+    /// Attach the agent's reply to the entry `entry_id`.
+    pub fn set_response(&mut self, entry_id: &str, response: String, summary: Option<String>) {
+        let Some(entry) = self.memory.entries.iter_mut().find(|e| e.id == entry_id) else { return };
+        entry.response = Some(response);
+        entry.summary = summary;
+        let _ = self.save_memory();
     }
 
     fn save_memory(&self) -> Result<(), Box<dyn std::error::Error>> {
@@ -101,4 +152,38 @@ impl StorageManager {
             .take(count)
             .collect()
     }
+
+    /// Every entry for a session, oldest first.
+    pub fn get_session_entries(&self, session_id: &str) -> Vec<&MemoryEntry> {
+        self.memory
+            .entries
+            .iter()
+            .filter(|e| e.session_id == session_id)
+            .collect()
+    }
+
+    /// Remember what `project` looked like when the user viewed it.
+    pub fn record_visit(&mut self, project: &str, snapshot: VisitSnapshot) {
+        self.visits.entry(project.to_string()).or_default().record(snapshot);
+        let _ = self.save_visits();
+    }
+
+    /// The project as the user last saw it before the current visit.
+    pub fn last_visit(&self, project: &str) -> Option<&VisitSnapshot> {
+        self.visits.get(project)?.last_visit(Utc::now())
+    }
+
+    /// Projects the user has viewed, most recently viewed first.
+    pub fn recent_projects(&self) -> Vec<&str> {
+        let mut projects: Vec<_> = self.visits.iter().filter_map(|(p, v)| Some((p.as_str(), v.latest_at()?))).collect();
+        projects.sort_by(|a, b| b.1.cmp(&a.1));
+        projects.into_iter().map(|(p, _)| p).collect()
+    }
+
+    fn save_visits(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = self.base_path.join("visits.json");
+        let json = serde_json::to_string_pretty(&self.visits)?;
+        fs::write(&path, json)?;
+        Ok(())
+    }
 }
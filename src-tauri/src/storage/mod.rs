@@ -1,3 +1,5 @@
 mod memory;
+mod visits;
 
-pub use memory::StorageManager;
+pub use memory::{MemoryEntry, StorageManager};
+pub use visits::{SessionMark, VisitSnapshot};
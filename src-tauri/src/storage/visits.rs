@@ -0,0 +1,63 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Coming back after this long (in minutes) starts a new visit; views
+/// closer together belong to the same one.
+const NEW_VISIT_AFTER_MINS: i64 = 10;
+
+/// What a project looked like when the user viewed one of its sessions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VisitSnapshot {
+    pub taken_at: DateTime<Utc>,
+    /// Full hash of HEAD.
+    pub head: Option<String>,
+    /// Changed files as `git status --short` shows them.
+    pub changes: Vec<String>,
+    /// Recent shell commands, oldest first.
+    pub commands: Vec<String>,
+    /// Sessions open in the project.
+    pub sessions: Vec<SessionMark>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionMark {
+    pub id: String,
+    pub name: String,
+}
+
+/// The latest snapshot of a project and the one from the visit before.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectVisits {
+    latest: Option<VisitSnapshot>,
+    previous: Option<VisitSnapshot>,
+}
+
+impl ProjectVisits {
+    /// Replace the latest snapshot. If it's from an earlier visit, it
+    /// becomes the one later changes are measured against.
+    pub fn record(&mut self, snapshot: VisitSnapshot) {
+        if let Some(latest) = self.latest.take() {
+            if snapshot.taken_at - latest.taken_at >= Duration::minutes(NEW_VISIT_AFTER_MINS) {
+                self.previous = Some(latest);
+            }
+        }
+        self.latest = Some(snapshot);
+    }
+
+    /// When the project was last viewed.
+    pub fn latest_at(&self) -> Option<DateTime<Utc>> {
+        self.latest.as_ref().map(|s| s.taken_at)
+    }
+
+    /// The snapshot from the user's last visit before the current one.
+    pub fn last_visit(&self, now: DateTime<Utc>) -> Option<&VisitSnapshot> {
+        match &self.latest {
+            // Not viewed since coming back: the latest is the last visit
+            Some(latest) if now - latest.taken_at >= Duration::minutes(NEW_VISIT_AFTER_MINS) => Some(latest),
+            _ => self.previous.as_ref(),
+        }
+    }
+}
+
+pub type Visits = HashMap<String, ProjectVisits>;
@@ -0,0 +1,341 @@
+//! Running a project's tests in a session's directory.
+
+pub mod parse;
+
+use crate::text::tail;
+use chrono::{DateTime, Utc};
+use parse::{TestFailure, TestFormat};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tauri::{AppHandle, Emitter};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::{mpsc, oneshot};
+
+/// Upper bound for a test run unless the caller sets one.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Output kept for display after the run.
+const OUTPUT_TAIL: usize = 4000;
+
+/// Output kept for parsing; the oldest is dropped beyond this.
+const MAX_OUTPUT: usize = 4 * 1024 * 1024;
+
+/// How long to keep reading output after the command exits, for lines
+/// still in the pipes.
+const DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Minimum gap between `test-progress` events that only carry a new line.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestStatus {
+    Running,
+    Passed,
+    Failed,
+    TimedOut,
+    Cancelled,
+    /// The command could not be started.
+    Error,
+}
+
+/// Latest run for a session.
+#[derive(Debug, Clone, Serialize)]
+pub struct TestReport {
+    pub session_id: String,
+    pub command: String,
+    pub status: TestStatus,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub exit_code: Option<i32>,
+    /// Format the results were parsed from; `None` if none was recognised.
+    pub format: Option<TestFormat>,
+    pub passed: usize,
+    pub failed: usize,
+    pub ignored: usize,
+    pub failures: Vec<TestFailure>,
+    pub output_tail: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct TestProgress {
+    session_id: String,
+    passed: usize,
+    failed: usize,
+    ignored: usize,
+    /// Most recent output line.
+    line: String,
+}
+
+/// What to run.
+pub struct TestRun {
+    pub dir: PathBuf,
+    pub command: String,
+    /// JUnit XML file the command writes, read once it exits.
+    pub report_file: Option<PathBuf>,
+    pub timeout: Duration,
+}
+
+/// Runs tests per session and keeps each session's latest report.
+pub struct TestRunner {
+    running: Mutex<HashMap<String, oneshot::Sender<()>>>,
+    reports: Mutex<HashMap<String, TestReport>>,
+}
+
+impl TestRunner {
+    pub fn new() -> Self {
+        TestRunner {
+            running: Mutex::new(HashMap::new()),
+            reports: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Start a run in the background. Progress is emitted as
+    /// `test-progress` events and the final report as `test-finished`.
+    pub fn start(self: &Arc<Self>, app: AppHandle, session_id: &str, run: TestRun) -> Result<TestReport, String> {
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+        {
+            let mut running = self.running.lock().map_err(|e| e.to_string())?;
+            if running.contains_key(session_id) {
+                return Err("Tests are already running for this session".to_string());
+            }
+            running.insert(session_id.to_string(), cancel_tx);
+        }
+
+        let report = TestReport {
+            session_id: session_id.to_string(),
+            command: run.command.clone(),
+            status: TestStatus::Running,
+            started_at: Utc::now(),
+            finished_at: None,
+            exit_code: None,
+            format: None,
+            passed: 0,
+            failed: 0,
+            ignored: 0,
+            failures: Vec::new(),
+            output_tail: String::new(),
+        };
+        self.store(report.clone());
+
+        let runner = self.clone();
+        let initial = report.clone();
+        tokio::spawn(async move {
+            let report = execute(&app, &runner, run, initial, cancel_rx).await;
+            if let Ok(mut running) = runner.running.lock() {
+                running.remove(&report.session_id);
+            }
+            runner.store(report.clone());
+            let _ = app.emit("test-finished", &report);
+        });
+
+        Ok(report)
+    }
+
+    /// Stop a session's run. `false` if nothing was running.
+    pub fn cancel(&self, session_id: &str) -> bool {
+        let sender = self.running.lock().ok().and_then(|mut r| r.remove(session_id));
+        match sender {
+            Some(sender) => sender.send(()).is_ok(),
+            None => false,
+        }
+    }
+
+    pub fn latest(&self, session_id: &str) -> Option<TestReport> {
+        self.reports.lock().ok()?.get(session_id).cloned()
+    }
+
+    /// Cancel any run and drop the session's report.
+    pub fn forget(&self, session_id: &str) {
+        self.cancel(session_id);
+        if let Ok(mut reports) = self.reports.lock() {
+            reports.remove(session_id);
+        }
+    }
+
+    fn store(&self, report: TestReport) {
+        if let Ok(mut reports) = self.reports.lock() {
+            reports.insert(report.session_id.clone(), report);
+        }
+    }
+}
+
+async fn execute(
+    app: &AppHandle,
+    runner: &TestRunner,
+    run: TestRun,
+    mut report: TestReport,
+    mut cancel: oneshot::Receiver<()>,
+) -> TestReport {
+    let started = SystemTime::now();
+    let child = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(&run.command)
+        .current_dir(&run.dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Own process group so the whole tree can be stopped
+        .process_group(0)
+        .kill_on_drop(true)
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            report.status = TestStatus::Error;
+            report.output_tail = format!("Failed to start: {}", e);
+            report.finished_at = Some(Utc::now());
+            return report;
+        }
+    };
+
+    let (tx, mut lines) = mpsc::unbounded_channel();
+    let mut readers = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        readers.push(tokio::spawn(forward_lines(stdout, tx.clone())));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        readers.push(tokio::spawn(forward_lines(stderr, tx)));
+    }
+
+    let deadline = tokio::time::sleep(run.timeout);
+    tokio::pin!(deadline);
+    let mut output = String::new();
+    let mut last_emit = Instant::now();
+    let mut absorb = |line: String| {
+        let (passed, failed, ignored) = parse::progress(&line);
+        report.passed += passed;
+        report.failed += failed;
+        report.ignored += ignored;
+        let counted = passed + failed + ignored > 0;
+        if counted {
+            runner.store(report.clone());
+        }
+        if counted || last_emit.elapsed() >= PROGRESS_INTERVAL {
+            last_emit = Instant::now();
+            let _ = app.emit("test-progress", TestProgress {
+                session_id: report.session_id.clone(),
+                passed: report.passed,
+                failed: report.failed,
+                ignored: report.ignored,
+                line: parse::strip_ansi(&line),
+            });
+        }
+        output.push_str(&line);
+        output.push('\n');
+        if output.len() > MAX_OUTPUT {
+            let mut cut = output.len() - MAX_OUTPUT;
+            while !output.is_char_boundary(cut) {
+                cut += 1;
+            }
+            output.drain(..cut);
+        }
+    };
+
+    // Ends when the command exits, not when its pipes close: a daemon it
+    // started in the background may hold them open indefinitely.
+    let mut stopped = None;
+    let mut exited = None;
+    loop {
+        tokio::select! {
+            Some(line) = lines.recv() => absorb(line),
+            status = child.wait() => {
+                exited = Some(status);
+                break;
+            }
+            _ = &mut cancel => {
+                stopped = Some(TestStatus::Cancelled);
+                break;
+            }
+            _ = &mut deadline => {
+                stopped = Some(TestStatus::TimedOut);
+                break;
+            }
+        }
+    }
+
+    if exited.is_some() {
+        // Whatever the command wrote before exiting is still in the pipes
+        let drain = tokio::time::sleep(DRAIN_TIMEOUT);
+        tokio::pin!(drain);
+        loop {
+            tokio::select! {
+                line = lines.recv() => match line {
+                    Some(line) => absorb(line),
+                    None => break,
+                },
+                _ = &mut drain => break,
+            }
+        }
+    }
+    for reader in readers {
+        reader.abort();
+    }
+
+    if stopped.is_some() {
+        if let Some(pid) = child.id() {
+            unsafe {
+                libc::kill(-(pid as i32), libc::SIGTERM);
+            }
+        }
+        let _ = child.kill().await;
+    }
+    let status = match exited {
+        Some(status) => status,
+        None => child.wait().await,
+    };
+    let exit_code = match status {
+        Ok(status) => status.code(),
+        Err(_) => None,
+    };
+
+    let xml = run
+        .report_file
+        .as_ref()
+        .filter(|path| {
+            // Ignore a report left over from an earlier run
+            std::fs::metadata(path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified >= started)
+        })
+        .and_then(|path| std::fs::read_to_string(path).ok());
+    let parsed = parse::parse(&output, xml.as_deref());
+
+    report.exit_code = exit_code;
+    report.format = parsed.format;
+    if parsed.format.is_some() {
+        report.passed = parsed.passed;
+        report.failed = parsed.failed;
+        report.ignored = parsed.ignored;
+    }
+    report.failures = parsed.failures;
+    report.status = match stopped {
+        Some(status) => status,
+        None if exit_code == Some(0) && report.failed == 0 => TestStatus::Passed,
+        None => TestStatus::Failed,
+    };
+    report.output_tail = tail(&parse::strip_ansi(&output), OUTPUT_TAIL);
+    report.finished_at = Some(Utc::now());
+    report
+}
+
+async fn forward_lines(stream: impl AsyncRead + Unpin, tx: mpsc::UnboundedSender<String>) {
+    let mut reader = BufReader::new(stream);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                let line = String::from_utf8_lossy(&buf);
+                if tx.send(line.trim_end_matches(['\n', '\r']).to_string()).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
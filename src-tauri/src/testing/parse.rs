@@ -0,0 +1,587 @@
+//! Parsers turning test runner output into pass/fail counts and failures.
+
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Longest failure message kept.
+const MAX_MESSAGE_CHARS: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestFailure {
+    pub name: String,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+/// Output format the results were read from.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestFormat {
+    CargoTest,
+    LibtestJson,
+    Junit,
+    Jest,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ParsedResults {
+    pub format: Option<TestFormat>,
+    pub passed: usize,
+    pub failed: usize,
+    pub ignored: usize,
+    pub failures: Vec<TestFailure>,
+}
+
+/// Parse a finished run. `report` is a JUnit XML file the command wrote,
+/// if one is configured; otherwise the format is recognised from the
+/// output.
+pub fn parse(output: &str, report: Option<&str>) -> ParsedResults {
+    let output = strip_ansi(output);
+    if let Some(xml) = report {
+        return junit(xml);
+    }
+    if output.lines().any(|l| libtest_event(l).is_some()) {
+        return libtest_json(&output);
+    }
+    if let Some(results) = jest(&output) {
+        return results;
+    }
+    if output.contains("<testsuite") {
+        if let Some(start) = output.find("<?xml").or_else(|| output.find("<testsuite")) {
+            return junit(&output[start..]);
+        }
+    }
+    cargo_test(&output)
+}
+
+/// Counts change a single output line implies, for live progress:
+/// `(passed, failed, ignored)`.
+pub fn progress(line: &str) -> (usize, usize, usize) {
+    if let Some(event) = libtest_event(line) {
+        return match (event["type"].as_str(), event["event"].as_str()) {
+            (Some("test"), Some("ok")) => (1, 0, 0),
+            (Some("test"), Some("failed" | "timeout")) => (0, 1, 0),
+            (Some("test"), Some("ignored")) => (0, 0, 1),
+            _ => (0, 0, 0),
+        };
+    }
+    let line = line.trim();
+    if !line.starts_with("test ") {
+        return (0, 0, 0);
+    }
+    if line.ends_with(" ... ok") {
+        (1, 0, 0)
+    } else if line.ends_with(" ... FAILED") {
+        (0, 1, 0)
+    } else if line.ends_with(" ... ignored") || line.contains(" ... ignored, ") {
+        (0, 0, 1)
+    } else {
+        (0, 0, 0)
+    }
+}
+
+/// Plain `cargo test` output. Compiler errors are reported as failures
+/// when the build fails before any test runs.
+fn cargo_test(output: &str) -> ParsedResults {
+    let mut results = ParsedResults::default();
+    let mut failed_names = Vec::new();
+
+    for line in output.lines() {
+        let (passed, failed, ignored) = progress(line);
+        results.passed += passed;
+        results.ignored += ignored;
+        if failed > 0 {
+            let name = line.trim().trim_start_matches("test ").trim_end_matches(" ... FAILED");
+            failed_names.push(name.to_string());
+        }
+    }
+
+    // ---- name stdout ---- blocks hold each failure's output
+    let mut blocks: Vec<(String, String)> = Vec::new();
+    let mut collecting = false;
+    for line in output.lines() {
+        if let Some(name) = line.strip_prefix("---- ").and_then(|l| l.strip_suffix(" stdout ----")) {
+            blocks.push((name.to_string(), String::new()));
+            collecting = true;
+        } else if line.starts_with("failures:") || line.starts_with("test result:") {
+            collecting = false;
+        } else if let (true, Some((_, text))) = (collecting, blocks.last_mut()) {
+            text.push_str(line);
+            text.push('\n');
+        }
+    }
+
+    for name in failed_names {
+        let text = blocks
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, t)| t.as_str())
+            .unwrap_or_default();
+        results.failures.push(panic_failure(&name, text));
+    }
+
+    if results.passed + results.failures.len() + results.ignored > 0 {
+        results.format = Some(TestFormat::CargoTest);
+    } else {
+        results.failures = compile_errors(output);
+        if !results.failures.is_empty() {
+            results.format = Some(TestFormat::CargoTest);
+        }
+    }
+    results.failed = results.failures.len();
+    results
+}
+
+/// Failure from a test's captured output, located by its panic message.
+fn panic_failure(name: &str, output: &str) -> TestFailure {
+    static PANIC: OnceLock<Regex> = OnceLock::new();
+    // thread 'x' panicked at src/lib.rs:10:5:          (Rust 1.73+)
+    // thread 'x' panicked at 'msg', src/lib.rs:10:5    (older)
+    let panic = PANIC.get_or_init(|| {
+        Regex::new(r"panicked at (?:'(?P<old>.*)', )?(?P<file>[^\s:]+):(?P<line>\d+):\d+:?").expect("panic pattern")
+    });
+
+    let mut failure = TestFailure {
+        name: name.to_string(),
+        file: None,
+        line: None,
+        message: clip(output.trim()),
+    };
+    if let Some(caps) = panic.captures(output) {
+        failure.file = Some(caps["file"].to_string());
+        failure.line = caps["line"].parse().ok();
+        let message = match caps.name("old") {
+            Some(old) => old.as_str().to_string(),
+            None => {
+                let after = &output[caps.get(0).expect("match").end()..];
+                after
+                    .lines()
+                    .skip_while(|l| l.trim().is_empty())
+                    .take_while(|l| !l.starts_with("note: "))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        };
+        if !message.trim().is_empty() {
+            failure.message = clip(message.trim());
+        }
+    }
+    failure
+}
+
+/// `error[E0308]: mismatched types` followed by `--> src/main.rs:3:5`.
+fn compile_errors(output: &str) -> Vec<TestFailure> {
+    let mut failures = Vec::new();
+    let mut lines = output.lines().peekable();
+    while let Some(line) = lines.next() {
+        let Some(message) = line.strip_prefix("error").and_then(|rest| {
+            let rest = match rest.strip_prefix('[') {
+                Some(code) => code.split_once(']')?.1,
+                None => rest,
+            };
+            rest.strip_prefix(": ")
+        }) else {
+            continue;
+        };
+        if message.starts_with("could not compile") || message.starts_with("aborting due to") {
+            continue;
+        }
+        let mut failure = TestFailure {
+            name: "build".to_string(),
+            file: None,
+            line: None,
+            message: clip(message),
+        };
+        if let Some(location) = lines.peek().and_then(|l| l.trim().strip_prefix("--> ")) {
+            let mut parts = location.split(':');
+            failure.file = parts.next().map(str::to_string);
+            failure.line = parts.next().and_then(|l| l.parse().ok());
+        }
+        failures.push(failure);
+    }
+    failures
+}
+
+fn libtest_event(line: &str) -> Option<serde_json::Value> {
+    let line = line.trim();
+    if !line.starts_with('{') || !line.contains("\"type\"") {
+        return None;
+    }
+    let event: serde_json::Value = serde_json::from_str(line).ok()?;
+    matches!(event["type"].as_str(), Some("test" | "suite")).then_some(event)
+}
+
+/// `--format json` output from libtest (or nextest's libtest-json).
+fn libtest_json(output: &str) -> ParsedResults {
+    let mut results = ParsedResults {
+        format: Some(TestFormat::LibtestJson),
+        ..Default::default()
+    };
+    for event in output.lines().filter_map(libtest_event) {
+        if event["type"] != "test" {
+            continue;
+        }
+        match event["event"].as_str() {
+            Some("ok") => results.passed += 1,
+            Some("ignored") => results.ignored += 1,
+            Some("failed" | "timeout") => {
+                let name = event["name"].as_str().unwrap_or("unknown");
+                let output = event["stdout"].as_str().or(event["message"].as_str()).unwrap_or("");
+                results.failures.push(panic_failure(name, output));
+            }
+            _ => {}
+        }
+    }
+    results.failed = results.failures.len();
+    results
+}
+
+/// JSON written by `jest --json` or `vitest --reporter=json`, possibly
+/// surrounded by other output.
+fn jest(output: &str) -> Option<ParsedResults> {
+    let report = output
+        .match_indices('{')
+        .filter_map(|(i, _)| {
+            serde_json::Deserializer::from_str(&output[i..])
+                .into_iter::<serde_json::Value>()
+                .next()?
+                .ok()
+        })
+        .find(|v| v.get("testResults").is_some())?;
+
+    let mut results = ParsedResults {
+        format: Some(TestFormat::Jest),
+        passed: report["numPassedTests"].as_u64().unwrap_or(0) as usize,
+        ignored: (report["numPendingTests"].as_u64().unwrap_or(0) + report["numTodoTests"].as_u64().unwrap_or(0))
+            as usize,
+        ..Default::default()
+    };
+
+    for file in report["testResults"].as_array().into_iter().flatten() {
+        let path = file["name"].as_str().map(str::to_string);
+        let assertions = file["assertionResults"].as_array().cloned().unwrap_or_default();
+        for assertion in assertions.iter().filter(|a| a["status"] == "failed") {
+            let message = assertion["failureMessages"]
+                .as_array()
+                .and_then(|m| m.first())
+                .and_then(|m| m.as_str())
+                .unwrap_or("");
+            let line = assertion["location"]["line"]
+                .as_u64()
+                .map(|l| l as u32)
+                .or_else(|| path.as_deref().and_then(|p| stack_line(message, p)));
+            results.failures.push(TestFailure {
+                name: assertion["fullName"]
+                    .as_str()
+                    .or(assertion["title"].as_str())
+                    .unwrap_or("unknown")
+                    .to_string(),
+                file: path.clone(),
+                line,
+                message: clip(first_paragraph(message)),
+            });
+        }
+        // A file that failed to load has no assertions, only a message
+        if assertions.is_empty() && file["status"] == "failed" {
+            results.failures.push(TestFailure {
+                name: path.clone().unwrap_or_else(|| "test file".to_string()),
+                file: path.clone(),
+                line: None,
+                message: clip(first_paragraph(file["message"].as_str().unwrap_or(""))),
+            });
+        }
+    }
+    results.failed = results.failures.len();
+    Some(results)
+}
+
+/// Line of `file` in a JS stack trace (`at fn (/abs/path/file.test.ts:12:5)`).
+fn stack_line(message: &str, file: &str) -> Option<u32> {
+    message.lines().find_map(|line| {
+        let rest = &line[line.find(file)? + file.len()..];
+        rest.strip_prefix(':')?.split(':').next()?.parse().ok()
+    })
+}
+
+fn first_paragraph(message: &str) -> &str {
+    message.trim().split("\n\n").next().unwrap_or("").trim()
+}
+
+/// JUnit XML, as written by pytest, gradle, go-junit-report and most CI
+/// reporters.
+fn junit(xml: &str) -> ParsedResults {
+    #[derive(PartialEq)]
+    enum Outcome {
+        Passed,
+        Failed,
+        Skipped,
+    }
+
+    let mut results = ParsedResults {
+        format: Some(TestFormat::Junit),
+        ..Default::default()
+    };
+    let mut reader = Reader::from_str(xml);
+    let mut case: Option<(TestFailure, Outcome)> = None;
+    // Body of the current <failure>/<error>
+    let mut body: Option<String> = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => match e.name().as_ref() {
+                b"testcase" => case = Some((testcase(&e), Outcome::Passed)),
+                b"failure" | b"error" => {
+                    if let Some((failure, outcome)) = case.as_mut() {
+                        *outcome = Outcome::Failed;
+                        failure.message = attr(&e, "message").unwrap_or_default();
+                        body = Some(String::new());
+                    }
+                }
+                b"skipped" => {
+                    if let Some((_, outcome)) = case.as_mut() {
+                        *outcome = Outcome::Skipped;
+                    }
+                }
+                _ => {}
+            },
+            Ok(Event::Empty(e)) => match e.name().as_ref() {
+                b"testcase" => results.passed += 1,
+                b"failure" | b"error" => {
+                    if let Some((failure, outcome)) = case.as_mut() {
+                        *outcome = Outcome::Failed;
+                        failure.message = attr(&e, "message").unwrap_or_default();
+                    }
+                }
+                b"skipped" => {
+                    if let Some((_, outcome)) = case.as_mut() {
+                        *outcome = Outcome::Skipped;
+                    }
+                }
+                _ => {}
+            },
+            Ok(Event::Text(t)) => {
+                if let (Some(body), Ok(text)) = (body.as_mut(), t.xml_content()) {
+                    body.push_str(&text);
+                }
+            }
+            Ok(Event::CData(t)) => {
+                if let (Some(body), Ok(text)) = (body.as_mut(), t.xml_content()) {
+                    body.push_str(&text);
+                }
+            }
+            Ok(Event::GeneralRef(r)) => {
+                if let Some(body) = body.as_mut() {
+                    if let Ok(Some(ch)) = r.resolve_char_ref() {
+                        body.push(ch);
+                    } else if let Some(text) = r.decode().ok().and_then(|n| resolve_predefined_entity(&n)) {
+                        body.push_str(text);
+                    }
+                }
+            }
+            Ok(Event::End(e)) => match e.name().as_ref() {
+                b"failure" | b"error" => {
+                    if let (Some((failure, _)), Some(text)) = (case.as_mut(), body.take()) {
+                        junit_details(failure, text.trim());
+                    }
+                }
+                b"testcase" => match case.take() {
+                    Some((failure, Outcome::Failed)) => results.failures.push(failure),
+                    Some((_, Outcome::Skipped)) => results.ignored += 1,
+                    Some((_, Outcome::Passed)) => results.passed += 1,
+                    None => {}
+                },
+                _ => {}
+            },
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    results.failed = results.failures.len();
+    results
+}
+
+fn testcase(e: &BytesStart) -> TestFailure {
+    TestFailure {
+        name: testcase_name(e),
+        file: attr(e, "file"),
+        line: attr(e, "line").and_then(|l| l.parse().ok()),
+        message: String::new(),
+    }
+}
+
+/// Fill in the message and location of a JUnit failure from its body
+/// (usually a traceback ending in `path/to/test.py:12: AssertionError`).
+fn junit_details(failure: &mut TestFailure, body: &str) {
+    static LOCATION: OnceLock<Regex> = OnceLock::new();
+    let location = LOCATION.get_or_init(|| Regex::new(r"([\w./-]+\.\w+):(\d+)").expect("location pattern"));
+
+    if failure.message.is_empty() {
+        failure.message = clip(first_paragraph(body));
+    } else {
+        failure.message = clip(&failure.message);
+    }
+    if failure.line.is_none() {
+        let file = failure.file.clone();
+        let found = location
+            .captures_iter(body)
+            .filter(|caps| match file.as_deref() {
+                Some(f) => caps[1].ends_with(f) || f.ends_with(&caps[1]),
+                None => true,
+            })
+            .last();
+        if let Some(caps) = found {
+            failure.file.get_or_insert_with(|| caps[1].to_string());
+            failure.line = caps[2].parse().ok();
+        }
+    }
+}
+
+fn testcase_name(e: &BytesStart) -> String {
+    let name = attr(e, "name").unwrap_or_default();
+    match attr(e, "classname") {
+        Some(class) if !class.is_empty() => format!("{}::{}", class, name),
+        _ => name,
+    }
+}
+
+fn attr(e: &BytesStart, name: &str) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == name.as_bytes())
+        .and_then(|a| a.unescape_value().ok().map(|v| v.into_owned()))
+}
+
+/// Remove ANSI escape sequences (colours, cursor movement).
+pub fn strip_ansi(text: &str) -> String {
+    static ANSI: OnceLock<Regex> = OnceLock::new();
+    let ansi = ANSI.get_or_init(|| Regex::new(r"\x1b(?:\[[0-9;?]*[ -/]*[@-~]|\][^\x07\x1b]*(?:\x07|\x1b\\))").expect("ansi pattern"));
+    ansi.replace_all(text, "").into_owned()
+}
+
+fn clip(text: &str) -> String {
+    if text.chars().count() <= MAX_MESSAGE_CHARS {
+        return text.to_string();
+    }
+    let mut clipped: String = text.chars().take(MAX_MESSAGE_CHARS).collect();
+    clipped.push('…');
+    clipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cargo_test_counts_and_locates_panics() {
+        let output = "\
+running 3 tests
+test add ... ok
+test slow ... ignored
+test sub ... FAILED
+
+failures:
+
+---- sub stdout ----
+
+thread 'sub' panicked at src/lib.rs:12:5:
+assertion `left == right` failed
+  left: 1
+ right: 2
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+failures:
+    sub
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out
+";
+        let results = parse(output, None);
+        assert_eq!(results.format, Some(TestFormat::CargoTest));
+        assert_eq!((results.passed, results.failed, results.ignored), (1, 1, 1));
+        let failure = &results.failures[0];
+        assert_eq!(failure.name, "sub");
+        assert_eq!(failure.file.as_deref(), Some("src/lib.rs"));
+        assert_eq!(failure.line, Some(12));
+        assert!(failure.message.starts_with("assertion `left == right` failed"));
+        assert!(!failure.message.contains("RUST_BACKTRACE"));
+    }
+
+    #[test]
+    fn cargo_test_reports_compile_errors() {
+        let output = "\
+error[E0308]: mismatched types
+ --> src/main.rs:3:18
+  |
+error: could not compile `demo` (bin \"demo\" test) due to 1 previous error
+";
+        let results = parse(output, None);
+        assert_eq!(results.failed, 1);
+        let failure = &results.failures[0];
+        assert_eq!(failure.name, "build");
+        assert_eq!(failure.message, "mismatched types");
+        assert_eq!((failure.file.as_deref(), failure.line), (Some("src/main.rs"), Some(3)));
+    }
+
+    #[test]
+    fn libtest_json_events() {
+        let output = r#"{ "type": "suite", "event": "started", "test_count": 3 }
+{ "type": "test", "event": "ok", "name": "a" }
+{ "type": "test", "event": "ignored", "name": "b" }
+{ "type": "test", "event": "failed", "name": "c", "stdout": "thread 'c' panicked at src/c.rs:4:9:\nboom\n" }
+{ "type": "suite", "event": "failed", "passed": 1, "failed": 1 }"#;
+        assert_eq!(progress(output.lines().nth(1).unwrap()), (1, 0, 0));
+        let results = parse(output, None);
+        assert_eq!(results.format, Some(TestFormat::LibtestJson));
+        assert_eq!((results.passed, results.failed, results.ignored), (1, 1, 1));
+        assert_eq!(results.failures[0].message, "boom");
+        assert_eq!(results.failures[0].line, Some(4));
+    }
+
+    #[test]
+    fn junit_report() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<testsuites><testsuite name="pytest" tests="3">
+  <testcase classname="tests.test_math" name="test_add" file="tests/test_math.py" line="3"/>
+  <testcase classname="tests.test_math" name="test_div" file="tests/test_math.py">
+    <failure message="ZeroDivisionError: division by zero">def test_div():
+&gt;       1 / 0
+
+tests/test_math.py:9: ZeroDivisionError</failure>
+  </testcase>
+  <testcase classname="tests.test_math" name="test_skip"><skipped message="later"/></testcase>
+</testsuite></testsuites>"#;
+        let results = parse("", Some(xml));
+        assert_eq!(results.format, Some(TestFormat::Junit));
+        assert_eq!((results.passed, results.failed, results.ignored), (1, 1, 1));
+        let failure = &results.failures[0];
+        assert_eq!(failure.name, "tests.test_math::test_div");
+        assert_eq!(failure.message, "ZeroDivisionError: division by zero");
+        assert_eq!(failure.line, Some(9));
+    }
+
+    #[test]
+    fn jest_json_among_other_output() {
+        let output = r#"> demo@1.0.0 test
+{"numPassedTests":2,"numPendingTests":1,"numTodoTests":0,"testResults":[{"name":"/repo/src/sum.test.ts","status":"failed","assertionResults":[
+{"fullName":"sum adds","status":"passed","failureMessages":[]},
+{"fullName":"sum carries","status":"failed","failureMessages":["Error: expected 3\n\n    at Object.<anonymous> (/repo/src/sum.test.ts:14:7)"]}]}]}
+Done in 1.2s"#;
+        let results = parse(output, None);
+        assert_eq!(results.format, Some(TestFormat::Jest));
+        assert_eq!((results.passed, results.failed, results.ignored), (2, 1, 1));
+        let failure = &results.failures[0];
+        assert_eq!(failure.name, "sum carries");
+        assert_eq!(failure.message, "Error: expected 3");
+        assert_eq!(failure.line, Some(14));
+    }
+
+    #[test]
+    fn strips_colours_and_titles() {
+        assert_eq!(strip_ansi("\x1b[32mok\x1b[0m \x1b]0;title\x07done"), "ok done");
+    }
+}
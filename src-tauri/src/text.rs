@@ -0,0 +1,14 @@
+//! Small text helpers shared by the places that keep command output.
+
+/// The last `max` bytes of `text` (moved forward to a character
+/// boundary), starting with `…` when anything was cut.
+pub fn tail(text: &str, max: usize) -> String {
+    if text.len() <= max {
+        return text.to_string();
+    }
+    let mut start = text.len() - max;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("…{}", &text[start..])
+}
@@ -16,11 +16,11 @@ import { useSession } from './hooks/useSession'
 import { useKeyboard } from './hooks/useKeyboard'
 import { useGamepad } from './hooks/useGamepad'
 import { useAppStore } from './stores/appStore'
-import type { AppConfig, DownloadProgress } from './types'
+import type { ActionTemplate, AppConfig, DownloadProgress } from './types'
 
 function App() {
   const { setupListeners } = useSession()
-  const { setConfig } = useAppStore()
+  const { setConfig, setActionTemplates } = useAppStore()
 
   useKeyboard()
   useGamepad()
@@ -32,7 +32,11 @@ function App() {
     invoke<AppConfig>('get_config')
       .then((config) => setConfig(config))
       .catch((e) => console.error('Failed to load config:', e))
-  }, [setupListeners, setConfig])
+
+    invoke<ActionTemplate[]>('list_actions')
+      .then((templates) => setActionTemplates(templates))
+      .catch((e) => console.error('Failed to load actions:', e))
+  }, [setupListeners, setConfig, setActionTemplates])
 
   // Global download progress listener (works even when model manager is closed)
   useEffect(() => {
@@ -1,9 +1,26 @@
+import { useEffect } from 'react'
+import { invoke } from '@tauri-apps/api/core'
 import { useAppStore } from '../stores/appStore'
 import { TerminalPane } from './TerminalPane'
 
 export function SessionView() {
   const { activeSessionId } = useAppStore()
 
+  // Snapshot the project each time the session comes into view, so the
+  // Context action can say what changed since
+  useEffect(() => {
+    if (!activeSessionId) return
+    const recordVisit = () => {
+      if (document.visibilityState !== 'visible') return
+      invoke('record_visit', { sessionId: activeSessionId }).catch((e) =>
+        console.error('Failed to record visit:', e)
+      )
+    }
+    recordVisit()
+    document.addEventListener('visibilitychange', recordVisit)
+    return () => document.removeEventListener('visibilitychange', recordVisit)
+  }, [activeSessionId])
+
   if (!activeSessionId) {
     return (
       <div className="main-content">
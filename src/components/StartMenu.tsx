@@ -3,7 +3,7 @@ import { invoke } from '@tauri-apps/api/core'
 import { useAppStore } from '../stores/appStore'
 import { buildClaudeCommand } from '../utils/buildClaudeCommand'
 import { MENU_ACTIONS } from '../types'
-import type { StartMenuItem, SafetyMode, SessionInfo, AppConfig, CustomActionDef } from '../types'
+import type { StartMenuItem, SafetyMode, SessionInfo, AppConfig, ActionTemplate, CustomActionDef } from '../types'
 
 const SAFETY_MODES: SafetyMode[] = ['observe', 'suggest', 'confirm', 'auto']
 const TAB_NAMES = ['Sessions', 'Actions', 'Settings']
@@ -13,14 +13,14 @@ const EFFORT_CYCLE: (string | null)[] = [null, 'low', 'medium', 'high']
 
 /** Build the focusable item list for a specific tab. Used by component and useGamepad. */
 export function buildStartMenuItemsForTab(tab: number): StartMenuItem[] {
-  const { sessions, activeSessionId, sessionStates, safetyMode, config } = useAppStore.getState()
+  const { sessions, activeSessionId, sessionStates, safetyMode, config, actionTemplates } = useAppStore.getState()
   const activeState = activeSessionId ? sessionStates[activeSessionId] : undefined
   const sessionEnded = activeState?.ended ?? false
   const claudeResumeId = activeState?.resumeId ?? null
 
   switch (tab) {
     case 0: return buildSessionsTab(sessions, sessionEnded, claudeResumeId)
-    case 1: return buildActionsTab(config)
+    case 1: return buildActionsTab(config, actionTemplates)
     case 2: return buildSettingsTab(safetyMode, config)
     default: return []
   }
@@ -57,7 +57,8 @@ function buildSessionsTab(
   return list
 }
 
-function buildActionsTab(config: AppConfig | null): StartMenuItem[] {
+function buildActionsTab(config: AppConfig | null, templates: ActionTemplate[]): StartMenuItem[] {
+  const templateMap = new Map(templates.map(t => [t.id, t]))
   const customActions = config?.custom_actions ?? []
   const overrideMap = new Map<string, CustomActionDef>()
   const newActions: CustomActionDef[] = []
@@ -82,18 +83,33 @@ function buildActionsTab(config: AppConfig | null): StartMenuItem[] {
         icon: override.icon ?? action.icon,
         actionId: action.id,
         customPrompt: override.prompt,
+        customSources: override.sources,
       }
     }
+    const template = templateMap.get(action.id)
     return {
       id: `action-${action.id}`,
       type: 'action' as const,
-      label: action.label,
-      sublabel: action.description,
-      icon: action.icon,
+      label: template?.label ?? action.label,
+      sublabel: template?.description ?? action.description,
+      icon: template?.icon ?? action.icon,
       actionId: action.id,
     }
   })
 
+  for (const template of templates) {
+    // config.yaml's custom_actions take precedence over files
+    if (template.builtin || customActions.some(ca => ca.id === template.id)) continue
+    items.push({
+      id: `action-${template.id}`,
+      type: 'action' as const,
+      label: template.label,
+      sublabel: template.description ?? '',
+      icon: template.icon ?? '>',
+      templateId: template.id,
+    })
+  }
+
   for (const ca of newActions) {
     items.push({
       id: `action-${ca.id}`,
@@ -102,6 +118,18 @@ function buildActionsTab(config: AppConfig | null): StartMenuItem[] {
       sublabel: ca.description ?? '',
       icon: ca.icon ?? '>',
       customPrompt: ca.prompt,
+      customSources: ca.sources,
+    })
+  }
+
+  for (const macro of config?.macros ?? []) {
+    items.push({
+      id: `macro-${macro.id}`,
+      type: 'action' as const,
+      label: macro.label,
+      sublabel: macro.description ?? `${macro.steps.length} steps`,
+      icon: macro.icon ?? '*',
+      macroId: macro.id,
     })
   }
 
@@ -176,6 +204,7 @@ export function StartMenu() {
     startMenuFocusIndex,
     startMenuTab,
     setStartMenuTab,
+    actionTemplates,
   } = useAppStore()
 
   const listRef = useRef<HTMLDivElement>(null)
@@ -187,11 +216,11 @@ export function StartMenu() {
   const items = useMemo(() => {
     switch (startMenuTab) {
       case 0: return buildSessionsTab(sessions, sessionEnded, claudeResumeId)
-      case 1: return buildActionsTab(config)
+      case 1: return buildActionsTab(config, actionTemplates)
       case 2: return buildSettingsTab(safetyMode, config)
       default: return []
     }
-  }, [startMenuTab, sessions, sessionEnded, claudeResumeId, safetyMode, config])
+  }, [startMenuTab, sessions, sessionEnded, claudeResumeId, safetyMode, config, actionTemplates])
 
   const actionsDisabled = !activeSessionId
 
@@ -314,16 +343,31 @@ export async function executeStartMenuItem(item: StartMenuItem) {
         } catch (e) {
           console.error('Failed to interrupt:', e)
         }
+      } else if (item.macroId) {
+        try {
+          await invoke('run_macro', { sessionId: state.activeSessionId, macroId: item.macroId })
+          state.showToast(`Running ${item.label}`)
+        } catch (e) {
+          state.showToast(`Macro failed: ${e}`)
+        }
       } else if (item.customPrompt) {
         try {
-          const prompt = await invoke<string>('build_custom_prompt', { template: item.customPrompt })
+          const prompt = await invoke<string>('build_custom_prompt', {
+            template: item.customPrompt,
+            sessionId: state.activeSessionId,
+            sources: item.customSources ?? null,
+            actionId: item.actionId ?? item.id.replace(/^action-/, ''),
+          })
           state.setDraftText(prompt)
         } catch (e) {
           console.error('Failed to build custom prompt:', e)
         }
-      } else if (item.actionId) {
+      } else if (item.actionId || item.templateId) {
         try {
-          const prompt = await invoke<string>('build_action_prompt', { action: item.actionId })
+          const prompt = await invoke<string>('build_action_prompt', {
+            action: item.templateId ? { custom: { id: item.templateId } } : item.actionId,
+            sessionId: state.activeSessionId,
+          })
           state.setDraftText(prompt)
         } catch (e) {
           console.error('Failed to build action prompt:', e)
@@ -2,7 +2,7 @@ import { useCallback } from 'react'
 import { invoke } from '@tauri-apps/api/core'
 import { listen } from '@tauri-apps/api/event'
 import { useAppStore } from '../stores/appStore'
-import type { SessionInfo } from '../types'
+import type { InterruptReport, MacroRun, SessionInfo } from '../types'
 
 let listenerSetup = false
 
@@ -50,6 +50,19 @@ export function useSession() {
       setSessionEnded(event.payload.session_id, true)
     })
 
+    await listen<{ session_id: string; message: string }>('macro-notify', (event) => {
+      useAppStore.getState().showToast(event.payload.message)
+    })
+
+    await listen<MacroRun>('macro-finished', (event) => {
+      const run = event.payload
+      if (run.status === 'failed') {
+        useAppStore.getState().showToast(`${run.label} failed: ${run.error ?? 'unknown error'}`)
+      } else if (run.status === 'completed') {
+        useAppStore.getState().showToast(`${run.label} done`)
+      }
+    })
+
     // Message sent acknowledgment
     await listen<{ session_id: string; message: string }>('session-message-sent', (_event) => {
       // Message was accepted by the session
@@ -127,8 +140,12 @@ export function useSession() {
   const interruptSession = useCallback(async () => {
     if (!activeSessionId) return
     try {
-      await invoke('interrupt_session', { sessionId: activeSessionId })
-      setBusy(false)
+      const report = await invoke<InterruptReport>('interrupt_session', { sessionId: activeSessionId })
+      if (report.succeeded) {
+        setBusy(false)
+      } else {
+        useAppStore.getState().showToast('Could not stop the agent')
+      }
     } catch (e) {
       console.error('Failed to interrupt session:', e)
     }
@@ -1,6 +1,6 @@
 import { create } from 'zustand'
 import type { Terminal } from '@xterm/xterm'
-import type { SessionInfo, SafetyMode, AppConfig, UIMode, DirEntry, WhisperModelInfo } from '../types'
+import type { SessionInfo, SafetyMode, AppConfig, ActionTemplate, UIMode, DirEntry, WhisperModelInfo } from '../types'
 
 interface SessionState {
   ended: boolean
@@ -75,6 +75,7 @@ interface AppStore {
 
   // Config
   config: AppConfig | null
+  actionTemplates: ActionTemplate[]
 
   // Toast
   toastMessage: string | null
@@ -119,6 +120,7 @@ interface AppStore {
   setRemapperFocusIndex: (index: number) => void
   setRemapperCaptureState: (state: { actionIndex: number; bindingType: 'keyboard' | 'gamepad' | null } | null) => void
   setConfig: (config: AppConfig) => void
+  setActionTemplates: (templates: ActionTemplate[]) => void
   showToast: (message: string) => void
   clearToast: () => void
   setDraftText: (text: string) => void
@@ -161,6 +163,7 @@ export const useAppStore = create<AppStore>((set, get) => ({
   remapperFocusIndex: 0,
   remapperCaptureState: null,
   config: null,
+  actionTemplates: [],
   toastMessage: null,
   toastTimerId: null,
   draftText: '',
@@ -237,6 +240,7 @@ export const useAppStore = create<AppStore>((set, get) => ({
   setRemapperFocusIndex: (index) => set({ remapperFocusIndex: index }),
   setRemapperCaptureState: (state) => set({ remapperCaptureState: state }),
   setConfig: (config) => set({ config, safetyMode: config.safety_mode }),
+  setActionTemplates: (templates) => set({ actionTemplates: templates }),
   showToast: (message: string) => {
     const prev = get().toastTimerId
     if (prev) clearTimeout(prev)
@@ -18,12 +18,89 @@ export interface DirEntry {
   is_dir: boolean
 }
 
+/** An action prompt from `~/.deckmind/actions/<id>.md` (or a built-in default). */
+export interface ActionTemplate {
+  id: string
+  label: string
+  icon: string | null
+  description: string | null
+  gamepad: string | null
+  sources: string[] | null
+  prompt: string
+  params: ActionParam[]
+  builtin: boolean
+}
+
+export type ActionParamType = 'text' | 'choice' | 'file' | 'recent_dir' | 'voice'
+
+export interface ActionParam {
+  name: string
+  label?: string | null
+  type: ActionParamType
+  options?: string[]
+  extensions?: string[]
+  default?: string | null
+  required?: boolean
+}
+
+/** A parameter as returned by get_action_params, with its choices listed. */
+export interface ParamField {
+  name: string
+  label: string
+  type: ActionParamType
+  default: string | null
+  required: boolean
+  options: string[]
+}
+
+export interface ActionForm {
+  action_id: string
+  label: string
+  params: ParamField[]
+}
+
+export interface MacroDef {
+  id: string
+  label: string
+  description?: string | null
+  icon?: string | null
+  steps: Record<string, unknown>[]
+  max_steps?: number
+}
+
+export type MacroStatus = 'running' | 'completed' | 'failed' | 'cancelled'
+
+export interface MacroRun {
+  session_id: string
+  macro_id: string
+  label: string
+  status: MacroStatus
+  started_at: string
+  finished_at: string | null
+  current: number | null
+  steps: { index: number; step: string; outcome: string }[]
+  error: string | null
+}
+
+/** An action or message sent to a session, from get_action_history. */
+export interface MemoryEntry {
+  id: string
+  timestamp: string
+  session_id: string
+  action: string
+  summary: string | null
+  response: string | null
+  checkpoint: string | null
+}
+
 export interface CustomActionDef {
   id: string
   label: string
   description?: string
   icon?: string
   prompt: string
+  sources?: string[]
+  params?: ActionParam[]
 }
 
 export interface StartMenuItem {
@@ -34,6 +111,10 @@ export interface StartMenuItem {
   sessionId?: string
   actionId?: SemanticAction
   customPrompt?: string
+  customSources?: string[]
+  /** Id of a file-defined action with no built-in counterpart. */
+  templateId?: string
+  macroId?: string
   icon?: string
   settingKey?: string
   value?: string
@@ -46,6 +127,24 @@ export interface SessionInfo {
   created_at: string
   is_active: boolean
   is_busy: boolean
+  tags: string[]
+  queued_prompts: number
+  worktree: ManagedWorktree | null
+  listening_ports: ListeningPort[]
+}
+
+export interface ListeningPort {
+  port: number
+  address: string
+  pid: number
+  command: string
+}
+
+export interface ManagedWorktree {
+  repo: string
+  path: string
+  branch: string
+  base_commit: string
 }
 
 export interface KeyBinding {
@@ -70,6 +169,82 @@ export interface AppConfig {
   voice_enabled: boolean
   theme: string
   custom_actions?: CustomActionDef[]
+  macros?: MacroDef[]
+  best_of_n_test_command?: string | null
+  worktree_root?: string | null
+  delete_worktrees_on_close?: boolean
+  worktree_pools?: WorktreePoolConfig[]
+  auto_checkpoint?: boolean
+  context_sources?: Record<string, ContextSourceConfig>
+  context_budget?: number
+  action_context_budgets?: Record<string, number>
+  redaction?: RedactionConfig
+  interrupt?: InterruptConfig
+}
+
+/** Milliseconds interrupt_session waits after each step before escalating. */
+export interface InterruptConfig {
+  escape_wait_ms?: number
+  ctrl_c_wait_ms?: number
+  sigint_wait_ms?: number
+  sigterm_wait_ms?: number
+  sigkill_wait_ms?: number
+}
+
+export type InterruptStep = 'escape' | 'ctrl_c' | 'sigint' | 'sigterm' | 'sigkill'
+
+export interface InterruptReport {
+  succeeded: InterruptStep | null
+  steps: { step: InterruptStep; skipped: string | null; stopped: boolean }[]
+}
+
+export interface RedactionConfig {
+  enabled?: boolean
+  patterns?: string[]
+}
+
+export interface ContextSourceConfig {
+  enabled?: boolean
+  timeout_ms?: number | null
+  order?: number | null
+}
+
+export type TestStatus = 'running' | 'passed' | 'failed' | 'timed_out' | 'cancelled' | 'error'
+
+export interface TestFailure {
+  name: string
+  file: string | null
+  line: number | null
+  message: string
+}
+
+export interface TestReport {
+  session_id: string
+  command: string
+  status: TestStatus
+  started_at: string
+  finished_at: string | null
+  exit_code: number | null
+  format: 'cargo_test' | 'libtest_json' | 'junit' | 'jest' | null
+  passed: number
+  failed: number
+  ignored: number
+  failures: TestFailure[]
+  output_tail: string
+}
+
+export interface TestProgress {
+  session_id: string
+  passed: number
+  failed: number
+  ignored: number
+  line: string
+}
+
+export interface WorktreePoolConfig {
+  repo: string
+  size: number
+  setup_command?: string | null
 }
 
 export interface WhisperModelInfo {
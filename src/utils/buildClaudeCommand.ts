@@ -4,6 +4,10 @@
  * Filters out launch-only flags (--worktree) when resuming or continuing,
  * since the worktree was already created on the initial launch.
  *
+ * The stored flags include --dangerously-skip-permissions when the session
+ * was launched with it; other agents (e.g. a best-of-N provider) may not
+ * understand it.
+ *
  * The invisible OSC sentinel (printf '\033]666;\007') is always appended
  * so the Rust reader thread can detect when Claude exits.
  */
@@ -12,7 +16,7 @@ export function buildClaudeCommand(
   storedFlags: string,
   opts?: { resumeId?: string; continue?: boolean },
 ): string {
-  let cmd = `${claudePath} --dangerously-skip-permissions`
+  let cmd = claudePath
 
   if (opts?.resumeId) {
     cmd += ` --resume ${opts.resumeId}`